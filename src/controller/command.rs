@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 use std::sync::mpsc;
 
-use crate::model::{TrackId, TrackType, ContainerId, MediaContent, EndpointId};
-use crate::tapestry::{TimePosition, Duration, Tempo, TimeSignature};
+use crate::model::{TrackId, TrackType, ContainerId, MediaContent, EndpointId, PreserveMode, Color, MarkerId};
+use crate::model::{PlaylistEntry, SectionId, SyncOutput};
+use crate::tapestry::{TimePosition, Duration, NoteValue, Quantizer, Tempo, TempoCurve, TimeSignature};
 use crate::engine::clock::ClockSourceType;
 
 /// Commands that can be sent to the controller
@@ -20,30 +21,60 @@ pub enum Command {
     SetTrackOutput { track_id: TrackId, output_id: Option<EndpointId> },
     MuteTrack { track_id: TrackId, muted: bool },
     SoloTrack { track_id: TrackId, solo: bool },
+    SetTrackChaseNotes { track_id: TrackId, enabled: bool },
 
     // Container commands
     AddContainer { track_id: TrackId, position: TimePosition, content: MediaContent },
     RemoveContainer { container_id: ContainerId },
     MoveContainer { container_id: ContainerId, new_position: TimePosition },
     ResizeContainer { container_id: ContainerId, new_length: Duration },
+    ResizeContainerMusical { container_id: ContainerId, length: NoteValue },
     SetContainerLoop { container_id: ContainerId, loop_count: Option<u32> },
     SetContainerTimeScale { container_id: ContainerId, time_scale: f64 },
+    /// Quantize the notes of the MIDI clip in a container
+    QuantizeClip { container_id: ContainerId, quantizer: Quantizer },
 
     // Timeline commands
-    SetTempo { position: TimePosition, tempo: Tempo },
-    SetTimeSignature { position: TimePosition, time_signature: TimeSignature },
+    SetTempo { position: TimePosition, tempo: Tempo, curve: TempoCurve, preserve: PreserveMode },
+    RemoveTempo { position: TimePosition, preserve: PreserveMode },
+    MoveTempo { from: TimePosition, to: TimePosition, preserve: PreserveMode },
+    SetTimeSignature { position: TimePosition, time_signature: TimeSignature, preserve: PreserveMode },
+    RemoveTimeSignature { position: TimePosition, preserve: PreserveMode },
+    MoveTimeSignature { from: TimePosition, to: TimePosition, preserve: PreserveMode },
+
+    // Marker commands
+    AddMarker { name: String, color: Color, position: TimePosition },
+    MoveMarker { marker_id: MarkerId, position: TimePosition },
+    RemoveMarker { marker_id: MarkerId },
+    SetLocator { number: u8, position: TimePosition },
+    RemoveLocator { number: u8 },
+    SetCycle { start: TimePosition, end: TimePosition },
+    SetCycleEnabled { enabled: bool },
+    ClearCycle,
+
+    // Arranger commands
+    AddSection { name: String, start: TimePosition, end: TimePosition },
+    RemoveSection { section_id: SectionId },
+    SetPlaylist { playlist: Vec<PlaylistEntry> },
+    SetArrangerEnabled { enabled: bool },
+    FlattenArrangement,
 
     // Transport commands
     Play,
     Stop,
     Pause,
     Seek { position: TimePosition },
+    SeekToMarker { marker_id: MarkerId },
+    SeekToLocator { number: u8 },
     Record { enabled: bool },
 
     // Output commands
     ScanOutputs,
     ConnectOutput { output_id: EndpointId },
     DisconnectOutput { output_id: EndpointId },
+    SetOutputSync { output_id: EndpointId, sync: SyncOutput },
+    /// Silence every output: All Notes Off and Reset All Controllers on every channel
+    Panic,
 
     // Clock commands
     SetClockSource { source_type: ClockSourceType },
@@ -101,6 +132,15 @@ impl CommandSender {
         })
     }
 
+    pub fn set_tempo(&self, position: TimePosition, tempo: Tempo) -> Result<(), mpsc::SendError<Command>> {
+        self.send(Command::SetTempo {
+            position,
+            tempo,
+            curve: TempoCurve::Step,
+            preserve: PreserveMode::FollowTimebase,
+        })
+    }
+
     pub fn shutdown(&self) -> Result<(), mpsc::SendError<Command>> {
         self.send(Command::Shutdown)
     }
@@ -5,10 +5,13 @@ use std::time::Duration as StdDuration;
 use crate::controller::command::{Command, CommandReceiver};
 use crate::controller::event::{Event, EventHub};
 use crate::controller::snapshot::{ProjectSnapshot, TimelineSnapshot};
+use crate::engine::clock::ClockSourceType;
 use crate::engine::playback::PlaybackEngine;
-use crate::model::{Project, TrackId, TrackType, ContainerId, MediaContent};
+use crate::model::{Project, TrackId, TrackType, ContainerId, PreserveMode};
+use crate::model::{Color, Marker, MarkerId, Timebase, Timeline};
+use crate::model::{ArrangerSection, EndpointId, PlaylistEntry, SectionId, SyncOutput};
 use crate::output::system::OutputSystem;
-use crate::tapestry::{TimePosition, Duration};
+use crate::tapestry::{TimePosition, Duration, NoteValue, Quantizer, TempoChange, TempoMap, TempoMapError, TimeSignature};
 
 /// Central controller for the application
 pub struct Controller {
@@ -79,13 +82,49 @@ impl Controller {
         match command {
             Command::CreateProject { name } => self.handle_create_project(name),
             Command::AddTrack { name, track_type } => self.handle_add_track(name, track_type),
+            Command::MuteTrack { track_id, muted } => self.handle_mute_track(track_id, muted),
+            Command::SoloTrack { track_id, solo } => self.handle_solo_track(track_id, solo),
+            Command::SetTrackChaseNotes { track_id, enabled } => self.handle_set_track_chase_notes(track_id, enabled),
             Command::MoveContainer { container_id, new_position } =>
                 self.handle_move_container(container_id, new_position),
             Command::ResizeContainer { container_id, new_length } =>
                 self.handle_resize_container(container_id, new_length),
+            Command::ResizeContainerMusical { container_id, length } =>
+                self.handle_resize_container_musical(container_id, length),
+            Command::QuantizeClip { container_id, quantizer } => self.handle_quantize_clip(container_id, quantizer),
+            Command::SetTempo { position, tempo, curve, preserve } =>
+                self.handle_set_tempo(position, TempoChange::new(tempo, curve), preserve),
+            Command::RemoveTempo { position, preserve } => self.handle_remove_tempo(position, preserve),
+            Command::MoveTempo { from, to, preserve } => self.handle_move_tempo(from, to, preserve),
+            Command::SetTimeSignature { position, time_signature, preserve } =>
+                self.handle_set_time_signature(position, time_signature, preserve),
+            Command::RemoveTimeSignature { position, preserve } =>
+                self.handle_remove_time_signature(position, preserve),
+            Command::MoveTimeSignature { from, to, preserve } =>
+                self.handle_move_time_signature(from, to, preserve),
             Command::Play => self.handle_play(),
             Command::Stop => self.handle_stop(),
+            Command::Pause => self.handle_pause(),
             Command::Seek { position } => self.handle_seek(position),
+            Command::SeekToMarker { marker_id } => self.handle_seek_to_marker(marker_id),
+            Command::SeekToLocator { number } => self.handle_seek_to_locator(number),
+            Command::AddMarker { name, color, position } => self.handle_add_marker(name, color, position),
+            Command::MoveMarker { marker_id, position } => self.handle_move_marker(marker_id, position),
+            Command::RemoveMarker { marker_id } => self.handle_remove_marker(marker_id),
+            Command::SetLocator { number, position } => self.handle_set_locator(number, position),
+            Command::RemoveLocator { number } => self.handle_remove_locator(number),
+            Command::SetCycle { start, end } => self.handle_set_cycle(start, end),
+            Command::SetCycleEnabled { enabled } => self.handle_set_cycle_enabled(enabled),
+            Command::ClearCycle => self.handle_clear_cycle(),
+            Command::AddSection { name, start, end } => self.handle_add_section(name, start, end),
+            Command::RemoveSection { section_id } => self.handle_remove_section(section_id),
+            Command::SetPlaylist { playlist } => self.handle_set_playlist(playlist),
+            Command::SetArrangerEnabled { enabled } => self.handle_set_arranger_enabled(enabled),
+            Command::FlattenArrangement => self.handle_flatten_arrangement(),
+            Command::DisconnectOutput { output_id } => self.handle_disconnect_output(output_id),
+            Command::SetOutputSync { output_id, sync } => self.handle_set_output_sync(output_id, sync),
+            Command::Panic => self.handle_panic(),
+            Command::SetClockSource { source_type } => self.handle_set_clock_source(source_type),
             Command::Shutdown => self.handle_shutdown(),
             // Handle other commands...
             _ => {
@@ -98,20 +137,16 @@ impl Controller {
 
     /// Create a project snapshot for the UI
     pub fn create_project_snapshot(&self) -> ProjectSnapshot {
+        // Before taking the project: a MIDI beat clock reads it to place its position,
+        // and a second read of the project would wait behind any queued writer
+        let playback_position = {
+            let engine = self.playback_engine.read().unwrap();
+            engine.is_playing().then(|| engine.current_position())
+        };
         let project = self.project.read().unwrap();
-        let engine = self.playback_engine.read().unwrap();
-
-        let active_timeline = if let Some(timeline) = project.active_timeline() {
-            let playback_position = if engine.is_playing() {
-                Some(engine.current_position())
-            } else {
-                None
-            };
 
-            Some(TimelineSnapshot::from_timeline(timeline, playback_position))
-        } else {
-            None
-        };
+        let active_timeline = project.active_timeline()
+            .map(|timeline| TimelineSnapshot::from_timeline(timeline, playback_position));
 
         let endpoints = project.endpoints.values()
             .map(|config| config.into())
@@ -161,11 +196,26 @@ impl Controller {
     }
 
     fn handle_move_container(&mut self, container_id: ContainerId, new_position: TimePosition) {
+        let mut new_position = new_position;
         let success = {
             let mut project = self.project.write().unwrap();
+            let project = &mut *project;
 
-            if let Some(timeline) = project.active_timeline_mut() {
-                timeline.move_container(container_id, new_position)
+            if let Some(timeline) = project.active_timeline_id.and_then(|id| project.timelines.get_mut(&id)) {
+                // Time-locked containers sit at a time, not on the grid
+                if project.settings.snap_to_grid && timeline.container_timebase(container_id) == Some(Timebase::Beats) {
+                    new_position = project.settings.quantizer().quantize_position(&new_position, &project.tempo_map);
+                }
+
+                if timeline.move_container(container_id, new_position) {
+                    // Tempo-locked containers change length when they move across tempo changes
+                    if let Some(container) = timeline.container_mut(container_id) {
+                        container.refresh_length(&project.tempo_map);
+                    }
+                    true
+                } else {
+                    false
+                }
             } else {
                 false
             }
@@ -182,10 +232,11 @@ impl Controller {
     fn handle_resize_container(&mut self, container_id: ContainerId, new_length: Duration) {
         let success = {
             let mut project = self.project.write().unwrap();
+            let project = &mut *project;
 
-            if let Some(timeline) = project.active_timeline_mut() {
+            if let Some(timeline) = project.active_timeline_id.and_then(|id| project.timelines.get_mut(&id)) {
                 if let Some(container) = timeline.container_mut(container_id) {
-                    container.length = new_length;
+                    container.set_length(new_length, &project.tempo_map);
                     true
                 } else {
                     false
@@ -203,6 +254,127 @@ impl Controller {
         }
     }
 
+    fn handle_resize_container_musical(&mut self, container_id: ContainerId, length: NoteValue) {
+        let new_length = {
+            let mut project = self.project.write().unwrap();
+            let project = &mut *project;
+
+            project.active_timeline_id
+                .and_then(|id| project.timelines.get_mut(&id))
+                .and_then(|timeline| timeline.container_mut(container_id))
+                .map(|container| {
+                    container.set_musical_length(length, &project.tempo_map);
+                    container.length
+                })
+        };
+
+        if let Some(length) = new_length {
+            self.event_hub.dispatch(Event::ContainerResized { container_id, length });
+        }
+    }
+
+    fn handle_quantize_clip(&mut self, container_id: ContainerId, quantizer: Quantizer) {
+        let result = self.project.write().unwrap().quantize_clip(container_id, &quantizer);
+
+        match result {
+            Ok(clip_id) => self.event_hub.dispatch(Event::MidiClipChanged { clip_id }),
+            Err(message) => self.event_hub.dispatch(Event::Error { message: message.to_string() }),
+        }
+    }
+
+    fn handle_set_tempo(&mut self, position: TimePosition, change: TempoChange, preserve: PreserveMode) {
+        let edited = self.edit_tempo_map(preserve, |map| {
+            map.set_tempo_change(position, change);
+            Ok(())
+        });
+
+        if edited {
+            self.event_hub.dispatch(Event::TempoChanged { position, tempo: change.tempo });
+        }
+    }
+
+    fn handle_remove_tempo(&mut self, position: TimePosition, preserve: PreserveMode) {
+        let edited = self.edit_tempo_map(preserve, |map| map.remove_tempo_change(position).map(|_| ()));
+
+        if edited {
+            self.event_hub.dispatch(Event::TempoRemoved { position });
+        }
+    }
+
+    fn handle_move_tempo(&mut self, from: TimePosition, to: TimePosition, preserve: PreserveMode) {
+        let mut tempo = None;
+        let edited = self.edit_tempo_map(preserve, |map| {
+            map.move_tempo_change(from, to)?;
+            tempo = map.tempo_change(&to).map(|change| change.tempo);
+            Ok(())
+        });
+
+        if let (true, Some(tempo)) = (edited, tempo) {
+            self.event_hub.dispatch(Event::TempoRemoved { position: from });
+            self.event_hub.dispatch(Event::TempoChanged { position: to, tempo });
+        }
+    }
+
+    fn handle_set_time_signature(&mut self, position: TimePosition, time_signature: TimeSignature, preserve: PreserveMode) {
+        let edited = self.edit_tempo_map(preserve, |map| {
+            map.add_time_signature_change(position, time_signature);
+            Ok(())
+        });
+
+        if edited {
+            self.event_hub.dispatch(Event::TimeSignatureChanged { position, time_signature });
+        }
+    }
+
+    fn handle_remove_time_signature(&mut self, position: TimePosition, preserve: PreserveMode) {
+        let edited = self.edit_tempo_map(preserve, |map| map.remove_time_signature_change(position).map(|_| ()));
+
+        if edited {
+            self.event_hub.dispatch(Event::TimeSignatureRemoved { position });
+        }
+    }
+
+    fn handle_move_time_signature(&mut self, from: TimePosition, to: TimePosition, preserve: PreserveMode) {
+        let mut time_signature = None;
+        let edited = self.edit_tempo_map(preserve, |map| {
+            map.move_time_signature_change(from, to)?;
+            time_signature = map.time_signature_change(&to).copied();
+            Ok(())
+        });
+
+        if let (true, Some(time_signature)) = (edited, time_signature) {
+            self.event_hub.dispatch(Event::TimeSignatureRemoved { position: from });
+            self.event_hub.dispatch(Event::TimeSignatureChanged { position: to, time_signature });
+        }
+    }
+
+    /// Apply a tempo map edit, reporting moved containers or the error
+    ///
+    /// Returns whether the edit was applied.
+    fn edit_tempo_map(
+        &mut self,
+        preserve: PreserveMode,
+        edit: impl FnOnce(&mut TempoMap) -> Result<(), TempoMapError>,
+    ) -> bool {
+        let result = {
+            let mut project = self.project.write().unwrap();
+            project.update_tempo_map(preserve, edit)
+        };
+
+        match result {
+            Ok(moved) => {
+                for (container_id, position) in moved {
+                    self.event_hub.dispatch(Event::ContainerMoved { container_id, position });
+                }
+                true
+            }
+            Err(error) => {
+                self.event_hub.dispatch(Event::Error { message: error.to_string() });
+                false
+            }
+        }
+    }
+
     fn handle_play(&mut self) {
         let mut engine = self.playback_engine.write().unwrap();
         engine.play();
@@ -217,6 +389,13 @@ impl Controller {
         self.event_hub.dispatch(Event::PlaybackStopped);
     }
 
+    fn handle_pause(&mut self) {
+        let mut engine = self.playback_engine.write().unwrap();
+        engine.pause();
+
+        self.event_hub.dispatch(Event::PlaybackPaused);
+    }
+
     fn handle_seek(&mut self, position: TimePosition) {
         let mut engine = self.playback_engine.write().unwrap();
         engine.seek(position);
@@ -224,7 +403,281 @@ impl Controller {
         self.event_hub.dispatch(Event::PlaybackPositionChanged { position });
     }
 
+    fn handle_seek_to_marker(&mut self, marker_id: MarkerId) {
+        let position = {
+            let project = self.project.read().unwrap();
+            project.active_timeline().and_then(|timeline| timeline.marker(marker_id)).map(|m| m.position)
+        };
+
+        match position {
+            Some(position) => self.handle_seek(position),
+            None => self.event_hub.dispatch(Event::Error { message: "Marker not found".to_string() }),
+        }
+    }
+
+    fn handle_seek_to_locator(&mut self, number: u8) {
+        let position = {
+            let project = self.project.read().unwrap();
+            project.active_timeline().and_then(|timeline| timeline.locator(number))
+        };
+
+        match position {
+            Some(position) => self.handle_seek(position),
+            None => self.event_hub.dispatch(Event::Error { message: format!("Locator {} is not set", number) }),
+        }
+    }
+
+    fn handle_add_marker(&mut self, name: String, color: Color, position: TimePosition) {
+        let marker = Marker::new(name, position).with_color(color);
+        let marker_id = self.with_active_timeline(|timeline| timeline.add_marker(marker));
+
+        if let Some(marker_id) = marker_id {
+            self.event_hub.dispatch(Event::MarkerAdded { marker_id, position });
+        }
+    }
+
+    fn handle_move_marker(&mut self, marker_id: MarkerId, position: TimePosition) {
+        let success = self.with_active_timeline(|timeline| timeline.move_marker(marker_id, position));
+
+        if success == Some(true) {
+            self.event_hub.dispatch(Event::MarkerMoved { marker_id, position });
+        }
+    }
+
+    fn handle_remove_marker(&mut self, marker_id: MarkerId) {
+        let removed = self.with_active_timeline(|timeline| timeline.remove_marker(marker_id));
+
+        if let Some(Some(_)) = removed {
+            self.event_hub.dispatch(Event::MarkerRemoved { marker_id });
+        }
+    }
+
+    fn handle_set_locator(&mut self, number: u8, position: TimePosition) {
+        let success = self.with_active_timeline(|timeline| timeline.set_locator(number, position));
+
+        if success.is_some() {
+            self.event_hub.dispatch(Event::LocatorSet { number, position });
+        }
+    }
+
+    fn handle_remove_locator(&mut self, number: u8) {
+        let removed = self.with_active_timeline(|timeline| timeline.remove_locator(number));
+
+        if let Some(Some(_)) = removed {
+            self.event_hub.dispatch(Event::LocatorRemoved { number });
+        }
+    }
+
+    fn handle_set_cycle(&mut self, start: TimePosition, end: TimePosition) {
+        let result = self.with_active_timeline(|timeline| timeline.set_cycle(start, end).map(|_| timeline.cycle));
+
+        match result {
+            Some(Ok(cycle)) => self.event_hub.dispatch(Event::CycleChanged { cycle }),
+            Some(Err(message)) => self.event_hub.dispatch(Event::Error { message: message.to_string() }),
+            None => {}
+        }
+    }
+
+    fn handle_set_cycle_enabled(&mut self, enabled: bool) {
+        let cycle = self.with_active_timeline(|timeline| {
+            timeline.set_cycle_enabled(enabled).then_some(timeline.cycle)
+        });
+
+        if let Some(Some(cycle)) = cycle {
+            self.event_hub.dispatch(Event::CycleChanged { cycle });
+        }
+    }
+
+    fn handle_clear_cycle(&mut self) {
+        if self.with_active_timeline(|timeline| timeline.clear_cycle()).is_some() {
+            self.event_hub.dispatch(Event::CycleChanged { cycle: None });
+        }
+    }
+
+    fn handle_add_section(&mut self, name: String, start: TimePosition, end: TimePosition) {
+        let Some(section) = ArrangerSection::new(name, start, end) else {
+            self.event_hub.dispatch(Event::Error { message: "Section end must be after its start".to_string() });
+            return;
+        };
+        let section_id = self.with_active_timeline(|timeline| timeline.arrangement.add_section(section));
+
+        if let Some(section_id) = section_id {
+            self.event_hub.dispatch(Event::SectionAdded { section_id });
+        }
+    }
+
+    fn handle_remove_section(&mut self, section_id: SectionId) {
+        let removed = self.with_active_timeline(|timeline| timeline.arrangement.remove_section(section_id));
+
+        if let Some(Some(_)) = removed {
+            self.event_hub.dispatch(Event::SectionRemoved { section_id });
+        }
+    }
+
+    fn handle_set_playlist(&mut self, playlist: Vec<PlaylistEntry>) {
+        let result = self.with_active_timeline(|timeline| timeline.arrangement.set_playlist(playlist));
+
+        match result {
+            Some(Ok(())) => self.event_hub.dispatch(Event::PlaylistChanged),
+            Some(Err(message)) => self.event_hub.dispatch(Event::Error { message: message.to_string() }),
+            None => {}
+        }
+    }
+
+    fn handle_set_arranger_enabled(&mut self, enabled: bool) {
+        let success = self.with_active_timeline(|timeline| timeline.arrangement.enabled = enabled);
+
+        if success.is_some() {
+            self.event_hub.dispatch(Event::ArrangerEnabledChanged { enabled });
+        }
+    }
+
+    fn handle_flatten_arrangement(&mut self) {
+        let result = self.project.write().unwrap().flatten_arrangement();
+
+        match result {
+            Ok(()) => self.event_hub.dispatch(Event::ArrangementFlattened),
+            Err(message) => self.event_hub.dispatch(Event::Error { message: message.to_string() }),
+        }
+    }
+
+    fn handle_mute_track(&mut self, track_id: TrackId, muted: bool) {
+        let found = self.with_active_timeline(|timeline| {
+            timeline.track_mut(track_id).map(|track| track.is_muted = muted).is_some()
+        });
+        if found != Some(true) {
+            return;
+        }
+
+        if muted {
+            // The track plays nothing more, so nothing of it may keep sounding
+            self.output_system.write().unwrap().release_track_notes(track_id);
+        }
+        self.event_hub.dispatch(Event::TrackMuteChanged { track_id, muted });
+    }
+
+    fn handle_solo_track(&mut self, track_id: TrackId, solo: bool) {
+        let silenced = self.with_active_timeline(|timeline| {
+            let audible: Vec<TrackId> = timeline.audible_tracks().map(|track| track.id).collect();
+            timeline.track_mut(track_id)?.is_solo = solo;
+            let silenced: Vec<TrackId> = audible.into_iter()
+                .filter(|id| !timeline.audible_tracks().any(|track| track.id == *id))
+                .collect();
+            Some(silenced)
+        }).flatten();
+        let Some(silenced) = silenced else {
+            return;
+        };
+
+        // Soloing silences every other track, so nothing of those may keep sounding
+        let mut output = self.output_system.write().unwrap();
+        for id in silenced {
+            output.release_track_notes(id);
+        }
+        drop(output);
+        self.event_hub.dispatch(Event::TrackSoloChanged { track_id, solo });
+    }
+
+    fn handle_set_track_chase_notes(&mut self, track_id: TrackId, enabled: bool) {
+        let found = self.with_active_timeline(|timeline| {
+            timeline.track_mut(track_id).map(|track| track.chase_notes = enabled).is_some()
+        });
+
+        if found == Some(true) {
+            self.event_hub.dispatch(Event::TrackChaseNotesChanged { track_id, enabled });
+        }
+    }
+
+    fn handle_set_clock_source(&mut self, source_type: ClockSourceType) {
+        let mut engine = self.playback_engine.write().unwrap();
+        // The clock manager reports the switch itself once the source is active
+        if let Err(message) = engine.select_clock_source(source_type) {
+            self.event_hub.dispatch(Event::Error { message: message.to_string() });
+        }
+    }
+
+    fn handle_set_output_sync(&mut self, output_id: EndpointId, sync: SyncOutput) {
+        let mut project = self.project.write().unwrap();
+        let Some(config) = project.endpoint_mut(output_id) else {
+            self.event_hub.dispatch(Event::Error { message: format!("Endpoint {} not found", output_id) });
+            return;
+        };
+        config.sync = sync;
+        project.version += 1;
+
+        self.event_hub.dispatch(Event::OutputSyncChanged { output_id, sync });
+    }
+
+    fn handle_disconnect_output(&mut self, output_id: EndpointId) {
+        if self.output_system.write().unwrap().disconnect_endpoint(output_id) {
+            self.event_hub.dispatch(Event::OutputDisconnected { output_id });
+        } else {
+            self.event_hub.dispatch(Event::Error { message: format!("Endpoint {} not found", output_id) });
+        }
+    }
+
+    fn handle_panic(&mut self) {
+        for result in self.output_system.write().unwrap().panic() {
+            if let Err(error) = result {
+                self.event_hub.dispatch(Event::Error { message: error.to_string() });
+            }
+        }
+    }
+
+    /// Run `f` on the active timeline, if there is one
+    ///
+    /// The project only counts as changed if what `f` returns says the edit was made.
+    fn with_active_timeline<R: TimelineEdit>(&self, f: impl FnOnce(&mut Timeline) -> R) -> Option<R> {
+        let mut project = self.project.write().unwrap();
+        let result = project.active_timeline_mut().map(f);
+        if result.as_ref().is_some_and(TimelineEdit::made) {
+            project.version += 1;
+        }
+        result
+    }
+
     fn handle_shutdown(&mut self) {
         self.running = false;
     }
-}
\ No newline at end of file
+}
+
+/// What an edit to a timeline returns, telling whether the edit was made
+trait TimelineEdit {
+    fn made(&self) -> bool;
+}
+
+impl TimelineEdit for () {
+    fn made(&self) -> bool {
+        true
+    }
+}
+
+impl TimelineEdit for bool {
+    fn made(&self) -> bool {
+        *self
+    }
+}
+
+impl<T> TimelineEdit for Option<T> {
+    fn made(&self) -> bool {
+        self.is_some()
+    }
+}
+
+impl<T, E> TimelineEdit for Result<T, E> {
+    fn made(&self) -> bool {
+        self.is_ok()
+    }
+}
+
+impl TimelineEdit for MarkerId {
+    fn made(&self) -> bool {
+        true
+    }
+}
+
+impl TimelineEdit for SectionId {
+    fn made(&self) -> bool {
+        true
+    }
+}
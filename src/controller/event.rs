@@ -1,8 +1,10 @@
 use std::path::PathBuf;
 use std::sync::mpsc;
 
-use crate::model::{ProjectId, TrackId, TrackType, ContainerId, EndpointId};
+use crate::model::{ProjectId, TrackId, TrackType, ContainerId, EndpointId, MarkerId, CycleRange, SectionId, SyncOutput};
+use crate::model::MidiClipId;
 use crate::tapestry::{TimePosition, Tempo, TimeSignature};
+use crate::engine::clock::ClockSourceType;
 
 /// Events that can be dispatched from the controller
 #[derive(Debug, Clone)]
@@ -20,6 +22,7 @@ pub enum Event {
     TrackOutputChanged { track_id: TrackId, output_id: Option<EndpointId> },
     TrackMuteChanged { track_id: TrackId, muted: bool },
     TrackSoloChanged { track_id: TrackId, solo: bool },
+    TrackChaseNotesChanged { track_id: TrackId, enabled: bool },
 
     // Container events
     ContainerAdded { container_id: ContainerId, track_id: TrackId },
@@ -27,16 +30,41 @@ pub enum Event {
     ContainerMoved { container_id: ContainerId, position: TimePosition },
     ContainerResized { container_id: ContainerId, length: crate::tapestry::Duration },
     ContainerLoopChanged { container_id: ContainerId, loop_count: Option<u32> },
+    MidiClipChanged { clip_id: MidiClipId },
 
     // Timeline events
     TempoChanged { position: TimePosition, tempo: Tempo },
+    TempoRemoved { position: TimePosition },
     TimeSignatureChanged { position: TimePosition, time_signature: TimeSignature },
+    TimeSignatureRemoved { position: TimePosition },
+
+    // Marker events
+    MarkerAdded { marker_id: MarkerId, position: TimePosition },
+    MarkerMoved { marker_id: MarkerId, position: TimePosition },
+    MarkerRemoved { marker_id: MarkerId },
+    LocatorSet { number: u8, position: TimePosition },
+    LocatorRemoved { number: u8 },
+    CycleChanged { cycle: Option<CycleRange> },
+
+    // Arranger events
+    SectionAdded { section_id: SectionId },
+    SectionRemoved { section_id: SectionId },
+    PlaylistChanged,
+    ArrangerEnabledChanged { enabled: bool },
+    ArrangementFlattened,
 
     // Playback events
     PlaybackStarted,
     PlaybackStopped,
     PlaybackPaused,
     PlaybackPositionChanged { position: TimePosition },
+    /// Tempo of the external master the transport is following
+    ClockTempoChanged { tempo: Tempo },
+
+    // Clock events
+    ClockSourceChanged { source_type: ClockSourceType },
+    ClockLocked { source_type: ClockSourceType },
+    ClockUnlocked { source_type: ClockSourceType },
     RecordingStarted,
     RecordingEnded,
 
@@ -44,6 +72,7 @@ pub enum Event {
     OutputsScanned,
     OutputConnected { output_id: EndpointId },
     OutputDisconnected { output_id: EndpointId },
+    OutputSyncChanged { output_id: EndpointId, sync: SyncOutput },
     OutputError { output_id: EndpointId, message: String },
 
     // UI events
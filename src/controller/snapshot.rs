@@ -2,7 +2,9 @@ use std::collections::HashMap;
 
 use crate::model::{
     TrackId, Track, ContainerId, MediaContainer,
-    Timeline, TimelineId, EndpointId, EndpointConfig
+    Timeline, TimelineId, EndpointId, EndpointConfig,
+    Marker, MarkerId, CycleRange,
+    ArrangerSection, SectionId, PlaylistEntry
 };
 use crate::tapestry::{TimePosition, Duration};
 
@@ -16,6 +18,7 @@ pub struct TrackSnapshot {
     pub is_solo: bool,
     pub output_id: Option<EndpointId>,
     pub height: u32,
+    pub chase_notes: bool,
 }
 
 impl From<&Track> for TrackSnapshot {
@@ -28,6 +31,7 @@ impl From<&Track> for TrackSnapshot {
             is_solo: track.is_solo,
             output_id: track.output_id,
             height: track.height,
+            chase_notes: track.chase_notes,
         }
     }
 }
@@ -67,6 +71,48 @@ impl From<&MediaContainer> for ContainerSnapshot {
     }
 }
 
+/// Snapshot of a marker for UI rendering
+#[derive(Debug, Clone)]
+pub struct MarkerSnapshot {
+    pub id: MarkerId,
+    pub name: String,
+    pub color: crate::model::Color,
+    pub position: TimePosition,
+}
+
+impl From<&Marker> for MarkerSnapshot {
+    fn from(marker: &Marker) -> Self {
+        Self {
+            id: marker.id,
+            name: marker.name.clone(),
+            color: marker.color,
+            position: marker.position,
+        }
+    }
+}
+
+/// Snapshot of an arranger section for UI rendering
+#[derive(Debug, Clone)]
+pub struct SectionSnapshot {
+    pub id: SectionId,
+    pub name: String,
+    pub color: crate::model::Color,
+    pub start: TimePosition,
+    pub end: TimePosition,
+}
+
+impl From<&ArrangerSection> for SectionSnapshot {
+    fn from(section: &ArrangerSection) -> Self {
+        Self {
+            id: section.id,
+            name: section.name.clone(),
+            color: section.color,
+            start: section.start,
+            end: section.end,
+        }
+    }
+}
+
 /// Snapshot of a timeline for UI rendering
 #[derive(Debug, Clone)]
 pub struct TimelineSnapshot {
@@ -75,6 +121,15 @@ pub struct TimelineSnapshot {
     pub tracks: Vec<TrackSnapshot>,
     pub containers: HashMap<TrackId, Vec<ContainerSnapshot>>,
     pub playback_position: Option<TimePosition>,
+    /// Markers ordered by position
+    pub markers: Vec<MarkerSnapshot>,
+    /// Numbered locators ordered by number
+    pub locators: Vec<(u8, TimePosition)>,
+    pub cycle: Option<CycleRange>,
+    /// Arranger sections ordered by start
+    pub sections: Vec<SectionSnapshot>,
+    pub playlist: Vec<PlaylistEntry>,
+    pub arranger_enabled: bool,
 }
 
 impl TimelineSnapshot {
@@ -101,12 +156,23 @@ impl TimelineSnapshot {
             containers.insert(track_id, track_containers);
         }
 
+        let mut sections: Vec<SectionSnapshot> = timeline.arrangement.sections.values()
+            .map(SectionSnapshot::from)
+            .collect();
+        sections.sort_by_key(|section| section.start);
+
         Self {
             id: timeline.id,
             name: timeline.name.clone(),
             tracks: timeline.tracks.iter().map(TrackSnapshot::from).collect(),
             containers,
             playback_position,
+            markers: timeline.markers_by_position().into_iter().map(MarkerSnapshot::from).collect(),
+            locators: timeline.locators.iter().map(|(number, position)| (*number, *position)).collect(),
+            cycle: timeline.cycle,
+            sections,
+            playlist: timeline.arrangement.playlist.clone(),
+            arranger_enabled: timeline.arrangement.enabled,
         }
     }
 }
@@ -119,6 +185,7 @@ pub struct EndpointSnapshot {
     pub device_id: String,
     pub endpoint_type: crate::model::EndpointType,
     pub enabled: bool,
+    pub sync: crate::model::SyncOutput,
 }
 
 impl From<&EndpointConfig> for EndpointSnapshot {
@@ -129,6 +196,7 @@ impl From<&EndpointConfig> for EndpointSnapshot {
             device_id: config.device_id.clone(),
             endpoint_type: config.endpoint_type,
             enabled: config.enabled,
+            sync: config.sync,
         }
     }
 }
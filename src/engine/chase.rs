@@ -0,0 +1,134 @@
+// src/engine/chase.rs
+use std::collections::BTreeMap;
+use crate::engine::clip::Placement;
+use crate::model::{ControlKind, EndpointId, MediaContent, Project, Track};
+use crate::output::{OutputEvent, OutputEventType};
+use crate::tapestry::{Duration, TimePosition};
+
+/// Bank select, which has to arrive before the program change it applies to
+const BANK_SELECT_MSB: u8 = 0;
+const BANK_SELECT_LSB: u8 = 32;
+
+/// What a track's output should be sounding at a position
+///
+/// Built by running through everything the track plays before the position: the
+/// latest program and controller values on each channel, pedals included, and the
+/// notes still held there.
+#[derive(Debug, Default)]
+pub struct ChaseState {
+    /// Program by channel
+    programs: BTreeMap<u8, u8>,
+    /// Value by channel and controller
+    controllers: BTreeMap<(u8, u8), u8>,
+    /// Velocity by channel and pitch
+    notes: BTreeMap<(u8, u8), u8>,
+}
+
+impl ChaseState {
+    /// State of `track` at `position` on the active timeline
+    pub fn for_track(project: &Project, track: &Track, position: TimePosition) -> Self {
+        let mut state = Self::default();
+        let Some(timeline) = project.active_timeline() else {
+            return state;
+        };
+        let Some(track_map) = timeline.track_containers.get(&track.id) else {
+            return state;
+        };
+
+        let tempo_map = &project.tempo_map;
+        let target = tempo_map.position_to_beats(&position);
+        let mut changes = Vec::new();
+
+        for container_id in track_map.iter().take_while(|(start, _)| *start < position).map(|(_, id)| id) {
+            let Some(container) = timeline.container(*container_id) else {
+                continue;
+            };
+            let MediaContent::MidiClip(clip_id) = &container.content else {
+                continue;
+            };
+            let Some(clip) = project.midi_clips.get(clip_id) else {
+                continue;
+            };
+
+            let placement = Placement::new(container, clip, tempo_map);
+            for control in &clip.controls {
+                for beat in placement.occurrences(control.start).take_while(|beat| *beat < target) {
+                    changes.push((beat, control.channel, control.kind));
+                }
+            }
+            for note in &clip.notes {
+                for beat in placement.occurrences(note.start).take_while(|beat| *beat < target) {
+                    if placement.note_end(beat, note.length) > target {
+                        state.notes.insert((note.channel, note.pitch), note.velocity);
+                    }
+                }
+            }
+        }
+
+        // Overlapping containers interleave, so apply changes in the order they play
+        changes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, channel, kind) in changes {
+            match kind {
+                ControlKind::Controller { controller, value } => {
+                    state.controllers.insert((channel, controller), value);
+                }
+                ControlKind::Program(program) => {
+                    state.programs.insert(channel, program);
+                }
+            }
+        }
+
+        state
+    }
+
+    /// Notes held at the position, as channel and pitch
+    pub fn held_notes(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.notes.keys().copied()
+    }
+
+    /// Messages recreating the state on `target`, re-triggering held notes if `notes`
+    pub fn events(&self, target: Option<EndpointId>, notes: bool) -> Vec<OutputEvent> {
+        let is_bank = |controller: u8| controller == BANK_SELECT_MSB || controller == BANK_SELECT_LSB;
+        let controllers = |bank: bool| self.controllers.iter()
+            .filter(move |((_, controller), _)| is_bank(*controller) == bank)
+            .map(move |(&(channel, controller), &value)| OutputEvent::midi_cc(channel, controller, value, target));
+
+        let mut events: Vec<_> = controllers(true).collect();
+        events.extend(self.programs.iter().map(|(&channel, &program)| {
+            OutputEvent::new(OutputEventType::MidiProgramChange { channel, program }, target)
+        }));
+        events.extend(controllers(false));
+
+        if notes {
+            events.extend(self.notes.iter().map(|(&(channel, pitch), &velocity)| {
+                OutputEvent::midi_note_on(channel, pitch, velocity, target)
+            }));
+        }
+        events
+    }
+}
+
+/// Messages bringing every track's output to its state at `position`
+///
+/// `position` is on the playhead, so it is followed through the arranger playlist.
+/// Tracks that are muted, silenced by a solo or have no output are skipped. Held notes
+/// are re-triggered only on tracks that chase notes, and only if `notes` is set; a
+/// note started while stopped would hang.
+pub fn chase(project: &Project, position: TimePosition, notes: bool) -> Vec<OutputEvent> {
+    let Some(timeline) = project.active_timeline() else {
+        return Vec::new();
+    };
+    let Some((position, _)) = timeline.playback_ranges(&position, &position.saturating_add(Duration::new(1))).first().copied() else {
+        return Vec::new();
+    };
+
+    timeline.audible_tracks()
+        .filter_map(|track| track.output_id.map(|output_id| (track, output_id)))
+        .flat_map(|(track, output_id)| {
+            ChaseState::for_track(project, track, position)
+                .events(Some(output_id), notes && track.chase_notes)
+                .into_iter()
+                .map(|event| event.with_source(track.id))
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+// src/engine/clip.rs
+use crate::model::{ControlKind, EndpointId, MediaContainer, MidiClip, PlaybackMode};
+use crate::output::{OutputEvent, OutputEventType};
+use crate::tapestry::TempoMap;
+
+/// Where a container plays its clip, in beats on the timeline
+pub(crate) struct Placement {
+    /// Where the container starts
+    start: f64,
+    /// Where the container ends; nothing plays from here on
+    end: f64,
+    /// Beats cropped from the start of the clip
+    offset: f64,
+    /// Beats of the clip in one pass, after the crop
+    pass: f64,
+    /// Passes played, or `None` to loop until the end of the container
+    passes: Option<u32>,
+    /// Beats of the clip played per beat on the timeline
+    scale: f64,
+    /// Ticks per beat in the clip
+    ppq: f64,
+}
+
+impl Placement {
+    pub(crate) fn new(container: &MediaContainer, clip: &MidiClip, tempo_map: &TempoMap) -> Self {
+        let ppq = clip.ppq as f64;
+        let offset = container.start_offset.to_beats(&container.position, tempo_map);
+        Self {
+            start: tempo_map.position_to_beats(&container.position),
+            end: tempo_map.position_to_beats(&container.position.saturating_add(container.length)),
+            offset,
+            pass: clip.length as f64 / ppq - offset,
+            passes: match container.playback_mode {
+                PlaybackMode::Loop => container.loop_count,
+                _ => Some(1),
+            },
+            scale: if container.time_scale > 0.0 { container.time_scale } else { 1.0 },
+            ppq,
+        }
+    }
+
+    /// Beats on the timeline where clip tick `tick` plays, in order
+    pub(crate) fn occurrences(&self, tick: u64) -> impl Iterator<Item = f64> + '_ {
+        self.occurrences_between(tick, f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// Beats in `from..to` on the timeline where clip tick `tick` plays, in order
+    ///
+    /// The first pass in range is worked out directly, less one for rounding, so a long
+    /// loop costs no more late in the container than early on.
+    pub(crate) fn occurrences_between(&self, tick: u64, from: f64, to: f64) -> impl Iterator<Item = f64> + '_ {
+        let beat = tick as f64 / self.ppq - self.offset;
+        let passes = if (0.0..self.pass).contains(&beat) {
+            self.passes.unwrap_or(u32::MAX)
+        } else {
+            0
+        };
+        let first = if passes == 0 {
+            0
+        } else {
+            ((((from - self.start) * self.scale - beat) / self.pass).ceil() - 1.0).clamp(0.0, passes as f64) as u32
+        };
+
+        (first..passes)
+            .map(move |pass| self.start + (pass as f64 * self.pass + beat) / self.scale)
+            .skip_while(move |at| *at < from)
+            .take_while(move |at| *at < to.min(self.end))
+    }
+
+    /// Beat on the timeline where a note of `length` ticks played at `on` stops
+    pub(crate) fn note_end(&self, on: f64, length: u64) -> f64 {
+        (on + self.note_length(length)).min(self.end)
+    }
+
+    /// Beats on the timeline a note of `length` ticks lasts, before the container cuts it
+    fn note_length(&self, length: u64) -> f64 {
+        length as f64 / self.ppq / self.scale
+    }
+}
+
+/// Messages `clip` sends from `container` between two beats on the timeline, each with
+/// the beat it is due at, in order
+///
+/// At the same beat, controller and program changes come first, then note-offs, then
+/// note-ons, so a note repeated back to back is not cut short. Notes without length
+/// are skipped, as their note-off would go out before them.
+pub fn clip_events(
+    container: &MediaContainer,
+    clip: &MidiClip,
+    tempo_map: &TempoMap,
+    from: f64,
+    to: f64,
+    target: Option<EndpointId>,
+) -> Vec<(f64, OutputEvent)> {
+    let placement = Placement::new(container, clip, tempo_map);
+    let mut events = Vec::new();
+
+    for control in &clip.controls {
+        let event_type = match control.kind {
+            ControlKind::Controller { controller, value } => {
+                OutputEventType::MidiControlChange { channel: control.channel, controller, value }
+            }
+            ControlKind::Program(program) => OutputEventType::MidiProgramChange { channel: control.channel, program },
+        };
+        for beat in placement.occurrences_between(control.start, from, to) {
+            events.push((beat, 0, OutputEvent::new(event_type.clone(), target)));
+        }
+    }
+
+    for note in clip.notes.iter().filter(|note| note.length > 0) {
+        // Notes started up to a note's length ago may stop in range
+        let earliest = from - placement.note_length(note.length);
+        for on in placement.occurrences_between(note.start, earliest, to) {
+            let off = placement.note_end(on, note.length);
+            if (from..to).contains(&off) {
+                events.push((off, 1, OutputEvent::midi_note_off(note.channel, note.pitch, target)));
+            }
+            if on >= from {
+                events.push((on, 2, OutputEvent::midi_note_on(note.channel, note.pitch, note.velocity, target)));
+            }
+        }
+    }
+
+    events.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    events.into_iter().map(|(beat, _, event)| (beat, event)).collect()
+}
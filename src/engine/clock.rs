@@ -1,56 +1,207 @@
 // In src/engine/clock.rs
-use crate::tapestry::TimePosition;
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::tapestry::{Tempo, TimePosition};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ClockSourceType {
     Internal,
     Mtc,
     Ltc,
+    MidiClock,
     // Other timing sources
 }
 
-pub trait ClockSource {
+/// How well a clock is following an external master
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    /// No running timecode
+    Unlocked,
+    /// Timecode is arriving but has not yet been confirmed
+    Locking,
+    /// Following running timecode
+    Locked,
+    /// Timecode dropped out; carrying on at the last known speed
+    Freewheeling,
+}
+
+/// Source of the transport position
+///
+/// Clocks are shared between the playback thread and the controller, so transport
+/// methods take `&self`. Clocks that follow an external master ignore them.
+pub trait ClockSource: Send + Sync {
     fn current_time(&self) -> TimePosition;
     fn sample_rate(&self) -> u32;
     fn is_running(&self) -> bool;
+
+    /// Start running from the current position
+    fn start(&self) {}
+
+    /// Stop running, holding the current position
+    fn pause(&self) {}
+
+    /// Stop running and return to the start
+    fn stop(&self) {
+        self.pause();
+        self.locate(TimePosition::zero());
+    }
+
+    /// Jump to a position, carrying on running if already running
+    fn locate(&self, _position: TimePosition) {}
+
+    /// Whether the transport is run by an external master rather than by us
+    fn follows_master(&self) -> bool {
+        false
+    }
+
+    /// Tempo set by the master, for clocks that follow one
+    fn tempo(&self) -> Option<Tempo> {
+        None
+    }
+
+    /// How fast the position runs against real time, 1.0 being normal speed
+    ///
+    /// Events rendered ahead of the playhead are timed with it, so a master running
+    /// faster or slower than the positions it reports says so here.
+    fn speed(&self) -> f64 {
+        1.0
+    }
+
+    /// How well the clock is following its master; clocks without one are always locked
+    fn lock_state(&self) -> LockState {
+        LockState::Locked
+    }
 }
 
+/// Free-running clock driven by the system's monotonic clock
 pub struct InternalClock {
-    pub start_time: Option<std::time::Instant>,
-    pub sample_rate: u32,
-    // Other internal clock state
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    state: Mutex<InternalClockState>,
+}
+
+/// Position at the last transport change, and when it happened if running
+struct InternalClockState {
+    anchor_position: TimePosition,
+    anchor_instant: Option<Instant>,
+}
+
+impl InternalClock {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            state: Mutex::new(InternalClockState {
+                anchor_position: TimePosition::zero(),
+                anchor_instant: None,
+            }),
+        }
+    }
+
+    fn position_in(&self, state: &InternalClockState) -> TimePosition {
+        match state.anchor_instant {
+            Some(instant) => {
+                let elapsed = instant.elapsed().as_secs_f64();
+                let ticks = (elapsed * self.sample_rate as f64).round() as u64;
+                TimePosition::new(state.anchor_position.position_ticks.saturating_add(ticks))
+            }
+            None => state.anchor_position,
+        }
+    }
 }
 
 impl ClockSource for InternalClock {
     fn current_time(&self) -> TimePosition {
-        todo!()
+        let state = self.state.lock().unwrap();
+        self.position_in(&state)
     }
 
     fn sample_rate(&self) -> u32 {
-        todo!()
+        self.sample_rate
     }
 
     fn is_running(&self) -> bool {
-        todo!()
+        self.state.lock().unwrap().anchor_instant.is_some()
+    }
+
+    fn start(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.anchor_instant.is_none() {
+            state.anchor_instant = Some(Instant::now());
+        }
     }
-    // Implementation using system clock
+
+    fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.anchor_position = self.position_in(&state);
+        state.anchor_instant = None;
+    }
+
+    fn locate(&self, position: TimePosition) {
+        let mut state = self.state.lock().unwrap();
+        state.anchor_position = position;
+        if state.anchor_instant.is_some() {
+            state.anchor_instant = Some(Instant::now());
+        }
+    }
+}
+
+/// Clock that only moves when told to
+///
+/// Nothing about it depends on the system clock, so rendering offline and tests get
+/// exactly the same positions on every run.
+pub struct ManualClock {
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    state: Mutex<ManualClockState>,
 }
 
-pub struct MtcClock {
-    // MTC sync state
+struct ManualClockState {
+    position: TimePosition,
+    running: bool,
 }
 
-impl ClockSource for MtcClock {
+impl ManualClock {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            state: Mutex::new(ManualClockState {
+                position: TimePosition::zero(),
+                running: false,
+            }),
+        }
+    }
+
+    /// Move forward by `ticks` if running
+    pub fn advance(&self, ticks: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.running {
+            state.position = TimePosition::new(state.position.position_ticks.saturating_add(ticks));
+        }
+    }
+}
+
+impl ClockSource for ManualClock {
     fn current_time(&self) -> TimePosition {
-        todo!()
+        self.state.lock().unwrap().position
     }
 
     fn sample_rate(&self) -> u32 {
-        todo!()
+        self.sample_rate
     }
 
     fn is_running(&self) -> bool {
-        todo!()
+        self.state.lock().unwrap().running
+    }
+
+    fn start(&self) {
+        self.state.lock().unwrap().running = true;
     }
-    // Implementation using MTC
-}
\ No newline at end of file
+
+    fn pause(&self) {
+        self.state.lock().unwrap().running = false;
+    }
+
+    fn locate(&self, position: TimePosition) {
+        self.state.lock().unwrap().position = position;
+    }
+}
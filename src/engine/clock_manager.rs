@@ -1,12 +1,252 @@
 // In src/engine/clock_manager.rs
-use crate::engine::ClockSource;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::controller::event::{Event, EventSender};
+use crate::engine::{ClockSource, ClockSourceType, InternalClock, LockState};
+use crate::tapestry::{Tempo, TimePosition};
 
+/// Largest difference between two sources that a switch glides out, in seconds;
+/// anything larger is a relocation
+const MAX_GLIDE_SECS: f64 = 0.1;
+
+/// Registered clock sources, one of which drives the transport
+///
+/// The manager is itself a `ClockSource`, so the playback engine never sees a switch.
+/// A source is selected by type but only becomes active once it is locked; until then,
+/// and whenever an external source loses lock, the internal clock carries on from
+/// where the transport was, so a dropped feed never stops the show. When the selected
+/// source locks again the transport goes back to it.
+///
+/// Switching keeps the position continuous: the internal clock is located to where
+/// the old source was, and a small difference to an external source is glided out.
 pub struct ClockManager {
-    available_sources: Vec<Box<dyn ClockSource>>,
-    active_source: usize,
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    /// How long a switch takes to glide out a difference in position
+    glide: Duration,
+    event_sender: EventSender,
+    state: Mutex<ManagerState>,
+}
+
+struct ManagerState {
+    sources: HashMap<ClockSourceType, Arc<dyn ClockSource>>,
+    /// The source the user asked for
+    selected: ClockSourceType,
+    /// The source driving the transport: the selected one, or internal as a fallback
+    active: ClockSourceType,
+    /// Lock state of each source when last polled
+    lock_states: HashMap<ClockSourceType, LockState>,
+    /// Difference in ticks still to glide out, and when the glide started
+    glide: Option<(f64, Instant)>,
+    /// Whether the transport was running when last polled
+    was_running: bool,
 }
 
 impl ClockManager {
-    todo!();
-    // Methods to manage and switch between clock sources
-}
\ No newline at end of file
+    /// A manager with only the internal clock, which is selected
+    pub fn new(sample_rate: u32, event_sender: EventSender) -> Self {
+        let internal: Arc<dyn ClockSource> = Arc::new(InternalClock::new(sample_rate));
+        Self {
+            sample_rate,
+            glide: Duration::from_secs(1),
+            event_sender,
+            state: Mutex::new(ManagerState {
+                sources: HashMap::from([(ClockSourceType::Internal, internal)]),
+                selected: ClockSourceType::Internal,
+                active: ClockSourceType::Internal,
+                lock_states: HashMap::new(),
+                glide: None,
+                was_running: false,
+            }),
+        }
+    }
+
+    /// Set how long a switch takes to glide out a difference in position
+    pub fn with_glide(mut self, glide: Duration) -> Self {
+        self.glide = glide;
+        self
+    }
+
+    /// Register a source, replacing any of the same type
+    pub fn register(&self, source_type: ClockSourceType, source: Arc<dyn ClockSource>) {
+        let mut state = self.state.lock().unwrap();
+        state.sources.insert(source_type, source);
+        state.lock_states.remove(&source_type);
+        if state.active == source_type && source_type != ClockSourceType::Internal {
+            // The old source is gone; carry on internally until the new one locks
+            self.switch(&mut state, ClockSourceType::Internal);
+        }
+    }
+
+    /// Select the source to follow
+    ///
+    /// An external source that is not yet locked takes over once it locks.
+    pub fn select(&self, source_type: ClockSourceType) -> Result<(), &'static str> {
+        let mut state = self.state.lock().unwrap();
+        let Some(source) = state.sources.get(&source_type) else {
+            return Err("Clock source is not registered");
+        };
+        let locked = source.lock_state() == LockState::Locked;
+
+        state.selected = source_type;
+        let target = if locked {
+            source_type
+        } else {
+            ClockSourceType::Internal
+        };
+        if target != state.active {
+            self.switch(&mut state, target);
+        }
+        Ok(())
+    }
+
+    /// The source the user asked for
+    pub fn selected(&self) -> ClockSourceType {
+        self.state.lock().unwrap().selected
+    }
+
+    /// The source driving the transport
+    pub fn active(&self) -> ClockSourceType {
+        self.state.lock().unwrap().active
+    }
+
+    /// Check every source for changes in lock, failing over or back as needed
+    ///
+    /// Called whenever the position is read, so the playback thread keeps it current.
+    pub fn poll(&self) {
+        let mut state = self.state.lock().unwrap();
+        self.poll_in(&mut state);
+    }
+
+    fn poll_in(&self, state: &mut ManagerState) {
+        let changes: Vec<_> = state.sources.iter()
+            .filter(|(source_type, _)| **source_type != ClockSourceType::Internal)
+            .map(|(source_type, source)| (*source_type, source.lock_state()))
+            .filter(|(source_type, lock)| state.lock_states.get(source_type) != Some(lock))
+            .collect();
+
+        for (source_type, lock) in changes {
+            let previous = state.lock_states.insert(source_type, lock);
+            match lock {
+                LockState::Locked => {
+                    let _ = self.event_sender.send(Event::ClockLocked { source_type });
+                }
+                LockState::Unlocked if previous.is_some() => {
+                    let _ = self.event_sender.send(Event::ClockUnlocked { source_type });
+                }
+                _ => {}
+            }
+        }
+
+        let active_lock = state.lock_states.get(&state.active).copied().unwrap_or(LockState::Locked);
+        let selected_lock = state.lock_states.get(&state.selected).copied().unwrap_or(LockState::Locked);
+        if state.active != ClockSourceType::Internal && active_lock == LockState::Unlocked {
+            self.switch(state, ClockSourceType::Internal);
+        } else if state.active != state.selected && selected_lock == LockState::Locked {
+            let selected = state.selected;
+            self.switch(state, selected);
+        }
+
+        state.was_running = self.active_source(state).is_running();
+    }
+
+    fn switch(&self, state: &mut ManagerState, to: ClockSourceType) {
+        let position = self.position_in(state);
+        let running = state.was_running || self.active_source(state).is_running();
+        state.active = to;
+        state.glide = None;
+
+        let source = self.active_source(state);
+        if to == ClockSourceType::Internal {
+            // Pick up exactly where the old source was, running if it was
+            source.locate(position);
+            if running {
+                source.start();
+            } else {
+                source.pause();
+            }
+        } else {
+            let difference = position.position_ticks as f64 - source.current_time().position_ticks as f64;
+            if difference.abs() <= MAX_GLIDE_SECS * self.sample_rate as f64 {
+                state.glide = Some((difference, Instant::now()));
+            }
+        }
+
+        let _ = self.event_sender.send(Event::ClockSourceChanged { source_type: to });
+    }
+
+    fn active_source(&self, state: &ManagerState) -> Arc<dyn ClockSource> {
+        Arc::clone(&state.sources[&state.active])
+    }
+
+    fn position_in(&self, state: &ManagerState) -> TimePosition {
+        let position = self.active_source(state).current_time();
+        let Some((difference, started)) = state.glide else {
+            return position;
+        };
+
+        let progress = started.elapsed().as_secs_f64() / self.glide.as_secs_f64().max(f64::EPSILON);
+        let remaining = difference * (1.0 - progress).max(0.0);
+        TimePosition::new((position.position_ticks as f64 + remaining).round().max(0.0) as u64)
+    }
+}
+
+impl ClockSource for ClockManager {
+    fn current_time(&self) -> TimePosition {
+        let mut state = self.state.lock().unwrap();
+        self.poll_in(&mut state);
+        self.position_in(&state)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_running(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).is_running()
+    }
+
+    fn start(&self) {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).start();
+    }
+
+    fn pause(&self) {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).pause();
+    }
+
+    fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.glide = None;
+        self.active_source(&state).stop();
+    }
+
+    fn locate(&self, position: TimePosition) {
+        let mut state = self.state.lock().unwrap();
+        state.glide = None;
+        self.active_source(&state).locate(position);
+    }
+
+    fn follows_master(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.sources[&state.selected].follows_master()
+    }
+
+    fn tempo(&self) -> Option<Tempo> {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).tempo()
+    }
+
+    fn speed(&self) -> f64 {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).speed()
+    }
+
+    fn lock_state(&self) -> LockState {
+        let state = self.state.lock().unwrap();
+        self.active_source(&state).lock_state()
+    }
+}
@@ -0,0 +1,469 @@
+// src/engine/ltc.rs
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::engine::clock::{ClockSource, LockState};
+use crate::tapestry::{FrameRate, TimePosition, Timecode};
+
+/// Bits in one LTC frame
+const FRAME_BITS: u32 = 80;
+const FRAME_MASK: u128 = (1 << FRAME_BITS) - 1;
+
+/// Sync word as the last 16 bits received when reading forwards
+const SYNC_FORWARD: u128 = 0x3FFD;
+/// Sync word as the first 16 bits received when reading backwards
+const SYNC_REVERSE: u128 = 0xBFFC;
+
+/// Fraction of the signal peak a sample must pass to count as a transition
+const HYSTERESIS: f32 = 0.25;
+/// Per-sample decay of the peak level, so the threshold follows level changes
+const PEAK_DECAY: f32 = 0.9995;
+
+/// Frames in a row at steady speed needed to measure the frame rate
+const RATE_RUN_FRAMES: u64 = 24;
+
+/// Consecutive frames needed before reporting lock
+const FRAMES_TO_LOCK: u32 = 4;
+
+/// Rates LTC can carry without the drop-frame flag, slowest first
+const NON_DROP_RATES: [FrameRate; 5] = [
+    FrameRate::Fps23_976,
+    FrameRate::Fps24,
+    FrameRate::Fps25,
+    FrameRate::Fps29_97NonDrop,
+    FrameRate::Fps30,
+];
+
+/// One frame of LTC read from an audio signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LtcFrame {
+    pub timecode: Timecode,
+
+    /// The eight user-bit groups, group 1 in the lowest nibble
+    pub user_bits: u32,
+
+    /// Whether the frame was read backwards
+    pub reverse: bool,
+
+    /// Index of the sample in the buffer at which the frame ended
+    pub offset: usize,
+}
+
+/// Decoder for SMPTE linear timecode carried as audio
+///
+/// LTC is biphase-mark coded: every bit cell starts with a transition, and a 1 has
+/// another in the middle. The decoder measures the time between transitions, keeping
+/// a running estimate of the bit length so it follows varispeed, and looks for the
+/// sync word at either end of the last 80 bits to read in both directions.
+pub struct LtcDecoder {
+    /// Sample rate of the incoming audio
+    input_rate: u32,
+
+    /// Current side of the hysteresis band
+    high: bool,
+    peak: f32,
+    samples_since_transition: u64,
+
+    /// Estimated length of one bit cell in samples
+    bit_period: f64,
+    /// A half-length cell was seen and awaits its pair
+    half_pending: bool,
+
+    /// The last bits received, newest in bit 0
+    bits: u128,
+    bit_count: u32,
+
+    /// Samples decoded so far
+    sample_clock: u64,
+    /// End of the last frame, and whether it was read backwards
+    last_frame: Option<(u64, bool)>,
+    /// Where the current run of back-to-back frames started, and its length
+    run_start: u64,
+    run_frames: u64,
+
+    /// Highest frame label seen, which rules out slower rates
+    max_label: u8,
+    rate: Option<FrameRate>,
+}
+
+impl LtcDecoder {
+    pub fn new(input_rate: u32) -> Self {
+        Self {
+            input_rate,
+            high: false,
+            peak: 0.0,
+            samples_since_transition: 0,
+            bit_period: Self::default_bit_period(input_rate),
+            half_pending: false,
+            bits: 0,
+            bit_count: 0,
+            sample_clock: 0,
+            last_frame: None,
+            run_start: 0,
+            run_frames: 0,
+            max_label: 0,
+            rate: None,
+        }
+    }
+
+    /// Frame rate of the signal, once a frame has been read
+    ///
+    /// The rate is measured while the signal plays at normal speed; the frame labels
+    /// and the drop-frame flag rule out rates that cannot carry them.
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.rate
+    }
+
+    /// Playing speed of the signal relative to normal, from the current bit length
+    pub fn speed(&self) -> f64 {
+        let fps = self.rate.map_or(25.0, |rate| rate.fps());
+        self.input_rate as f64 / (FRAME_BITS as f64 * fps) / self.bit_period
+    }
+
+    /// Decode a buffer of mono samples, returning the frames that ended in it
+    pub fn decode(&mut self, samples: &[f32]) -> Vec<LtcFrame> {
+        let mut frames = Vec::new();
+
+        for (offset, &sample) in samples.iter().enumerate() {
+            self.sample_clock += 1;
+            self.samples_since_transition += 1;
+            self.peak = (self.peak * PEAK_DECAY).max(sample.abs());
+
+            let threshold = self.peak * HYSTERESIS;
+            let high = if sample > threshold {
+                true
+            } else if sample < -threshold {
+                false
+            } else {
+                continue;
+            };
+            if high == self.high {
+                continue;
+            }
+            self.high = high;
+
+            let interval = self.samples_since_transition as f64;
+            self.samples_since_transition = 0;
+            if let Some(frame) = self.cell(interval).and_then(|bit| self.push_bit(bit, offset)) {
+                frames.push(frame);
+            }
+        }
+
+        frames
+    }
+
+    /// Bit length at normal speed for the middle of the range of rates
+    fn default_bit_period(input_rate: u32) -> f64 {
+        input_rate as f64 / (FRAME_BITS as f64 * 25.0)
+    }
+
+    /// Turn the time between two transitions into a bit, if it completes one
+    fn cell(&mut self, interval: f64) -> Option<bool> {
+        if interval > self.bit_period * 2.5 {
+            // Far too long for a bit: either the signal slowed right down or it went
+            // quiet. Start again from the new length, or from normal speed after silence.
+            self.half_pending = false;
+            self.bit_period = if interval > self.input_rate as f64 / 10.0 {
+                Self::default_bit_period(self.input_rate)
+            } else {
+                interval
+            };
+            return None;
+        }
+
+        if interval < self.bit_period * 0.75 {
+            self.bit_period += (interval * 2.0 - self.bit_period) / 4.0;
+            self.half_pending = !self.half_pending;
+            // The second half of a 1
+            (!self.half_pending).then_some(true)
+        } else {
+            self.bit_period += (interval - self.bit_period) / 4.0;
+            // A half cell without its pair is dropped
+            self.half_pending = false;
+            Some(false)
+        }
+    }
+
+    fn push_bit(&mut self, bit: bool, offset: usize) -> Option<LtcFrame> {
+        self.bits = ((self.bits << 1) | bit as u128) & FRAME_MASK;
+        self.bit_count = (self.bit_count + 1).min(FRAME_BITS);
+        if self.bit_count < FRAME_BITS {
+            return None;
+        }
+
+        let reverse = if self.bits & 0xFFFF == SYNC_FORWARD {
+            false
+        } else if self.bits >> 64 == SYNC_REVERSE {
+            true
+        } else {
+            return None;
+        };
+        self.bit_count = 0;
+
+        let bits = self.bits;
+        let bit = |index: u32| {
+            // Forwards, bit 0 of the frame arrived first; backwards, it arrived last
+            let shift = if reverse { index } else { FRAME_BITS - 1 - index };
+            ((bits >> shift) & 1) as u8
+        };
+        let field = |first: u32, width: u32| {
+            (0..width).fold(0u8, |value, i| value | (bit(first + i) << i))
+        };
+        let bcd = |units: u8, tens: u8| (units < 10).then_some(tens * 10 + units);
+
+        let frames = bcd(field(0, 4), field(8, 2))?;
+        let drop_frame = bit(10) == 1;
+        let seconds = bcd(field(16, 4), field(24, 3))?;
+        let minutes = bcd(field(32, 4), field(40, 3))?;
+        let hours = bcd(field(48, 4), field(56, 2))?;
+        let user_bits = (0..8).fold(0u32, |value, group| {
+            value | (field(4 + group * 8, 4) as u32) << (group * 4)
+        });
+
+        self.track_run(reverse);
+        self.max_label = self.max_label.max(frames);
+        let rate = self.detect_rate(drop_frame);
+        let timecode = Timecode::new(hours, minutes, seconds, frames, rate).ok()?;
+
+        Some(LtcFrame { timecode, user_bits, reverse, offset })
+    }
+
+    /// Count frames arriving back to back in one direction
+    fn track_run(&mut self, reverse: bool) {
+        let now = self.sample_clock;
+        // Allow for 24 fps at a little under normal speed
+        let longest_frame = self.input_rate as u64 / 20;
+        let continues = matches!(
+            self.last_frame,
+            Some((last, last_reverse)) if last_reverse == reverse && now - last <= longest_frame
+        );
+
+        if continues {
+            self.run_frames += 1;
+        } else {
+            self.run_start = now;
+            self.run_frames = 0;
+        }
+        self.last_frame = Some((now, reverse));
+    }
+
+    fn detect_rate(&mut self, drop_frame: bool) -> FrameRate {
+        if drop_frame {
+            self.rate = Some(FrameRate::Fps29_97Drop);
+            return FrameRate::Fps29_97Drop;
+        }
+
+        let labels_fit = self.rate.is_some_and(|rate| {
+            !rate.is_drop_frame() && self.max_label < rate.frames_per_second() as u8
+        });
+        let measured = if self.run_frames >= RATE_RUN_FRAMES {
+            // Frames per second over the whole run
+            let samples = (self.sample_clock - self.run_start) as f64;
+            Some(self.run_frames as f64 * self.input_rate as f64 / samples)
+        } else if !labels_fit {
+            // Not enough to go on yet, so guess from the bit length
+            Some(self.input_rate as f64 / (FRAME_BITS as f64 * self.bit_period))
+        } else {
+            None
+        };
+
+        if let Some(fps) = measured {
+            let rate = NON_DROP_RATES.into_iter()
+                .filter(|rate| self.max_label < rate.frames_per_second() as u8)
+                .min_by(|a, b| (a.fps() - fps).abs().total_cmp(&(b.fps() - fps).abs()))
+                .unwrap_or(FrameRate::Fps30);
+            self.rate = Some(rate);
+        }
+
+        self.rate.unwrap_or(FrameRate::Fps30)
+    }
+}
+
+/// Clock that chases LTC read from an audio input
+///
+/// Feed it the sample buffers from the input with `receive`. Each frame is placed at
+/// the sample where it ended: forwards that is the start of the next frame, backwards
+/// the start of the frame itself. Between frames the position runs on at the speed
+/// measured from the signal, in whichever direction it is playing.
+pub struct LtcClock {
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    /// Sample rate of the incoming audio
+    input_rate: u32,
+    /// Channel of interleaved buffers that carries the timecode
+    channel: usize,
+    /// Timecode at timeline position zero
+    start: Timecode,
+    /// How long to keep running after timecode stops arriving
+    freewheel: Duration,
+    state: Mutex<LtcState>,
+}
+
+struct LtcState {
+    decoder: LtcDecoder,
+    /// Position at an instant, running on from there at `speed`
+    anchor: Option<(TimePosition, Instant)>,
+    /// Playing speed, negative when running backwards
+    speed: f64,
+    /// When the last frame ended, and its frame count
+    last_frame: Option<(Instant, u64)>,
+    /// Frames in a row, each one after the last in the playing direction
+    good_frames: u32,
+}
+
+impl LtcClock {
+    pub fn new(sample_rate: u32, input_rate: u32, start: Timecode) -> Self {
+        Self {
+            sample_rate,
+            input_rate,
+            channel: 0,
+            start,
+            freewheel: Duration::from_millis(500),
+            state: Mutex::new(LtcState {
+                decoder: LtcDecoder::new(input_rate),
+                anchor: None,
+                speed: 0.0,
+                last_frame: None,
+                good_frames: 0,
+            }),
+        }
+    }
+
+    /// Read timecode from this channel of interleaved buffers
+    pub fn with_channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Set how long to keep running after timecode stops arriving
+    pub fn with_freewheel(mut self, freewheel: Duration) -> Self {
+        self.freewheel = freewheel;
+        self
+    }
+
+    /// Frame rate of the incoming timecode, once one has been read
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.state.lock().unwrap().decoder.frame_rate()
+    }
+
+    /// Decode interleaved samples whose first frame arrived at `at`
+    ///
+    /// Takes the same layout as `OutputEventType::AudioBuffer` payloads.
+    pub fn receive(&self, data: &[f32], channels: u8, at: Instant) {
+        let channels = channels.max(1) as usize;
+        if self.channel >= channels {
+            return;
+        }
+        let samples: Vec<f32> = data.iter().skip(self.channel).step_by(channels).copied().collect();
+
+        let mut state = self.state.lock().unwrap();
+        for frame in state.decoder.decode(&samples) {
+            let ended = at + Duration::from_secs_f64(frame.offset as f64 / self.input_rate as f64);
+            self.frame(&mut state, &frame, ended);
+        }
+    }
+
+    /// Position at `now`, following the last timecode received
+    pub fn position_at(&self, now: Instant) -> TimePosition {
+        let state = self.state.lock().unwrap();
+        self.position_in(&state, now)
+    }
+
+    /// Lock state at `now`
+    pub fn lock_state_at(&self, now: Instant) -> LockState {
+        let state = self.state.lock().unwrap();
+        self.lock_state_in(&state, now)
+    }
+
+    /// Lock state now
+    pub fn lock_state(&self) -> LockState {
+        self.lock_state_at(Instant::now())
+    }
+
+    fn frame(&self, state: &mut LtcState, frame: &LtcFrame, ended: Instant) {
+        let count = frame.timecode.frame_count();
+        let follows = match state.last_frame {
+            Some((_, last)) if frame.reverse => count + 1 == last,
+            Some((_, last)) => count == last + 1,
+            None => false,
+        };
+        state.good_frames = if follows { state.good_frames + 1 } else { 1 };
+        state.last_frame = Some((ended, count));
+
+        // Forwards the frame has just finished; backwards we are back at its start
+        let label = if frame.reverse {
+            frame.timecode
+        } else {
+            Timecode::from_frame_count(count + 1, frame.timecode.rate)
+        };
+        state.anchor = Some((label.to_position(&self.start, self.sample_rate), ended));
+
+        let speed = state.decoder.speed();
+        state.speed = if frame.reverse { -speed } else { speed };
+    }
+
+    /// Longest gap between frames that still counts as running timecode
+    fn dropout_gap(&self, state: &LtcState) -> Duration {
+        let rate = state.decoder.frame_rate().unwrap_or(FrameRate::Fps24);
+        Duration::from_secs_f64(2.0 / rate.fps())
+    }
+
+    fn position_in(&self, state: &LtcState, now: Instant) -> TimePosition {
+        let Some((position, anchor_instant)) = state.anchor else {
+            return TimePosition::zero();
+        };
+
+        let until = match state.last_frame {
+            Some((last, _)) => now.min(last + self.dropout_gap(state) + self.freewheel),
+            None => now,
+        };
+        let elapsed = until.saturating_duration_since(anchor_instant).as_secs_f64();
+        let ticks = (elapsed * self.sample_rate as f64 * state.speed.abs()).round() as u64;
+
+        if state.speed < 0.0 {
+            TimePosition::new(position.position_ticks.saturating_sub(ticks))
+        } else {
+            TimePosition::new(position.position_ticks.saturating_add(ticks))
+        }
+    }
+
+    fn lock_state_in(&self, state: &LtcState, now: Instant) -> LockState {
+        let Some((last, _)) = state.last_frame else {
+            return LockState::Unlocked;
+        };
+
+        let gap = now.saturating_duration_since(last);
+        if gap <= self.dropout_gap(state) {
+            if state.good_frames >= FRAMES_TO_LOCK {
+                LockState::Locked
+            } else {
+                LockState::Locking
+            }
+        } else if gap <= self.dropout_gap(state) + self.freewheel {
+            LockState::Freewheeling
+        } else {
+            LockState::Unlocked
+        }
+    }
+}
+
+impl ClockSource for LtcClock {
+    fn current_time(&self) -> TimePosition {
+        self.position_at(Instant::now())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_running(&self) -> bool {
+        self.lock_state() != LockState::Unlocked
+    }
+
+    fn follows_master(&self) -> bool {
+        true
+    }
+
+    fn lock_state(&self) -> LockState {
+        LtcClock::lock_state(self)
+    }
+}
@@ -0,0 +1,288 @@
+// src/engine/midi_clock.rs
+use std::f64::consts::{PI, SQRT_2};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use crate::engine::clock::{ClockSource, LockState};
+use crate::model::Project;
+use crate::tapestry::{Tempo, TimePosition};
+
+const TIMING_CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION: u8 = 0xF2;
+
+/// MIDI clocks per quarter note
+pub const CLOCKS_PER_BEAT: u64 = 24;
+/// MIDI clocks per Song Position Pointer step, a sixteenth note
+const CLOCKS_PER_SPP_STEP: u64 = 6;
+
+/// Clocks in a row, each where the filter expected it, needed before reporting lock
+const CLOCKS_TO_LOCK: u32 = CLOCKS_PER_BEAT as u32;
+
+/// Tempo range accepted when the filter starts from two clocks
+const MIN_BPM: f64 = 20.0;
+const MAX_BPM: f64 = 400.0;
+
+/// Clock that follows MIDI Beat Clock from an external master
+///
+/// Feed it the raw bytes from a MIDI input with `receive`. Start, Continue and Stop
+/// run the transport, Song Position Pointer relocates it, and the 24-per-beat timing
+/// clocks are smoothed by a delay-locked loop to estimate the master's tempo. The
+/// master counts beats, so positions come from the project's tempo map: beat `n` of
+/// the master is beat `n` of the project, whatever tempo the map says. Where the
+/// two tempos differ, `speed` is their ratio, so events are timed at the master's.
+pub struct MidiBeatClock {
+    project: Arc<RwLock<Project>>,
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    /// Bandwidth of the tempo filter in Hz; lower is smoother but slower to follow
+    bandwidth: f64,
+    state: Mutex<BeatClockState>,
+}
+
+#[derive(Default)]
+struct BeatClockState {
+    /// Data bytes of a Song Position Pointer being parsed
+    song_position: Option<Vec<u8>>,
+    /// Clocks since the start of the song
+    song_clocks: u64,
+    /// Start or Continue was received; the next clock starts the transport
+    armed: bool,
+    running: bool,
+    /// When the last clock arrived
+    last_clock: Option<Instant>,
+    filter: Option<ClockFilter>,
+    /// Clocks in a row that the filter expected
+    good_clocks: u32,
+}
+
+/// Delay-locked loop following the timing clocks
+#[derive(Clone, Copy)]
+struct ClockFilter {
+    /// Smoothed time of the last clock
+    clock: Instant,
+    /// Smoothed time between clocks in seconds
+    period: f64,
+}
+
+impl MidiBeatClock {
+    pub fn new(project: Arc<RwLock<Project>>) -> Self {
+        let sample_rate = project.read().unwrap().settings.reference_sample_rate;
+        Self {
+            project,
+            sample_rate,
+            bandwidth: 1.0,
+            state: Mutex::new(BeatClockState::default()),
+        }
+    }
+
+    /// Set the bandwidth of the tempo filter in Hz
+    pub fn with_bandwidth(mut self, bandwidth: f64) -> Self {
+        self.bandwidth = bandwidth.max(0.01);
+        self
+    }
+
+    /// Parse MIDI bytes that arrived at `at`
+    ///
+    /// Bytes may be split anywhere, and anything other than beat clock is ignored.
+    pub fn receive(&self, bytes: &[u8], at: Instant) {
+        let mut state = self.state.lock().unwrap();
+
+        for &byte in bytes {
+            match byte {
+                TIMING_CLOCK => self.clock(&mut state, at),
+                START => {
+                    state.song_clocks = 0;
+                    state.armed = true;
+                }
+                CONTINUE => state.armed = true,
+                STOP => {
+                    state.armed = false;
+                    state.running = false;
+                }
+                // Other real-time messages can appear anywhere
+                0xF9 | 0xFD..=0xFF => {}
+                SONG_POSITION => state.song_position = Some(Vec::new()),
+                0x00..=0x7F => {
+                    let Some(data) = state.song_position.as_mut() else {
+                        continue;
+                    };
+                    data.push(byte);
+                    if let [lsb, msb] = data[..] {
+                        let steps = (msb as u64) << 7 | lsb as u64;
+                        state.song_clocks = steps * CLOCKS_PER_SPP_STEP;
+                        state.song_position = None;
+                    }
+                }
+                _ => state.song_position = None,
+            }
+        }
+    }
+
+    /// Position in beats (quarter notes) at `now`
+    pub fn beats_at(&self, now: Instant) -> f64 {
+        let state = self.state.lock().unwrap();
+        Self::beats_in(&state, now)
+    }
+
+    /// Position at `now`
+    pub fn position_at(&self, now: Instant) -> TimePosition {
+        let beats = self.beats_at(now);
+        self.project.read().unwrap().tempo_map.beats_to_position(beats)
+    }
+
+    /// Tempo of the master, once two clocks have arrived
+    pub fn tempo(&self) -> Option<Tempo> {
+        let state = self.state.lock().unwrap();
+        state.filter.map(|filter| Tempo::new(filter.bpm()))
+    }
+
+    /// How fast the master runs through the project at `now`: its tempo over the map's
+    pub fn speed_at(&self, now: Instant) -> f64 {
+        let (beats, tempo) = {
+            let state = self.state.lock().unwrap();
+            (Self::beats_in(&state, now), state.filter.map(|filter| filter.bpm()))
+        };
+        let Some(bpm) = tempo else {
+            return 1.0;
+        };
+
+        let tempo_map = &self.project.read().unwrap().tempo_map;
+        let project_bpm = tempo_map.tempo_at(&tempo_map.beats_to_position(beats)).bpm;
+        if project_bpm > 0.0 { bpm / project_bpm } else { 1.0 }
+    }
+
+    /// Lock state at `now`
+    pub fn lock_state_at(&self, now: Instant) -> LockState {
+        let state = self.state.lock().unwrap();
+        Self::lock_state_in(&state, now)
+    }
+
+    /// Lock state now
+    pub fn lock_state(&self) -> LockState {
+        self.lock_state_at(Instant::now())
+    }
+
+    fn clock(&self, state: &mut BeatClockState, at: Instant) {
+        if state.armed {
+            // The first clock after Start or Continue is the song position itself
+            state.armed = false;
+            state.running = true;
+        } else if state.running {
+            state.song_clocks += 1;
+        }
+
+        let last = state.last_clock.replace(at);
+        state.filter = match state.filter {
+            Some(filter) => {
+                let expected = offset(filter.clock, filter.period);
+                let error = signed_secs(at, expected);
+                if error.abs() > filter.period / 2.0 {
+                    // Too far out to be jitter: a dropout or a jump in tempo
+                    state.good_clocks = 0;
+                    None
+                } else {
+                    let omega = 2.0 * PI * self.bandwidth * filter.period;
+                    state.good_clocks = state.good_clocks.saturating_add(1);
+                    Some(ClockFilter {
+                        clock: offset(expected, SQRT_2 * omega * error),
+                        period: filter.period + omega * omega * error,
+                    })
+                }
+            }
+            None => last.and_then(|last| {
+                let period = at.saturating_duration_since(last).as_secs_f64();
+                let bpm = 60.0 / (period * CLOCKS_PER_BEAT as f64);
+                (MIN_BPM..=MAX_BPM).contains(&bpm).then_some(ClockFilter { clock: at, period })
+            }),
+        };
+    }
+
+    fn beats_in(state: &BeatClockState, now: Instant) -> f64 {
+        let clocks = state.song_clocks as f64;
+        let fraction = match state.filter {
+            // Run on between clocks, but never past the next one
+            Some(filter) if state.running => {
+                let since = now.saturating_duration_since(filter.clock).as_secs_f64();
+                (since / filter.period).min(1.0)
+            }
+            _ => 0.0,
+        };
+        (clocks + fraction) / CLOCKS_PER_BEAT as f64
+    }
+
+    fn lock_state_in(state: &BeatClockState, now: Instant) -> LockState {
+        let (Some(last), Some(filter)) = (state.last_clock, state.filter) else {
+            return match state.last_clock {
+                Some(last) if now.saturating_duration_since(last) < Duration::from_millis(250) => LockState::Locking,
+                _ => LockState::Unlocked,
+            };
+        };
+
+        // A few missing clocks are tolerated before giving up on the master
+        let dropout = Duration::from_secs_f64(filter.period * 4.0);
+        if now.saturating_duration_since(last) > dropout {
+            LockState::Unlocked
+        } else if state.good_clocks >= CLOCKS_TO_LOCK {
+            LockState::Locked
+        } else {
+            LockState::Locking
+        }
+    }
+}
+
+impl ClockFilter {
+    fn bpm(&self) -> f64 {
+        60.0 / (self.period * CLOCKS_PER_BEAT as f64)
+    }
+}
+
+/// `instant` moved by a signed number of seconds
+fn offset(instant: Instant, secs: f64) -> Instant {
+    if secs >= 0.0 {
+        instant + Duration::from_secs_f64(secs)
+    } else {
+        instant.checked_sub(Duration::from_secs_f64(-secs)).unwrap_or(instant)
+    }
+}
+
+/// Signed number of seconds from `earlier` to `later`
+fn signed_secs(later: Instant, earlier: Instant) -> f64 {
+    if later >= earlier {
+        (later - earlier).as_secs_f64()
+    } else {
+        -(earlier - later).as_secs_f64()
+    }
+}
+
+impl ClockSource for MidiBeatClock {
+    fn current_time(&self) -> TimePosition {
+        self.position_at(Instant::now())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_running(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.running && Self::lock_state_in(&state, Instant::now()) != LockState::Unlocked
+    }
+
+    fn tempo(&self) -> Option<Tempo> {
+        MidiBeatClock::tempo(self)
+    }
+
+    fn follows_master(&self) -> bool {
+        true
+    }
+
+    fn speed(&self) -> f64 {
+        self.speed_at(Instant::now())
+    }
+
+    fn lock_state(&self) -> LockState {
+        MidiBeatClock::lock_state(self)
+    }
+}
@@ -1,8 +1,21 @@
+pub mod chase;
+pub mod clip;
 pub mod clock;
 pub mod clock_manager;
+pub mod ltc;
+pub mod midi_clock;
+pub mod mtc;
 pub mod playback;
 pub mod scheduler;
+pub mod sync;
 
 // Re-export main types
-pub use clock::{ClockSource, ClockSourceType, InternalClock};
-pub use playback::PlaybackEngine;
\ No newline at end of file
+pub use chase::{chase, ChaseState};
+pub use clip::clip_events;
+pub use clock::{ClockSource, ClockSourceType, InternalClock, LockState, ManualClock};
+pub use clock_manager::ClockManager;
+pub use ltc::{LtcClock, LtcDecoder, LtcFrame};
+pub use midi_clock::MidiBeatClock;
+pub use mtc::MtcClock;
+pub use playback::{PlaybackEngine, RenderedEvent};
+pub use sync::SyncGenerator;
\ No newline at end of file
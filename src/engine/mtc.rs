@@ -0,0 +1,278 @@
+// src/engine/mtc.rs
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::engine::clock::{ClockSource, LockState};
+use crate::tapestry::{FrameRate, TimePosition, Timecode};
+use crate::tapestry::timecode::SUBFRAMES_PER_FRAME;
+
+/// Status byte of an MTC quarter-frame message
+const QUARTER_FRAME: u8 = 0xF1;
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+
+/// Complete sequences in a row needed before reporting lock
+const SEQUENCES_TO_LOCK: u32 = 2;
+
+/// Clock that chases incoming MIDI Time Code
+///
+/// Feed it the raw bytes from a MIDI input with `receive`. Quarter-frame messages
+/// make it run; a full-frame sysex message locates it while stopped. A quarter-frame
+/// sequence takes two frames to send and describes the frame at which it started,
+/// so positions are corrected by that latency.
+pub struct MtcClock {
+    /// Ticks per second of the positions this clock reports
+    sample_rate: u32,
+    /// Timecode at timeline position zero
+    start: Timecode,
+    /// How long to keep running after timecode stops arriving
+    freewheel: Duration,
+    state: Mutex<MtcState>,
+}
+
+#[derive(Default)]
+struct MtcState {
+    /// Partial message being parsed
+    parser: Parser,
+    /// Quarter-frame pieces of the sequence in progress
+    pieces: [u8; 8],
+    /// Piece expected next, or `None` until a sequence starts
+    next_piece: Option<u8>,
+    /// Frame rate announced by the sender
+    rate: Option<FrameRate>,
+    /// Position at an instant, and whether the clock runs on from it
+    anchor: Option<(TimePosition, Instant)>,
+    running: bool,
+    /// When the last quarter frame arrived
+    last_quarter_frame: Option<Instant>,
+    /// Frame count of the last complete sequence
+    last_sequence_frames: Option<u64>,
+    /// Complete sequences in a row, each two frames after the last
+    good_sequences: u32,
+}
+
+#[derive(Default)]
+enum Parser {
+    #[default]
+    Idle,
+    QuarterFrame,
+    SysEx(Vec<u8>),
+}
+
+impl MtcClock {
+    pub fn new(sample_rate: u32, start: Timecode) -> Self {
+        Self {
+            sample_rate,
+            start,
+            freewheel: Duration::from_millis(500),
+            state: Mutex::new(MtcState::default()),
+        }
+    }
+
+    /// Set how long to keep running after timecode stops arriving
+    pub fn with_freewheel(mut self, freewheel: Duration) -> Self {
+        self.freewheel = freewheel;
+        self
+    }
+
+    /// Frame rate announced by the sender, once one has been received
+    pub fn frame_rate(&self) -> Option<FrameRate> {
+        self.state.lock().unwrap().rate
+    }
+
+    /// Parse MIDI bytes that arrived at `at`
+    ///
+    /// Bytes may be split anywhere, and anything other than MTC is ignored.
+    pub fn receive(&self, bytes: &[u8], at: Instant) {
+        let mut state = self.state.lock().unwrap();
+
+        for &byte in bytes {
+            // Real-time messages can appear anywhere, even inside sysex
+            if byte >= 0xF8 {
+                continue;
+            }
+
+            match std::mem::take(&mut state.parser) {
+                Parser::QuarterFrame if byte < 0x80 => self.quarter_frame(&mut state, byte, at),
+                Parser::SysEx(mut data) if byte < 0x80 => {
+                    data.push(byte);
+                    state.parser = Parser::SysEx(data);
+                }
+                Parser::SysEx(data) if byte == SYSEX_END => self.sysex(&mut state, &data, at),
+                _ => {
+                    state.parser = match byte {
+                        QUARTER_FRAME => Parser::QuarterFrame,
+                        SYSEX_START => Parser::SysEx(Vec::new()),
+                        _ => Parser::Idle,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Position at `now`, following the last timecode received
+    pub fn position_at(&self, now: Instant) -> TimePosition {
+        let state = self.state.lock().unwrap();
+        self.position_in(&state, now)
+    }
+
+    /// Lock state at `now`
+    pub fn lock_state_at(&self, now: Instant) -> LockState {
+        let state = self.state.lock().unwrap();
+        self.lock_state_in(&state, now)
+    }
+
+    /// Lock state now
+    pub fn lock_state(&self) -> LockState {
+        self.lock_state_at(Instant::now())
+    }
+
+    fn quarter_frame(&self, state: &mut MtcState, data: u8, at: Instant) {
+        let piece = data >> 4;
+        let value = data & 0x0F;
+        state.last_quarter_frame = Some(at);
+
+        // Only forward sequences are followed; anything else restarts the sequence
+        if piece == 0 {
+            state.next_piece = Some(0);
+        }
+        if state.next_piece != Some(piece) {
+            state.next_piece = None;
+            return;
+        }
+
+        state.pieces[piece as usize] = value;
+        if piece < 7 {
+            state.next_piece = Some(piece + 1);
+            return;
+        }
+        state.next_piece = None;
+
+        let pieces = state.pieces;
+        let rate = Self::decode_rate(pieces[7] >> 1);
+        let Ok(timecode) = Timecode::new(
+            pieces[6] | ((pieces[7] & 0x01) << 4),
+            pieces[4] | ((pieces[5] & 0x03) << 4),
+            pieces[2] | ((pieces[3] & 0x03) << 4),
+            pieces[0] | ((pieces[1] & 0x01) << 4),
+            rate,
+        ) else {
+            state.good_sequences = 0;
+            return;
+        };
+
+        let frames = timecode.frame_count();
+        state.good_sequences = match state.last_sequence_frames {
+            Some(last) if frames == last + 2 && state.rate == Some(rate) => state.good_sequences + 1,
+            _ => 1,
+        };
+        state.last_sequence_frames = Some(frames);
+        state.rate = Some(rate);
+
+        // The sequence began at `timecode` and this last piece is seven quarter frames later
+        let quarters = frames * 4 + 7;
+        let mut now = Timecode::from_frame_count(quarters / 4, rate);
+        now.subframes = ((quarters % 4) * SUBFRAMES_PER_FRAME as u64 / 4) as u8;
+
+        state.anchor = Some((now.to_position(&self.start, self.sample_rate), at));
+        state.running = true;
+    }
+
+    fn sysex(&self, state: &mut MtcState, data: &[u8], at: Instant) {
+        // Full frame: F0 7F <device> 01 01 hr mn sc fr F7
+        let [0x7F, _, 0x01, 0x01, hours, minutes, seconds, frames] = *data else {
+            return;
+        };
+
+        let rate = Self::decode_rate(hours >> 5);
+        let Ok(timecode) = Timecode::new(hours & 0x1F, minutes, seconds, frames, rate) else {
+            return;
+        };
+
+        state.rate = Some(rate);
+        state.anchor = Some((timecode.to_position(&self.start, self.sample_rate), at));
+        state.running = false;
+        state.next_piece = None;
+        state.last_sequence_frames = None;
+        state.good_sequences = 0;
+    }
+
+    fn decode_rate(code: u8) -> FrameRate {
+        match code & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps29_97Drop,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    /// Longest gap between quarter frames that still counts as running timecode
+    fn dropout_gap(&self, state: &MtcState) -> Duration {
+        let rate = state.rate.unwrap_or(FrameRate::Fps24);
+        // One frame, four times the normal gap
+        Duration::from_secs_f64(1.0 / rate.fps())
+    }
+
+    /// When the clock stops running after timecode stopped arriving
+    fn freewheel_deadline(&self, state: &MtcState) -> Option<Instant> {
+        state.last_quarter_frame.map(|last| last + self.dropout_gap(state) + self.freewheel)
+    }
+
+    fn position_in(&self, state: &MtcState, now: Instant) -> TimePosition {
+        let Some((position, anchor_instant)) = state.anchor else {
+            return TimePosition::zero();
+        };
+        if !state.running {
+            return position;
+        }
+
+        let until = match self.freewheel_deadline(state) {
+            Some(deadline) => now.min(deadline),
+            None => now,
+        };
+        let elapsed = until.saturating_duration_since(anchor_instant).as_secs_f64();
+        let ticks = (elapsed * self.sample_rate as f64).round() as u64;
+        TimePosition::new(position.position_ticks.saturating_add(ticks))
+    }
+
+    fn lock_state_in(&self, state: &MtcState, now: Instant) -> LockState {
+        let Some(last) = state.last_quarter_frame else {
+            return LockState::Unlocked;
+        };
+
+        let gap = now.saturating_duration_since(last);
+        if gap <= self.dropout_gap(state) {
+            if state.running && state.good_sequences >= SEQUENCES_TO_LOCK {
+                LockState::Locked
+            } else {
+                LockState::Locking
+            }
+        } else if state.running && gap <= self.dropout_gap(state) + self.freewheel {
+            LockState::Freewheeling
+        } else {
+            LockState::Unlocked
+        }
+    }
+}
+
+impl ClockSource for MtcClock {
+    fn current_time(&self) -> TimePosition {
+        self.position_at(Instant::now())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(self.lock_state(), LockState::Locking | LockState::Locked | LockState::Freewheeling)
+            && self.state.lock().unwrap().running
+    }
+
+    fn follows_master(&self) -> bool {
+        true
+    }
+
+    fn lock_state(&self) -> LockState {
+        MtcClock::lock_state(self)
+    }
+}
@@ -1,21 +1,52 @@
 // src/engine/playback.rs
-use std::sync::{Arc, RwLock};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Instant, Duration};
-use eframe::glow::TEXTURE_BORDER_COLOR;
-use crate::engine::clock::{ClockSource, InternalClock};
+use std::time::{Duration, Instant};
+use crate::engine::chase::{chase, ChaseState};
+use crate::engine::clip::clip_events;
+use crate::engine::clock::{ClockSource, ClockSourceType, ManualClock};
+use crate::engine::clock_manager::ClockManager;
+use crate::engine::scheduler::EventScheduler;
+use crate::engine::sync::SyncGenerator;
 use crate::controller::event::{Event, EventSender};
-use crate::model::{MediaContent, Project};
-use crate::tapestry::{TimePosition};
-use crate::output::{OutputEvent, OutputSystem};
+use crate::model::{CycleRange, EndpointId, MediaContent, Project};
+use crate::tapestry::{Tempo, TimePosition};
+use crate::output::{ActiveNotes, OutputEvent, OutputEventType, OutputSystem};
 
+/// Smallest change in a master's tempo worth telling the rest of the app about, in BPM
+const TEMPO_PUBLISH_THRESHOLD: f64 = 0.05;
+
+/// How far ahead of the playhead events are rendered into the scheduler, in seconds
+const LOOKAHEAD_SECS: f64 = 0.05;
+
+/// How long before they are due events are handed to their endpoints, in seconds
+///
+/// Their timestamps assume the clock runs at normal speed from when they are handed
+/// over, which a master may not quite do, so this is kept well short of the lookahead.
+const SEND_AHEAD_SECS: f64 = 0.01;
+
+/// An event produced by an offline render
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedEvent {
+    /// Where the event is due
+    pub position: TimePosition,
+    pub event: OutputEvent,
+}
 
 pub struct PlaybackEngine {
     project: Arc<RwLock<Project>>,
-    clock_source: Box<dyn ClockSource>,
+    clock_source: Arc<dyn ClockSource>,
+    /// The same clock as `clock_source`, for registering and switching sources
+    clock_manager: Arc<ClockManager>,
     playing: Arc<AtomicBool>,
+    /// Set by `seek` so the playback thread skips the range it jumped over, and leaves
+    /// the notes alone; `seek` has released them and chased the new position
+    relocated: Arc<AtomicBool>,
     playback_thread: Option<JoinHandle<()>>,
+    /// MIDI clock and MTC sent to endpoints that follow us
+    sync: Arc<Mutex<SyncGenerator>>,
     event_sender: EventSender,
     output_system: Arc<RwLock<OutputSystem>>,
 }
@@ -27,28 +58,40 @@ impl PlaybackEngine {
         output_system: Arc<RwLock<OutputSystem>>,
     ) -> Self {
         // Start with internal clock by default
-        let clock_source = Box::new(InternalClock {
-            start_time: None,
-            sample_rate: 44100,
-        });
+        let sample_rate = project.read().unwrap().settings.reference_sample_rate;
+        let clock_manager = Arc::new(ClockManager::new(sample_rate, event_sender.clone()));
 
         Self {
             project,
-            clock_source,
+            clock_source: clock_manager.clone(),
+            clock_manager,
             playing: Arc::new(AtomicBool::new(false)),
+            relocated: Arc::new(AtomicBool::new(false)),
             playback_thread: None,
+            sync: Arc::new(Mutex::new(SyncGenerator::new())),
             event_sender,
             output_system,
         }
     }
 
-    pub fn set_clock_source(&mut self, clock_source: Box<dyn ClockSource>) {
-        // Stop playback if running
-        if self.playing.load(Ordering::SeqCst) {
-            self.stop();
+    /// Make a clock source available for selection
+    pub fn register_clock_source(&self, source_type: ClockSourceType, source: Arc<dyn ClockSource>) {
+        self.clock_manager.register(source_type, source);
+    }
+
+    /// Switch to a registered clock source without stopping
+    pub fn select_clock_source(&mut self, source_type: ClockSourceType) -> Result<(), &'static str> {
+        self.clock_manager.select(source_type)?;
+
+        // Chase an external master straight away; it decides when we actually run
+        if self.clock_source.follows_master() {
+            self.play();
         }
+        Ok(())
+    }
 
-        self.clock_source = clock_source;
+    pub fn clock_manager(&self) -> &Arc<ClockManager> {
+        &self.clock_manager
     }
 
     pub fn play(&mut self) {
@@ -57,85 +100,141 @@ impl PlaybackEngine {
         }
 
         self.playing.store(true, Ordering::SeqCst);
+        self.clock_source.start();
+
+        // Before taking the project: a MIDI beat clock reads it to place its position
+        let position = self.clock_source.current_time();
+        let start_events = {
+            let project = self.project.read().unwrap();
+            let mut events = self.sync.lock().unwrap().start(&project, position);
+            events.extend(chase(&project, position, true));
+            events
+        };
+        self.send_output(&start_events);
 
         // Clone necessary references for the playback thread
         let project = Arc::clone(&self.project);
         let playing = Arc::clone(&self.playing);
+        let relocated = Arc::clone(&self.relocated);
+        let clock = Arc::clone(&self.clock_source);
+        let sync = Arc::clone(&self.sync);
         let event_sender = self.event_sender.clone();
         let output_system = Arc::clone(&self.output_system);
 
         // Start playback thread
         self.playback_thread = Some(thread::spawn(move || {
-            let start_time = Instant::now();
-            let mut last_position = TimePosition::zero();
+            let mut last_position = clock.current_time();
+            let sample_rate = clock.sample_rate();
+            // Further than the clock can run between two passes of this loop
+            let max_step = sample_rate as u64 / 2;
+            let lookahead = (LOOKAHEAD_SECS * sample_rate as f64) as u64;
+            let send_ahead = (SEND_AHEAD_SECS * sample_rate as f64) as u64;
+            let mut scheduler = EventScheduler::new();
+            // End of the range already rendered into the scheduler
+            let mut rendered_until = last_position;
+            let mut was_running = false;
+            let mut published_tempo: Option<Tempo> = None;
 
             while playing.load(Ordering::SeqCst) {
-                //get current time from clock
-                let elapsed_secs = start_time.elapsed().as_secs_f64();
+                // `seek` holds the outputs while it relocates and chases, so the clock
+                // is read here either before a seek or after it, never in between
+                let mut output = output_system.write().unwrap();
+                let mut current_position = clock.current_time();
+                let now = Instant::now();
+                let running = clock.is_running();
+                let speed = clock.speed();
+                let seeked = relocated.swap(false, Ordering::SeqCst);
+                // Nothing plays while an external master is stopped, so a relocation
+                // it makes then (e.g. Song Position Pointer) is not swept over. Nor is
+                // a jump made by a master or a clock switch while running.
+                let jumped = current_position < last_position
+                    || current_position.position_ticks - last_position.position_ticks > max_step;
+                // Only a clock we run ourselves can be sent back to the start of the cycle
+                let cycle = if clock.follows_master() { None } else { active_cycle(&project.read().unwrap(), &last_position) };
+                let wrap = cycle.filter(|cycle| running && !seeked && !jumped && current_position >= cycle.end);
+                if let Some(cycle) = wrap {
+                    current_position = cycle.wrap(&current_position);
+                    clock.locate(current_position);
 
-                let project_guard = project.read().unwrap();
+                    // As for a seek: the notes at the end are released and the start chased
+                    scheduler.clear();
+                    output.cancel_scheduled();
+                    output.release_all_notes();
+                    let locate_events = {
+                        let project = project.read().unwrap();
+                        let mut events = sync.lock().unwrap().locate(&project, current_position, true);
+                        events.extend(chase(&project, current_position, true));
+                        events
+                    };
+                    for event in &locate_events {
+                        let _ = output.send_event(event);
+                    }
+                    rendered_until = current_position;
+                } else if seeked || !running || jumped {
+                    if rendered_until > last_position {
+                        // What was rendered ahead belongs to the old position
+                        scheduler.clear();
+                        output.cancel_scheduled();
+                        // and so do the notes still sounding, unless `seek` released them
+                        if !seeked {
+                            output.release_all_notes();
+                        }
+                    }
+                    // `seek` chases its own jumps; those made by a master are chased here
+                    if jumped && !seeked {
+                        let chase_events = chase(&project.read().unwrap(), current_position, running);
+                        for event in &chase_events {
+                            let _ = output.send_event(event);
+                        }
+                    }
+                    rendered_until = current_position;
+                }
+                drop(output);
 
-                let current_position = {
+                if clock.follows_master() {
+                    if running != was_running {
+                        let _ = event_sender.send(if running { Event::PlaybackStarted } else { Event::PlaybackStopped });
+                    }
 
-                    TimePosition::from_seconds(
-                        elapsed_secs,
-                        project_guard.settings.reference_sample_rate,
-                    )
-                };
+                    if let Some(tempo) = clock.tempo() {
+                        let changed = published_tempo.is_none_or(|published| (published.bpm - tempo.bpm).abs() >= TEMPO_PUBLISH_THRESHOLD);
+                        if changed {
+                            let _ = event_sender.send(Event::ClockTempoChanged { tempo });
+                            published_tempo = Some(tempo);
+                        }
+                    }
+                }
+                was_running = running;
 
                 let _ = event_sender.send(Event::PlaybackPositionChanged {
                     position: current_position
                 });
 
-                //process events between last_ and current_ positions
-                let events_to_process = {
-                    if let Some(timeline) = project_guard.active_timeline() {
-                        // get containers that are active in this range
-                        let containers = timeline.containers_in_range(
-                            &last_position,
-                            &current_position,
-                        );
-
-                        // convert container events to output events
-                        let mut output_events = Vec::new();
-                        for container in containers {
-                            match &container.content {
-                                MediaContent::Pattern(pattern_id) => {
-                                    todo!();
-                                },
-                                MediaContent::MidiClip(midi_clip_id) => {
-                                    todo!();
-                                },
-                                MediaContent::AudioFile(audio_file_id) => {
-                                    todo!();
-                                },
-                            }
-                        }
-
-                        // test tone generator
-                        let beat = project_guard.tempo_map.position_to_beats(&current_position);
-                        if beat.floor() > project_guard.tempo_map.position_to_beats(&last_position).floor() {
-                            // beat tone
-                            let note = 60 + ((beat as u8) % 12); // procedural C major
-                            let event = OutputEvent::midi_note_on(0, note, 100, None);
-                            output_events.push(event.clone());
-
-                            let note_off = OutputEvent::midi_note_off(0,note, None);
-                            output_events.push(note_off);
-                        }
-                        output_events
-                    } else {
-                        Vec::new()
+                // Render the lookahead window, so each event can be handed over with
+                // the time it is due rather than whenever this loop wakes
+                let horizon = TimePosition::new(current_position.position_ticks + lookahead);
+                // Nothing past the end of the cycle, which the playhead never reaches
+                let horizon = cycle.map_or(horizon, |cycle| horizon.min(cycle.end));
+                if running && horizon > rendered_until {
+                    let project_guard = project.read().unwrap();
+                    let events = process_block(&project_guard, &mut sync.lock().unwrap(), &rendered_until, &horizon);
+                    for (position, event) in events {
+                        scheduler.schedule_event(position, event);
                     }
-                };
-
-                drop(project_guard);
+                    rendered_until = horizon;
+                }
 
+                let due = scheduler.take_events_before(&TimePosition::new(current_position.position_ticks + send_ahead));
                 {
                     let mut output_guard = output_system.write().unwrap();
-                    for event in events_to_process {
-                        let _ = output_guard.send_event(&event);
+                    // After a seek these belong to the old position; the next pass drops the rest
+                    if !relocated.load(Ordering::SeqCst) {
+                        for (position, event) in due {
+                            let at = instant_at(now, &current_position, &position, sample_rate, speed);
+                            let _ = output_guard.send_event_at(&event, at);
+                        }
                     }
+                    let _ = output_guard.deliver_due();
                 }
 
                 last_position = current_position;
@@ -146,21 +245,147 @@ impl PlaybackEngine {
         }));
     }
 
+    /// Stop playback and return to the start
     pub fn stop(&mut self) {
-        if !self.playing.load(Ordering::SeqCst) {
-            return; // Not playing
+        let was_playing = self.halt();
+        self.clock_source.stop();
+
+        let stop_sync = {
+            let project = self.project.read().unwrap();
+            let mut sync = self.sync.lock().unwrap();
+            let mut events = if was_playing { sync.stop(&project) } else { Vec::new() };
+            events.extend(sync.locate(&project, TimePosition::zero(), false));
+            events
+        };
+        self.send_output(&stop_sync);
+    }
+
+    /// Stop playback, holding the current position
+    pub fn pause(&mut self) {
+        if self.halt() {
+            let stop_sync = self.sync.lock().unwrap().stop(&self.project.read().unwrap());
+            self.send_output(&stop_sync);
+        }
+        self.clock_source.pause();
+    }
+
+    /// Jump to `position`, recreating the programs, controllers and held notes there
+    ///
+    /// Notes are only re-triggered while playing; otherwise they are when play starts.
+    pub fn seek(&mut self, position: TimePosition) {
+        // Held throughout, so the playback thread sees the jump and `relocated` together
+        let mut output = self.output_system.write().unwrap();
+        self.relocated.store(true, Ordering::SeqCst);
+        self.clock_source.locate(position);
+
+        let locate_events = {
+            let project = self.project.read().unwrap();
+            let playing = self.is_playing();
+            let mut events = self.sync.lock().unwrap().locate(&project, position, playing);
+            events.extend(chase(&project, position, playing));
+            events
+        };
+        output.cancel_scheduled();
+        output.release_all_notes();
+        for event in &locate_events {
+            let _ = output.send_event(event);
+        }
+    }
+
+    /// Play `start..end` offline, as fast as possible, returning what would have been sent
+    ///
+    /// The range is walked in blocks of `block_ticks` on a `ManualClock`, through the
+    /// same processing as playback but without the system clock, so the same project
+    /// always renders the same events. As when play starts, the state at `start` is
+    /// chased first. With the cycle on, the playhead loops over it as in playback, and
+    /// the render lasts as long as `start..end` takes to play. Nothing is sent to the
+    /// outputs and the transport is left alone; the project is held for reading until
+    /// the render is done.
+    pub fn render(&self, start: TimePosition, end: TimePosition, block_ticks: u64) -> Vec<RenderedEvent> {
+        let project = self.project.read().unwrap();
+        let clock = ManualClock::new(project.settings.reference_sample_rate);
+        let mut sync = SyncGenerator::new();
+        let rendered = |position: TimePosition| move |event| RenderedEvent { position, event };
+
+        clock.locate(start);
+        clock.start();
+        let mut start_events = sync.start(&project, start);
+        start_events.extend(chase(&project, start, true));
+        let mut events: Vec<_> = start_events.into_iter().map(rendered(start)).collect();
+
+        // Notes sounding on each target, to release where the playhead loops; every
+        // change is recorded at the same instant, so they apply in the order rendered
+        let mut notes: BTreeMap<Option<EndpointId>, ActiveNotes> = BTreeMap::new();
+        let at = Instant::now();
+        let record = |notes: &mut BTreeMap<Option<EndpointId>, ActiveNotes>, events: &[RenderedEvent]| {
+            for RenderedEvent { event, .. } in events {
+                notes.entry(event.target).or_default().record(event, at);
+            }
+        };
+        record(&mut notes, &events);
+
+        let mut last_position = clock.current_time();
+        let mut remaining = end.position_ticks.saturating_sub(start.position_ticks);
+        while remaining > 0 {
+            let cycle = active_cycle(&project, &last_position);
+            let until_end = cycle.map_or(u64::MAX, |cycle| cycle.end.position_ticks - last_position.position_ticks);
+            let step = block_ticks.max(1).min(remaining).min(until_end);
+            clock.advance(step);
+            remaining -= step;
+
+            let current_position = clock.current_time();
+            let block: Vec<_> = process_block(&project, &mut sync, &last_position, &current_position)
+                .into_iter()
+                .map(|(position, event)| RenderedEvent { position, event })
+                .collect();
+            record(&mut notes, &block);
+            events.extend(block);
+            last_position = current_position;
+
+            if let Some(cycle) = cycle.filter(|cycle| current_position >= cycle.end) {
+                for (target, active) in &mut notes {
+                    events.extend(active.release(at, None).into_iter().map(|(_, channel, note)| RenderedEvent {
+                        position: current_position,
+                        event: OutputEvent::midi_note_off(channel, note, *target),
+                    }));
+                }
+
+                clock.locate(cycle.start);
+                let mut locate_events = sync.locate(&project, cycle.start, true);
+                locate_events.extend(chase(&project, cycle.start, true));
+                let locate_events: Vec<_> = locate_events.into_iter().map(rendered(cycle.start)).collect();
+                record(&mut notes, &locate_events);
+                events.extend(locate_events);
+                last_position = cycle.start;
+            }
+        }
+
+        clock.pause();
+        events.extend(sync.stop(&project).into_iter().map(rendered(last_position)));
+        events
+    }
+
+    /// Send events straight to the outputs, outside the playback thread
+    fn send_output(&self, events: &[OutputEvent]) {
+        let mut output = self.output_system.write().unwrap();
+        for event in events {
+            let _ = output.send_event(event);
         }
+    }
 
-        self.playing.store(false, Ordering::SeqCst);
+    /// Stop the playback thread, returning whether it was running
+    fn halt(&mut self) -> bool {
+        let was_playing = self.playing.swap(false, Ordering::SeqCst);
 
         // Wait for playback thread to finish
         if let Some(thread) = self.playback_thread.take() {
             let _ = thread.join();
         }
-    }
-
-    pub fn seek(&mut self, position: TimePosition) {
-        // Seeking logic
+        // Nothing rendered ahead is wanted any more, nor anything still sounding
+        let mut output = self.output_system.write().unwrap();
+        output.cancel_scheduled();
+        output.release_all_notes();
+        was_playing
     }
 
     pub fn current_position(&self) -> TimePosition {
@@ -170,4 +395,121 @@ impl PlaybackEngine {
     pub fn is_playing(&self) -> bool {
         self.playing.load(Ordering::SeqCst)
     }
-}
\ No newline at end of file
+}
+
+/// Events due as the playhead moves from `from` to `to`, in order, each with the
+/// position it is due at
+fn process_block(project: &Project, sync: &mut SyncGenerator, from: &TimePosition, to: &TimePosition) -> Vec<(TimePosition, OutputEvent)> {
+    let mut content_events = Vec::new();
+    let mut output_events = Vec::new();
+
+    if let Some(timeline) = project.active_timeline() {
+        let tempo_map = &project.tempo_map;
+
+        // Follow the arranger playlist; each range plays straight on from the last
+        let mut range_start = *from;
+        for (start, end) in timeline.playback_ranges(from, to) {
+            let (start_beat, end_beat) = (tempo_map.position_to_beats(&start), tempo_map.position_to_beats(&end));
+            let on_playhead = |beat: f64| range_start.saturating_add(tempo_map.beats_to_position(beat).duration_since(start));
+
+            for track in timeline.audible_tracks() {
+                let Some(output_id) = track.output_id else {
+                    continue;
+                };
+                for container in timeline.track_containers_in_range(track.id, &start, &end) {
+                    match &container.content {
+                        MediaContent::MidiClip(clip_id) => {
+                            let Some(clip) = project.midi_clips.get(clip_id) else {
+                                continue;
+                            };
+                            let events = clip_events(container, clip, tempo_map, start_beat, end_beat, Some(output_id));
+                            content_events.extend(events.into_iter()
+                                .map(|(beat, event)| (on_playhead(beat), event.with_source(track.id))));
+                        }
+                        // Patterns and audio have nothing to play them yet
+                        MediaContent::Pattern(_) | MediaContent::AudioFile(_) => {}
+                    }
+                }
+            }
+            range_start = range_start.saturating_add(end.duration_since(start));
+        }
+        content_events.extend(section_cuts(project, from, to));
+        // Keep each clip's order at a position: changes, then note-offs, then note-ons
+        content_events.sort_by_key(|(position, event)| (*position, content_order(event)));
+
+        // test tone generator, a tone on every beat
+        let first_beat = tempo_map.position_to_beats(from).floor() as u64 + 1;
+        let last_beat = tempo_map.position_to_beats(to).floor() as u64;
+        for beat in first_beat..=last_beat {
+            let position = tempo_map.beats_to_position(beat as f64);
+            let note = 60 + (beat % 12) as u8; // procedural C major
+            output_events.push((position, OutputEvent::midi_note_on(0, note, 100, None)));
+            output_events.push((position, OutputEvent::midi_note_off(0, note, None)));
+        }
+    }
+
+    output_events.extend(sync.advance(project, from, to));
+    content_events.extend(output_events);
+    content_events.sort_by_key(|(position, _)| *position);
+    content_events
+}
+
+/// The active timeline's cycle, if the playhead at `position` is to loop over it
+///
+/// Only a playhead before the end loops; one already past it plays on.
+fn active_cycle(project: &Project, position: &TimePosition) -> Option<CycleRange> {
+    project.active_timeline()?.cycle.filter(|cycle| cycle.enabled && *position < cycle.end)
+}
+
+/// Note-offs for notes still held where the arranger playlist leaves a section, since
+/// the rest of them is never played
+fn section_cuts(project: &Project, from: &TimePosition, to: &TimePosition) -> Vec<(TimePosition, OutputEvent)> {
+    let Some(timeline) = project.active_timeline() else {
+        return Vec::new();
+    };
+    if !timeline.arrangement.is_active() {
+        return Vec::new();
+    }
+
+    let spans = timeline.arrangement.spans();
+    let mut events = Vec::new();
+    for (index, span) in spans.iter().enumerate() {
+        let cut = span.song_end();
+        let source_end = span.source_start.saturating_add(span.length);
+        let continues = spans.get(index + 1).is_some_and(|next| next.source_start == source_end);
+        if continues || cut < *from || cut >= *to {
+            continue;
+        }
+
+        for track in timeline.audible_tracks() {
+            let Some(output_id) = track.output_id else {
+                continue;
+            };
+            for (channel, pitch) in ChaseState::for_track(project, track, source_end).held_notes() {
+                let note_off = OutputEvent::midi_note_off(channel, pitch, Some(output_id)).with_source(track.id);
+                events.push((cut, note_off));
+            }
+        }
+    }
+    events
+}
+
+/// Rank of a content event among those at the same position
+fn content_order(event: &OutputEvent) -> u8 {
+    match event.event_type {
+        OutputEventType::MidiNoteOff { .. } | OutputEventType::MidiNoteOn { velocity: 0, .. } => 1,
+        OutputEventType::MidiNoteOn { .. } => 2,
+        _ => 0,
+    }
+}
+
+/// When the playhead, at `current` at `now` and moving at `speed`, reaches `position`
+fn instant_at(now: Instant, current: &TimePosition, position: &TimePosition, sample_rate: u32, speed: f64) -> Instant {
+    let speed = if speed > 0.0 { speed } else { 1.0 };
+    let secs = (position.position_ticks as f64 - current.position_ticks as f64) / sample_rate as f64 / speed;
+    if secs >= 0.0 {
+        now + Duration::from_secs_f64(secs)
+    } else {
+        now.checked_sub(Duration::from_secs_f64(-secs)).unwrap_or(now)
+    }
+}
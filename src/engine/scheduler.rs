@@ -1,9 +1,10 @@
 // src/engine/scheduler.rs
 use std::collections::BTreeMap;
-use crate::model::container::MediaContainer;
 use crate::tapestry::TimePosition;
 use crate::output::event::OutputEvent;
 
+/// Events rendered ahead of the playhead, waiting to be handed to the outputs
+#[derive(Default)]
 pub struct EventScheduler {
     scheduled_events: BTreeMap<TimePosition, Vec<OutputEvent>>,
 }
@@ -17,7 +18,7 @@ impl EventScheduler {
 
     /// Schedule an output event at a specific time
     pub fn schedule_event(&mut self, position: TimePosition, event: OutputEvent) {
-        self.scheduled_events.entry(position).or_insert_with(Vec::new).push(event);
+        self.scheduled_events.entry(position).or_default().push(event);
     }
 
     /// Get all events between two time positions
@@ -33,6 +34,16 @@ impl EventScheduler {
         result
     }
 
+    /// Remove and return all events before `end`, in order
+    pub fn take_events_before(&mut self, end: &TimePosition) -> Vec<(TimePosition, OutputEvent)> {
+        let later = self.scheduled_events.split_off(end);
+        let due = std::mem::replace(&mut self.scheduled_events, later);
+
+        due.into_iter()
+            .flat_map(|(pos, events)| events.into_iter().map(move |event| (pos, event)))
+            .collect()
+    }
+
     /// Clear all scheduled events
     pub fn clear(&mut self) {
         self.scheduled_events.clear();
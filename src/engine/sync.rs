@@ -0,0 +1,185 @@
+// src/engine/sync.rs
+use crate::model::{EndpointId, Project, SyncOutput};
+use crate::output::{OutputEvent, OutputEventType};
+use crate::tapestry::{TimePosition, Timecode};
+use crate::tapestry::timecode::SUBFRAMES_PER_FRAME;
+
+/// MIDI clocks per quarter note
+const CLOCKS_PER_BEAT: f64 = 24.0;
+/// MIDI clocks per Song Position Pointer step, a sixteenth note
+const CLOCKS_PER_SIXTEENTH: u64 = 6;
+/// Largest position Song Position Pointer can express, in sixteenths
+const MAX_SONG_POSITION: u64 = 0x3FFF;
+
+/// Generates MIDI Beat Clock and MTC for endpoints that take sync from us
+///
+/// Transport changes produce Start, Stop, Continue and Song Position Pointer, and
+/// full-frame MTC on relocation; `advance` produces the clocks and quarter frames due
+/// as the playhead moves. Every message is targeted at one endpoint, and each endpoint
+/// gets its sync early or late by its own offset.
+#[derive(Debug, Default)]
+pub struct SyncGenerator {
+    /// First MIDI clock to send after starting or relocating, counted from the start
+    first_clock: u64,
+}
+
+impl SyncGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages for the transport starting at `position`
+    pub fn start(&mut self, project: &Project, position: TimePosition) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        let song_position = self.relocate(project, position);
+
+        for (id, sync) in sync_endpoints(project) {
+            if sync.midi_clock {
+                if song_position == 0 {
+                    events.push(OutputEvent::new(OutputEventType::SyncStart, Some(id)));
+                } else {
+                    events.push(Self::song_position(song_position, id));
+                    events.push(OutputEvent::new(OutputEventType::SyncContinue, Some(id)));
+                }
+            }
+            if sync.mtc {
+                events.push(Self::full_frame(project, position, id));
+            }
+        }
+
+        events
+    }
+
+    /// Messages for the transport stopping
+    pub fn stop(&mut self, project: &Project) -> Vec<OutputEvent> {
+        sync_endpoints(project)
+            .filter(|(_, sync)| sync.midi_clock)
+            .map(|(id, _)| OutputEvent::new(OutputEventType::SyncStop, Some(id)))
+            .collect()
+    }
+
+    /// Messages for the transport jumping to `position`
+    ///
+    /// Song Position Pointer is only meant to be sent while stopped, so a running
+    /// transport is stopped around it.
+    pub fn locate(&mut self, project: &Project, position: TimePosition, running: bool) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        let song_position = self.relocate(project, position);
+
+        for (id, sync) in sync_endpoints(project) {
+            if sync.midi_clock {
+                if running {
+                    events.push(OutputEvent::new(OutputEventType::SyncStop, Some(id)));
+                }
+                events.push(Self::song_position(song_position, id));
+                if running {
+                    events.push(OutputEvent::new(OutputEventType::SyncContinue, Some(id)));
+                }
+            }
+            if sync.mtc {
+                events.push(Self::full_frame(project, position, id));
+            }
+        }
+
+        events
+    }
+
+    /// Clocks and quarter frames due as the playhead moves from `from` to `to`, each
+    /// with the playhead position it is to be sent at
+    pub fn advance(&mut self, project: &Project, from: &TimePosition, to: &TimePosition) -> Vec<(TimePosition, OutputEvent)> {
+        let mut events = Vec::new();
+        if to <= from {
+            return events;
+        }
+
+        let rate = project.settings.reference_sample_rate as f64;
+        for (id, sync) in sync_endpoints(project) {
+            // Sync for position `p` goes out when the playhead is at `p - offset`
+            let offset = sync.offset.ticks() as f64;
+            let from = (from.position_ticks as f64 + offset).max(0.0);
+            let to = (to.position_ticks as f64 + offset).max(0.0);
+            if to <= from {
+                continue;
+            }
+
+            // Where the playhead is when the message for `ticks` goes out
+            let sent_at = |ticks: f64| TimePosition::new((ticks - offset).round().max(0.0) as u64);
+
+            if sync.midi_clock {
+                let tempo_map = &project.tempo_map;
+                let first = clock_index(tempo_map.position_to_beats(&TimePosition::new(from as u64)));
+                let end = clock_index(tempo_map.position_to_beats(&TimePosition::new(to as u64)));
+                for clock in first.max(self.first_clock)..end.max(self.first_clock) {
+                    let position = tempo_map.beats_to_position(clock as f64 / CLOCKS_PER_BEAT);
+                    let event = OutputEvent::new(OutputEventType::SyncPulse, Some(id));
+                    events.push((sent_at(position.position_ticks as f64), event));
+                }
+            }
+
+            if sync.mtc {
+                let start = &project.settings.timecode_start;
+                let first = quarter_frame_index(start, from / rate);
+                let end = quarter_frame_index(start, to / rate);
+                for quarter in first..end {
+                    // Each sequence of eight pieces describes the frame it starts on
+                    let timecode = Timecode::from_frame_count(quarter / 8 * 2, start.rate);
+                    let piece = (quarter % 8) as u8;
+                    let event = OutputEvent::new(OutputEventType::MtcQuarterFrame { piece, timecode }, Some(id));
+                    events.push((sent_at(quarter_frame_seconds(start, quarter) * rate), event));
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Move to `position`, returning the Song Position Pointer to announce
+    ///
+    /// Song Position Pointer only counts sixteenths, so clocks stay silent until the
+    /// next sixteenth where the slave will pick up.
+    fn relocate(&mut self, project: &Project, position: TimePosition) -> u64 {
+        let beats = project.tempo_map.position_to_beats(&position);
+        let sixteenths = ((beats * 4.0).ceil() as u64).min(MAX_SONG_POSITION);
+        self.first_clock = sixteenths * CLOCKS_PER_SIXTEENTH;
+        sixteenths
+    }
+
+    fn song_position(sixteenths: u64, id: EndpointId) -> OutputEvent {
+        OutputEvent::new(OutputEventType::SongPosition { sixteenths: sixteenths as u16 }, Some(id))
+    }
+
+    fn full_frame(project: &Project, position: TimePosition, id: EndpointId) -> OutputEvent {
+        let settings = &project.settings;
+        let timecode = Timecode::from_position(&position, &settings.timecode_start, settings.reference_sample_rate);
+        OutputEvent::new(OutputEventType::MtcFullFrame { timecode }, Some(id))
+    }
+}
+
+/// Enabled endpoints that take sync, with their settings
+fn sync_endpoints(project: &Project) -> impl Iterator<Item = (EndpointId, SyncOutput)> + '_ {
+    project.endpoints.values()
+        .filter(|config| config.enabled && config.sync.is_enabled())
+        .map(|config| (config.id, config.sync))
+}
+
+/// Index of the first MIDI clock at or after a position in beats
+fn clock_index(beats: f64) -> u64 {
+    (beats * CLOCKS_PER_BEAT).ceil() as u64
+}
+
+/// Seconds into the timeline of a quarter frame, by its index counted from midnight
+fn quarter_frame_seconds(start: &Timecode, quarter: u64) -> f64 {
+    let frames = quarter as f64 / 4.0
+        - start.frame_count() as f64
+        - start.subframes as f64 / SUBFRAMES_PER_FRAME as f64;
+    frames / start.rate.fps()
+}
+
+/// Index, counted from midnight, of the first quarter frame at or after `seconds`
+/// into the timeline
+fn quarter_frame_index(start: &Timecode, seconds: f64) -> u64 {
+    let frames = start.frame_count() as f64
+        + start.subframes as f64 / SUBFRAMES_PER_FRAME as f64
+        + seconds * start.rate.fps();
+    (frames * 4.0).ceil() as u64
+}
@@ -1,3 +1,6 @@
+// Types are built with `new`, which mints a fresh ID for ID types; a `Default` would hide that
+#![allow(clippy::new_without_default)]
+
 pub mod controller;
 pub mod engine;
 pub mod model;
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::model::track::Color;
+use crate::tapestry::{Duration, TempoChange, TempoCurve, TempoMap, TimePosition};
+
+/// Unique identifier for an arranger section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionId(Uuid);
+
+impl SectionId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// A named region of the timeline, e.g. Intro, Verse or Chorus
+#[derive(Debug, Clone)]
+pub struct ArrangerSection {
+    /// Unique identifier
+    pub id: SectionId,
+
+    /// User-visible name
+    pub name: String,
+
+    /// Display color
+    pub color: Color,
+
+    /// Start of the region (inclusive)
+    pub start: TimePosition,
+
+    /// End of the region (exclusive)
+    pub end: TimePosition,
+}
+
+impl ArrangerSection {
+    /// Create a section, or `None` if it would be empty
+    pub fn new(name: String, start: TimePosition, end: TimePosition) -> Option<Self> {
+        (end > start).then(|| Self {
+            id: SectionId::new(),
+            name,
+            color: Color::new(90, 170, 120),  // Default green
+            start,
+            end,
+        })
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Length of the region
+    pub fn length(&self) -> Duration {
+        self.end.duration_since(self.start)
+    }
+}
+
+/// One step of the playlist: a section played one or more times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub section_id: SectionId,
+
+    /// Number of times the section plays in a row
+    pub repeats: u32,
+}
+
+impl PlaylistEntry {
+    pub fn new(section_id: SectionId, repeats: u32) -> Self {
+        Self { section_id, repeats }
+    }
+}
+
+/// A stretch of song time that plays a stretch of the timeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrangementSpan {
+    pub section_id: SectionId,
+
+    /// Where the span starts in song time
+    pub song_start: TimePosition,
+
+    /// Where the span starts on the timeline
+    pub source_start: TimePosition,
+
+    pub length: Duration,
+}
+
+impl ArrangementSpan {
+    /// End of the span in song time
+    pub fn song_end(&self) -> TimePosition {
+        self.song_start.saturating_add(self.length)
+    }
+}
+
+/// Arranger sections and the order they are played in
+///
+/// When enabled, playback walks "song time": the playlist laid end to end, with each
+/// entry mapped back onto its section of the timeline. Containers are never copied,
+/// so the playlist can be rearranged while playing.
+#[derive(Debug, Clone, Default)]
+pub struct Arrangement {
+    /// Sections by ID
+    pub sections: HashMap<SectionId, ArrangerSection>,
+
+    /// Order in which sections are played
+    pub playlist: Vec<PlaylistEntry>,
+
+    /// Whether playback follows the playlist instead of the linear timeline
+    pub enabled: bool,
+}
+
+impl Arrangement {
+    /// Add a section
+    pub fn add_section(&mut self, section: ArrangerSection) -> SectionId {
+        let id = section.id;
+        self.sections.insert(id, section);
+        id
+    }
+
+    /// Remove a section and any playlist entries that play it
+    pub fn remove_section(&mut self, id: SectionId) -> Option<ArrangerSection> {
+        let section = self.sections.remove(&id)?;
+        self.playlist.retain(|entry| entry.section_id != id);
+        Some(section)
+    }
+
+    /// Replace the playlist, refusing entries for unknown sections
+    pub fn set_playlist(&mut self, playlist: Vec<PlaylistEntry>) -> Result<(), &'static str> {
+        if playlist.iter().any(|entry| !self.sections.contains_key(&entry.section_id)) {
+            return Err("Playlist refers to an unknown section");
+        }
+        self.playlist = playlist;
+        Ok(())
+    }
+
+    /// Whether playback should follow the playlist
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.playlist.is_empty()
+    }
+
+    /// The playlist laid out in song time, one span per repeat
+    pub fn spans(&self) -> Vec<ArrangementSpan> {
+        let mut spans = Vec::new();
+        let mut song_start = TimePosition::zero();
+
+        for entry in &self.playlist {
+            let Some(section) = self.sections.get(&entry.section_id) else {
+                continue;
+            };
+            for _ in 0..entry.repeats {
+                let span = ArrangementSpan {
+                    section_id: section.id,
+                    song_start,
+                    source_start: section.start,
+                    length: section.length(),
+                };
+                song_start = span.song_end();
+                spans.push(span);
+            }
+        }
+
+        spans
+    }
+
+    /// Length of the whole song
+    pub fn song_length(&self) -> Duration {
+        self.spans().last().map_or(Duration::zero(), |span| span.song_end().duration_since(TimePosition::zero()))
+    }
+
+    /// The tempo map laid out in song time, each span taking the tempo and meter of its section
+    ///
+    /// A tempo ramp still under way at the end of a section heads for the tempo the next
+    /// span starts at, so that has to be the tempo the ramp reaches; otherwise this fails.
+    pub fn song_tempo_map(&self, tempo_map: &TempoMap) -> Result<TempoMap, &'static str> {
+        let spans = self.spans();
+        let mut song_map = TempoMap::new(tempo_map.reference_sample_rate(), tempo_map.playback_sample_rate());
+
+        for (index, span) in spans.iter().enumerate() {
+            let source_end = span.source_start.saturating_add(span.length);
+            let inside = |position: &TimePosition| *position > span.source_start && *position < source_end;
+            let to_song = |position: &TimePosition| span.song_start.saturating_add(position.duration_since(span.source_start));
+
+            // A ramp in progress carries on from the tempo it has reached
+            let curve = tempo_map.tempo_changes()
+                .take_while(|(position, _)| **position <= span.source_start)
+                .last()
+                .map_or(TempoCurve::Step, |(_, change)| change.curve);
+            song_map.set_tempo_change(span.song_start, TempoChange::new(tempo_map.tempo_at(&span.source_start), curve));
+            song_map.add_time_signature_change(span.song_start, tempo_map.time_signature_at(&span.source_start));
+            for (position, change) in tempo_map.tempo_changes().filter(|(position, _)| inside(position)) {
+                song_map.set_tempo_change(to_song(position), *change);
+            }
+            for (position, time_signature) in tempo_map.time_signature_changes().filter(|(position, _)| inside(position)) {
+                song_map.add_time_signature_change(to_song(position), *time_signature);
+            }
+
+            let ramping = tempo_map.tempo_changes()
+                .take_while(|(position, _)| **position < source_end)
+                .last()
+                .is_some_and(|(_, change)| change.curve != TempoCurve::Step)
+                && tempo_map.tempo_changes().any(|(position, _)| *position >= source_end);
+            if ramping {
+                let reached = tempo_map.tempo_at(&source_end);
+                match spans.get(index + 1) {
+                    Some(next) if tempo_map.tempo_at(&next.source_start) != reached => {
+                        return Err("A tempo ramp runs across the end of a section");
+                    }
+                    Some(_) => {}
+                    None => song_map.add_tempo_change(span.song_end(), reached),
+                }
+            }
+        }
+
+        Ok(song_map)
+    }
+
+    /// Timeline position played at a song position, or `None` past the end of the song
+    pub fn song_to_timeline(&self, song_position: &TimePosition) -> Option<TimePosition> {
+        self.spans().into_iter()
+            .find(|span| *song_position >= span.song_start && *song_position < span.song_end())
+            .map(|span| span.source_start.saturating_add(song_position.duration_since(span.song_start)))
+    }
+
+    /// Timeline ranges played between two song positions, in playing order
+    pub fn timeline_ranges(&self, from: &TimePosition, to: &TimePosition) -> Vec<(TimePosition, TimePosition)> {
+        self.spans().into_iter()
+            .filter(|span| span.song_start < *to && span.song_end() > *from)
+            .map(|span| {
+                let start = std::cmp::max(*from, span.song_start).duration_since(span.song_start);
+                let end = std::cmp::min(*to, span.song_end()).duration_since(span.song_start);
+                (span.source_start.saturating_add(start), span.source_start.saturating_add(end))
+            })
+            .collect()
+    }
+}
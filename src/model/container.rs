@@ -1,14 +1,20 @@
 use uuid::Uuid;
-use crate::tapestry::{TimePosition, Duration};
+use crate::model::track::Timebase;
+use crate::tapestry::{TimePosition, Duration, NoteValue, TempoMap};
 
 /// Unique identifier for a media container
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ContainerId(Uuid);
 
 impl ContainerId {
     pub fn new() -> Self {
         Self(Uuid::new_v4())
     }
+
+    /// The lowest ID, for bounding lookups ordered by position and then ID
+    pub(crate) const fn lowest() -> Self {
+        Self(Uuid::nil())
+    }
 }
 
 /// Unique identifier for a pattern
@@ -84,6 +90,9 @@ pub struct MediaContainer {
     /// Length on the timeline (can differ from content's intrinsic length)
     pub length: Duration,
 
+    /// Musical length for tempo-locked containers; `length` is derived from it
+    pub musical_length: Option<NoteValue>,
+
     /// How the content is played back
     pub playback_mode: PlaybackMode,
 
@@ -99,31 +108,80 @@ pub struct MediaContainer {
     /// Playback speed (1.0 = normal)
     pub time_scale: f64,
 
+    /// Time domain override (None = follow the track)
+    pub timebase: Option<Timebase>,
+
     /// The actual content in the container
     pub content: MediaContent,
 }
 
 impl MediaContainer {
-    pub fn new(position: TimePosition, content: MediaContent) -> Self {
+    pub fn new(position: TimePosition, content: MediaContent, tempo_map: &TempoMap) -> Self {
+        let musical_length = NoteValue::WHOLE;  // Default 4 beats
+
         // Default container with reasonable settings
         Self {
             id: ContainerId::new(),
             position,
-            length: Duration::from_beats(4.0),  // Default 4 beats
+            length: tempo_map.note_value_to_duration(&position, musical_length),
+            musical_length: Some(musical_length),
             playback_mode: PlaybackMode::Normal,
             loop_count: None,
             start_offset: Duration::zero(),
             end_offset: Duration::zero(),
             time_scale: 1.0,
+            timebase: None,
             content,
         }
     }
 
+    /// Set a fixed length that ignores tempo changes
     pub fn with_length(mut self, length: Duration) -> Self {
         self.length = length;
+        self.musical_length = None;
         self
     }
 
+    /// Set a musical length that follows the tempo map
+    pub fn with_musical_length(mut self, length: NoteValue, tempo_map: &TempoMap) -> Self {
+        self.musical_length = Some(length);
+        self.refresh_length(tempo_map);
+        self
+    }
+
+    /// Whether the container length follows the tempo map
+    pub fn is_tempo_locked(&self) -> bool {
+        self.musical_length.is_some()
+    }
+
+    /// Change the length on the timeline, keeping the musical length in step if tempo-locked
+    pub fn set_length(&mut self, length: Duration, tempo_map: &TempoMap) {
+        // A length the note value already gives keeps it exact
+        let unchanged = self.musical_length.is_some_and(|musical_length| {
+            tempo_map.note_value_to_duration(&self.position, musical_length) == length
+        });
+        self.length = length;
+        if self.is_tempo_locked() && !unchanged {
+            self.musical_length = Some(NoteValue::from_beats(length.to_beats(&self.position, tempo_map)));
+        }
+    }
+
+    /// Change the length to a note value, locking it to the tempo
+    ///
+    /// Prefer this to `set_length` when the length is musical: a length on the timeline
+    /// only maps back to the nearest approximate note value.
+    pub fn set_musical_length(&mut self, length: NoteValue, tempo_map: &TempoMap) {
+        self.musical_length = Some(length);
+        self.refresh_length(tempo_map);
+    }
+
+    /// Recompute the length on the timeline from the musical length, e.g. after a move
+    pub fn refresh_length(&mut self, tempo_map: &TempoMap) {
+        if let Some(musical_length) = self.musical_length {
+            self.length = tempo_map.note_value_to_duration(&self.position, musical_length);
+        }
+    }
+
     pub fn with_loop(mut self, count: Option<u32>) -> Self {
         self.playback_mode = PlaybackMode::Loop;
         self.loop_count = count;
@@ -140,4 +198,101 @@ impl MediaContainer {
         self.time_scale = scale;
         self
     }
+
+    pub fn with_timebase(mut self, timebase: Timebase) -> Self {
+        self.timebase = Some(timebase);
+        self
+    }
+
+    /// What plays from `at` on, as new containers
+    ///
+    /// The head is cropped off the content. A loop cut part way through a pass becomes
+    /// the rest of that pass followed by the remaining loop, which needs `content_beats`,
+    /// the length of the content; without it the content is cropped as if it played
+    /// once. Tempo-locked containers stay tempo-locked. Empty if the container has
+    /// finished by `at`.
+    pub fn played_from(&self, at: TimePosition, content_beats: Option<f64>, tempo_map: &TempoMap) -> Vec<MediaContainer> {
+        let end = self.position.saturating_add(self.length);
+        if at >= end {
+            return Vec::new();
+        }
+        let mut rest = self.clone();
+        rest.id = ContainerId::new();
+        if at <= self.position {
+            return vec![rest];
+        }
+
+        let scale = if self.time_scale > 0.0 { self.time_scale } else { 1.0 };
+        let offset = self.start_offset.to_beats(&self.position, tempo_map);
+        // Beats of content played before `at`
+        let played = at.duration_since(self.position).to_beats(&self.position, tempo_map) * scale;
+        let pass = content_beats.map(|beats| beats - offset).filter(|pass| *pass > 0.0);
+
+        let (Some(pass), PlaybackMode::Loop) = (pass, self.playback_mode) else {
+            rest.position = at;
+            rest.start_offset = Duration::from_beats(offset + played, &at, tempo_map);
+            rest.set_length(end.duration_since(at), tempo_map);
+            return vec![rest];
+        };
+
+        let passes_played = (played / pass).floor();
+        let into_pass = played - passes_played * pass;
+        let mut loop_count = match self.loop_count {
+            Some(count) if passes_played >= count as f64 => return Vec::new(),
+            Some(count) => Some(count - passes_played as u32),
+            None => None,
+        };
+
+        let mut parts = Vec::new();
+        let mut rest_start = at;
+        // Less than a tick into the pass is on its start
+        if Duration::from_beats(into_pass / scale, &at, tempo_map).ticks() > 0 {
+            let pass_end = at.saturating_add(Duration::from_beats((pass - into_pass) / scale, &at, tempo_map)).min(end);
+            let mut head = self.clone();
+            head.id = ContainerId::new();
+            head.position = at;
+            head.playback_mode = PlaybackMode::Normal;
+            head.loop_count = None;
+            head.start_offset = Duration::from_beats(offset + into_pass, &at, tempo_map);
+            head.set_length(pass_end.duration_since(at), tempo_map);
+            parts.push(head);
+
+            rest_start = pass_end;
+            loop_count = loop_count.map(|count| count - 1);
+            if rest_start >= end || loop_count == Some(0) {
+                return parts;
+            }
+        }
+
+        rest.position = rest_start;
+        rest.loop_count = loop_count;
+        rest.start_offset = Duration::from_beats(offset, &rest_start, tempo_map);
+        rest.set_length(end.duration_since(rest_start), tempo_map);
+        parts.push(rest);
+        parts
+    }
+
+    /// Re-anchor after a tempo map edit, given the maps before and after
+    ///
+    /// Beat-locked containers keep their bar and beat, and tempo-locked lengths are
+    /// recomputed. Time-locked containers keep their absolute position and length.
+    pub fn retime(&mut self, timebase: Timebase, old_map: &TempoMap, new_map: &TempoMap) {
+        match timebase {
+            Timebase::Beats => {
+                let beats = if old_map.time_signature_changes().eq(new_map.time_signature_changes()) {
+                    old_map.position_to_beats(&self.position)
+                } else {
+                    // The bar lines moved, so counting quarter notes would leave the bar
+                    let (bars, beat_in_bar) = old_map.position_to_bars_and_beats(&self.position);
+                    new_map.bars_and_beats_to_beats(bars, beat_in_bar)
+                };
+                self.position = new_map.beats_to_position(beats);
+                self.refresh_length(new_map);
+            }
+            Timebase::Time => {
+                let length = self.length;
+                self.set_length(length, new_map);
+            }
+        }
+    }
 }
\ No newline at end of file
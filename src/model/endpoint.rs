@@ -1,8 +1,9 @@
 use uuid::Uuid;
 use std::fmt;
+use crate::tapestry::TimeOffset;
 
 /// Unique identifier for an output endpoint
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct EndpointId(Uuid);
 
 impl EndpointId {
@@ -45,6 +46,30 @@ pub struct EndpointConfig {
 
     /// Additional configuration parameters
     pub parameters: EndpointParameters,
+
+    /// Sync sent to this endpoint while we are the master
+    pub sync: SyncOutput,
+}
+
+/// Sync messages an endpoint receives from us
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SyncOutput {
+    /// Send MIDI Beat Clock with Start, Stop, Continue and Song Position Pointer
+    pub midi_clock: bool,
+
+    /// Send MIDI Time Code quarter frames, and a full frame on relocation
+    pub mtc: bool,
+
+    /// How far ahead to send sync, to make up for the device's latency; negative
+    /// offsets send it late
+    pub offset: TimeOffset,
+}
+
+impl SyncOutput {
+    /// Whether any sync is sent
+    pub fn is_enabled(&self) -> bool {
+        self.midi_clock || self.mtc
+    }
 }
 
 /// Type-specific parameters for endpoints
@@ -82,6 +107,7 @@ impl EndpointConfig {
             parameters: EndpointParameters::Midi {
                 channel: None,  // All channels
             },
+            sync: SyncOutput::default(),
         }
     }
 
@@ -97,6 +123,7 @@ impl EndpointConfig {
                 volume: 1.0,
                 pan: 0.0,
             },
+            sync: SyncOutput::default(),
         }
     }
 
@@ -112,6 +139,12 @@ impl EndpointConfig {
                 plugin_path,
                 plugin_state: None,
             },
+            sync: SyncOutput::default(),
         }
     }
+
+    pub fn with_sync(mut self, sync: SyncOutput) -> Self {
+        self.sync = sync;
+        self
+    }
 }
\ No newline at end of file
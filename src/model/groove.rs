@@ -0,0 +1,150 @@
+use uuid::Uuid;
+use crate::model::midi_clip::MidiClip;
+
+/// How close a note must be to its straight or grooved step to be moved, in steps
+const CAPTURE_STEPS: f64 = 0.25;
+
+/// Unique identifier for a groove template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GrooveId(Uuid);
+
+impl GrooveId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// Feel of one step of a groove
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrooveStep {
+    /// Timing offset from the straight grid, as a fraction of a step (-0.5 to 0.5)
+    pub timing_offset: f64,
+
+    /// Factor applied to the velocity of notes on this step
+    pub velocity_scale: f64,
+
+    /// Factor applied to the length of notes on this step
+    pub length_scale: f64,
+}
+
+impl Default for GrooveStep {
+    fn default() -> Self {
+        Self { timing_offset: 0.0, velocity_scale: 1.0, length_scale: 1.0 }
+    }
+}
+
+/// A repeating feel of per-step timing, velocity and length over a cycle of beats
+///
+/// Templates are independent of PPQ, so one extracted from a clip can be applied to
+/// clips at any resolution.
+#[derive(Debug, Clone)]
+pub struct GrooveTemplate {
+    /// Unique identifier
+    pub id: GrooveId,
+
+    /// User-visible name
+    pub name: String,
+
+    /// Length of the cycle in beats (quarter notes)
+    pub cycle_beats: u32,
+
+    /// Steps spread evenly over the cycle
+    pub steps: Vec<GrooveStep>,
+}
+
+impl GrooveTemplate {
+    /// A straight groove of `steps_per_beat` steps per beat over `cycle_beats` beats
+    pub fn new(name: String, cycle_beats: u32, steps_per_beat: u32) -> Self {
+        let step_count = (cycle_beats.max(1) * steps_per_beat.max(1)) as usize;
+        Self {
+            id: GrooveId::new(),
+            name,
+            cycle_beats: cycle_beats.max(1),
+            steps: vec![GrooveStep::default(); step_count],
+        }
+    }
+
+    /// Measure the feel of a clip against a straight grid
+    ///
+    /// Each note is assigned to its nearest step, folding every cycle of the clip onto
+    /// one. Velocity and length factors are relative to the clip's averages, and steps
+    /// without notes stay straight.
+    pub fn extract(name: String, clip: &MidiClip, cycle_beats: u32, steps_per_beat: u32) -> Self {
+        let mut template = Self::new(name, cycle_beats, steps_per_beat);
+        if clip.notes.is_empty() {
+            return template;
+        }
+
+        let step_ticks = template.step_ticks(clip.ppq);
+        let mut sums = vec![(0.0, 0.0, 0.0, 0u32); template.steps.len()];
+        for note in &clip.notes {
+            let (step, offset) = template.nearest_step(note.start, step_ticks);
+            let sum = &mut sums[step];
+            sum.0 += offset / step_ticks;
+            sum.1 += note.velocity as f64;
+            sum.2 += note.length as f64;
+            sum.3 += 1;
+        }
+
+        let count = clip.notes.len() as f64;
+        let mean_velocity = clip.notes.iter().map(|n| n.velocity as f64).sum::<f64>() / count;
+        let mean_length = clip.notes.iter().map(|n| n.length as f64).sum::<f64>() / count;
+
+        for (step, (offset, velocity, length, notes)) in template.steps.iter_mut().zip(sums) {
+            if notes == 0 {
+                continue;
+            }
+            let notes = notes as f64;
+            step.timing_offset = offset / notes;
+            if mean_velocity > 0.0 {
+                step.velocity_scale = velocity / notes / mean_velocity;
+            }
+            if mean_length > 0.0 {
+                step.length_scale = length / notes / mean_length;
+            }
+        }
+
+        template
+    }
+
+    /// Pull the notes of a clip towards this groove; `strength` is a percentage from 0 to 100
+    ///
+    /// Notes more than a quarter of a step from both the straight and the grooved
+    /// step, such as triplets against a sixteenth groove, are left alone.
+    pub fn apply(&self, clip: &mut MidiClip, strength: f64) {
+        let amount = strength.clamp(0.0, 100.0) / 100.0;
+        let step_ticks = self.step_ticks(clip.ppq);
+        let capture = step_ticks * CAPTURE_STEPS;
+
+        for note in &mut clip.notes {
+            let (step, offset) = self.nearest_step(note.start, step_ticks);
+            let groove = &self.steps[step];
+            if offset.abs() > capture && (offset - groove.timing_offset * step_ticks).abs() > capture {
+                continue;
+            }
+
+            let shift = (groove.timing_offset * step_ticks - offset) * amount;
+            note.start = (note.start as f64 + shift).round().max(0.0) as u64;
+
+            let velocity_scale = 1.0 + (groove.velocity_scale - 1.0) * amount;
+            note.velocity = (note.velocity as f64 * velocity_scale).round().clamp(1.0, 127.0) as u8;
+
+            let length_scale = 1.0 + (groove.length_scale - 1.0) * amount;
+            note.length = (note.length as f64 * length_scale).round().max(1.0) as u64;
+        }
+
+        clip.sort_notes();
+    }
+
+    /// Length of one step in ticks at the given resolution
+    fn step_ticks(&self, ppq: u32) -> f64 {
+        self.cycle_beats as f64 * ppq as f64 / self.steps.len() as f64
+    }
+
+    /// Step nearest to `ticks`, with the signed distance from that step in ticks
+    fn nearest_step(&self, ticks: u64, step_ticks: f64) -> (usize, f64) {
+        let index = (ticks as f64 / step_ticks).round();
+        let offset = ticks as f64 - index * step_ticks;
+        (index as usize % self.steps.len(), offset)
+    }
+}
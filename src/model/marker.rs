@@ -0,0 +1,78 @@
+use uuid::Uuid;
+use crate::model::track::Color;
+use crate::tapestry::TimePosition;
+
+/// Unique identifier for a marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MarkerId(Uuid);
+
+impl MarkerId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+/// A named position on the timeline
+#[derive(Debug, Clone)]
+pub struct Marker {
+    /// Unique identifier
+    pub id: MarkerId,
+
+    /// User-visible name
+    pub name: String,
+
+    /// Display color
+    pub color: Color,
+
+    /// Position on the timeline
+    pub position: TimePosition,
+}
+
+impl Marker {
+    pub fn new(name: String, position: TimePosition) -> Self {
+        Self {
+            id: MarkerId::new(),
+            name,
+            color: Color::new(200, 160, 60),  // Default amber
+            position,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// The region playback loops over when cycling is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleRange {
+    /// Start of the loop (inclusive)
+    pub start: TimePosition,
+
+    /// End of the loop (exclusive), always after `start`
+    pub end: TimePosition,
+
+    /// Whether playback loops over the range
+    pub enabled: bool,
+}
+
+impl CycleRange {
+    /// Create an enabled cycle range, or `None` if it would be empty
+    pub fn new(start: TimePosition, end: TimePosition) -> Option<Self> {
+        (end > start).then_some(Self { start, end, enabled: true })
+    }
+
+    /// Whether a position lies inside the range
+    pub fn contains(&self, position: &TimePosition) -> bool {
+        *position >= self.start && *position < self.end
+    }
+
+    /// Where the playhead is once it has run on to `position`, at or past the end,
+    /// looping back to the start
+    pub fn wrap(&self, position: &TimePosition) -> TimePosition {
+        let overshoot = position.position_ticks.saturating_sub(self.end.position_ticks);
+        let length = self.end.position_ticks - self.start.position_ticks;
+        TimePosition::new(self.start.position_ticks + overshoot % length)
+    }
+}
@@ -0,0 +1,160 @@
+use crate::model::container::{MediaContainer, MidiClipId};
+use crate::tapestry::{QuantizeMode, QuantizeSpan, Quantizer, TempoMap, DEFAULT_PPQ};
+
+/// A single note in a MIDI clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiNote {
+    /// Start in ticks from the start of the clip
+    pub start: u64,
+
+    /// Length in ticks
+    pub length: u64,
+
+    /// MIDI note number (0-127)
+    pub pitch: u8,
+
+    /// Note-on velocity (1-127)
+    pub velocity: u8,
+
+    /// MIDI channel (0-15)
+    pub channel: u8,
+}
+
+impl MidiNote {
+    pub fn new(start: u64, length: u64, pitch: u8, velocity: u8) -> Self {
+        Self { start, length, pitch, velocity, channel: 0 }
+    }
+
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// End in ticks from the start of the clip
+    pub fn end(&self) -> u64 {
+        self.start + self.length
+    }
+}
+
+/// What a `MidiControl` sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlKind {
+    /// Control change, including pedals and bank select
+    Controller { controller: u8, value: u8 },
+
+    /// Program change
+    Program(u8),
+}
+
+/// A controller or program change in a MIDI clip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiControl {
+    /// Time in ticks from the start of the clip
+    pub start: u64,
+
+    /// MIDI channel (0-15)
+    pub channel: u8,
+
+    pub kind: ControlKind,
+}
+
+impl MidiControl {
+    pub fn controller(start: u64, channel: u8, controller: u8, value: u8) -> Self {
+        Self { start, channel, kind: ControlKind::Controller { controller, value } }
+    }
+
+    pub fn program(start: u64, channel: u8, program: u8) -> Self {
+        Self { start, channel, kind: ControlKind::Program(program) }
+    }
+}
+
+/// Note data referenced by `MediaContent::MidiClip`
+///
+/// Note times are musical, in ticks at the clip's PPQ, so the clip follows the
+/// tempo map wherever its container is placed.
+#[derive(Debug, Clone)]
+pub struct MidiClip {
+    /// Unique identifier
+    pub id: MidiClipId,
+
+    /// User-visible name
+    pub name: String,
+
+    /// Resolution of note times, in ticks per quarter note
+    pub ppq: u32,
+
+    /// Length of the clip in ticks
+    pub length: u64,
+
+    /// Notes ordered by start
+    pub notes: Vec<MidiNote>,
+
+    /// Controller and program changes ordered by start
+    pub controls: Vec<MidiControl>,
+}
+
+impl MidiClip {
+    pub fn new(name: String, length: u64) -> Self {
+        Self {
+            id: MidiClipId::new(),
+            name,
+            ppq: DEFAULT_PPQ,
+            length,
+            notes: Vec::new(),
+            controls: Vec::new(),
+        }
+    }
+
+    pub fn with_ppq(mut self, ppq: u32) -> Self {
+        self.ppq = ppq;
+        self
+    }
+
+    /// Add a note, keeping notes ordered by start
+    pub fn add_note(&mut self, note: MidiNote) {
+        let index = self.notes.partition_point(|n| n.start <= note.start);
+        self.notes.insert(index, note);
+    }
+
+    /// Add a controller or program change, keeping changes ordered by start
+    pub fn add_control(&mut self, control: MidiControl) {
+        let index = self.controls.partition_point(|c| c.start <= control.start);
+        self.controls.insert(index, control);
+    }
+
+    /// Move notes towards the grid where `container` first plays them on the timeline
+    ///
+    /// Notes keep no record of their timing as played, so every pass starts from
+    /// their current timing, as an iterative quantize would.
+    pub fn quantize(&mut self, quantizer: &Quantizer, container: &MediaContainer, tempo_map: &TempoMap) {
+        let ppq = self.ppq as f64;
+        let start = tempo_map.position_to_beats(&container.position);
+        let offset = container.start_offset.to_beats(&container.position, tempo_map);
+        let scale = if container.time_scale > 0.0 { container.time_scale } else { 1.0 };
+
+        let to_position = |ticks: u64| tempo_map.beats_to_position(start + (ticks as f64 / ppq - offset) / scale);
+        let to_ticks = |position| {
+            let beats = (tempo_map.position_to_beats(&position) - start) * scale + offset;
+            (beats * ppq).round().max(0.0) as u64
+        };
+
+        for note in &mut self.notes {
+            let on = to_position(note.start);
+            let mut span = QuantizeSpan::new(on, to_position(note.end()).duration_since(on));
+            quantizer.quantize_span(&mut span, tempo_map);
+
+            let start = to_ticks(span.start);
+            if quantizer.mode == QuantizeMode::StartAndEnd {
+                note.length = to_ticks(span.start.saturating_add(span.length)).saturating_sub(start).max(1);
+            }
+            note.start = start;
+        }
+
+        self.sort_notes();
+    }
+
+    /// Restore start order after notes were moved in place
+    pub fn sort_notes(&mut self) {
+        self.notes.sort_by_key(|note| note.start);
+    }
+}
@@ -3,11 +3,19 @@ pub mod timeline;
 pub mod track;
 pub mod container;
 pub mod endpoint;
+pub mod midi_clip;
+pub mod groove;
+pub mod marker;
+pub mod arranger;
 
 // Re-export common types
 pub use project::{Project, ProjectId, ProjectSettings};
-pub use timeline::{Timeline, TimelineId};
-pub use track::{Track, TrackId, TrackType, Color};
+pub use timeline::{Timeline, TimelineId, TrackContainers};
+pub use track::{Track, TrackId, TrackType, Color, Timebase, PreserveMode};
 pub use container::{MediaContainer, ContainerId, MediaContent, PlaybackMode};
 pub use container::{PatternId, MidiClipId, AudioFileId};
-pub use endpoint::{EndpointConfig, EndpointId, EndpointType, EndpointParameters};
\ No newline at end of file
+pub use endpoint::{EndpointConfig, EndpointId, EndpointType, EndpointParameters, SyncOutput};
+pub use midi_clip::{ControlKind, MidiClip, MidiControl, MidiNote};
+pub use groove::{GrooveTemplate, GrooveStep, GrooveId};
+pub use marker::{Marker, MarkerId, CycleRange};
+pub use arranger::{Arrangement, ArrangerSection, ArrangementSpan, PlaylistEntry, SectionId};
@@ -2,7 +2,12 @@ use std::collections::HashMap;
 use uuid::Uuid;
 use crate::model::timeline::{Timeline, TimelineId};
 use crate::model::endpoint::{EndpointConfig, EndpointId};
-use crate::tapestry::{TempoMap, Tempo, TimePosition, TimeSignature};
+use crate::model::container::{ContainerId, MediaContent, MidiClipId};
+use crate::model::groove::{GrooveId, GrooveTemplate};
+use crate::model::midi_clip::MidiClip;
+use crate::model::track::PreserveMode;
+use crate::tapestry::{TempoMap, TempoMapError, Tempo, TimePosition, TimeSignature, DEFAULT_PPQ};
+use crate::tapestry::{FrameRate, Grid, NoteValue, Quantizer, Timecode};
 
 /// Unique identifier for a project
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -44,8 +49,11 @@ pub struct ProjectSettings {
     /// Default grid size in beats
     pub grid_size: f64,
 
-    /// Auto-quantize MIDI input
-    pub auto_quantize: bool,
+    /// Resolution of displayed and entered BBT positions, in ticks per quarter note
+    pub ppq: u32,
+
+    /// Timecode at the start of the timeline; its rate is the project frame rate
+    pub timecode_start: Timecode,
 }
 
 impl Default for ProjectSettings {
@@ -60,11 +68,24 @@ impl Default for ProjectSettings {
             default_note_duration: 0.25,  // 16th note by default
             snap_to_grid: true,
             grid_size: 0.25,              // 16th note grid by default
-            auto_quantize: true,
+            ppq: DEFAULT_PPQ,
+            timecode_start: Timecode::zero(FrameRate::Fps30),
         }
     }
 }
 
+impl ProjectSettings {
+    /// Grid size as a note value, the default subdivision for grid lines
+    pub fn grid_subdivision(&self) -> NoteValue {
+        NoteValue::from_beats(self.grid_size)
+    }
+
+    /// Full-strength quantizer on the project grid, e.g. for snapping
+    pub fn quantizer(&self) -> Quantizer {
+        Quantizer::new(self.grid_subdivision())
+    }
+}
+
 /// Represents the main project container
 #[derive(Debug, Clone)]
 pub struct Project {
@@ -89,6 +110,12 @@ pub struct Project {
     /// Configured output endpoints
     pub endpoints: HashMap<EndpointId, EndpointConfig>,
 
+    /// MIDI note data referenced by containers
+    pub midi_clips: HashMap<MidiClipId, MidiClip>,
+
+    /// Groove templates shared between tracks
+    pub grooves: HashMap<GrooveId, GrooveTemplate>,
+
     /// The currently active timeline
     pub active_timeline_id: Option<TimelineId>,
 }
@@ -98,7 +125,7 @@ impl Project {
         // Create a default tempo map
         let mut tempo_map = TempoMap::new(44100, 44100);
         tempo_map.add_tempo_change(TimePosition::zero(), Tempo::new(120.0));
-        tempo_map.add_time_signature_change(TimePosition::zero(), TimeSignature::new(4, 4));
+        tempo_map.add_time_signature_change(TimePosition::zero(), TimeSignature::common_time());
 
         // Create a default timeline
         let timeline = Timeline::new("Main".to_string());
@@ -115,6 +142,8 @@ impl Project {
             tempo_map,
             timelines,
             endpoints: HashMap::new(),
+            midi_clips: HashMap::new(),
+            grooves: HashMap::new(),
             active_timeline_id: Some(timeline_id),
         }
     }
@@ -150,6 +179,82 @@ impl Project {
         }
     }
 
+    /// Edit the tempo map, re-anchoring containers on every timeline
+    ///
+    /// `preserve` chooses whether containers keep their musical position, their
+    /// absolute time, or follow their timebase. If the edit fails the map is left
+    /// untouched. Returns the containers that moved, with their new positions.
+    pub fn update_tempo_map(
+        &mut self,
+        preserve: PreserveMode,
+        edit: impl FnOnce(&mut TempoMap) -> Result<(), TempoMapError>,
+    ) -> Result<Vec<(ContainerId, TimePosition)>, TempoMapError> {
+        let old_map = self.tempo_map.clone();
+        if let Err(error) = edit(&mut self.tempo_map) {
+            self.tempo_map = old_map;
+            return Err(error);
+        }
+
+        let moved = self.timelines.values_mut()
+            .flat_map(|timeline| timeline.retime_containers(&old_map, &self.tempo_map, preserve))
+            .collect();
+        self.version += 1;
+        Ok(moved)
+    }
+
+    /// Replace the active timeline with its arranger playlist played out end to end
+    ///
+    /// The tempo map is laid out in song order to match, and containers on the other
+    /// timelines are re-anchored to it by their timebase.
+    pub fn flatten_arrangement(&mut self) -> Result<(), &'static str> {
+        let active_id = self.active_timeline_id.ok_or("No active timeline")?;
+        let timeline = self.timelines.get_mut(&active_id).ok_or("No active timeline")?;
+        let midi_clips = &self.midi_clips;
+        let song_map = timeline.flatten_arrangement(&self.tempo_map, |content| match content {
+            MediaContent::MidiClip(clip_id) => midi_clips.get(clip_id).map(|clip| clip.length as f64 / clip.ppq as f64),
+            _ => None,
+        })?;
+
+        let old_map = std::mem::replace(&mut self.tempo_map, song_map);
+        for (_, timeline) in self.timelines.iter_mut().filter(|(id, _)| **id != active_id) {
+            timeline.retime_containers(&old_map, &self.tempo_map, PreserveMode::FollowTimebase);
+        }
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Quantize the notes of the MIDI clip in a container, on the grid where the container plays them
+    pub fn quantize_clip(&mut self, container_id: ContainerId, quantizer: &Quantizer) -> Result<MidiClipId, &'static str> {
+        let container = self.active_timeline_id
+            .and_then(|id| self.timelines.get(&id))
+            .and_then(|timeline| timeline.containers.get(&container_id))
+            .ok_or("Container not found")?;
+        let MediaContent::MidiClip(clip_id) = container.content else {
+            return Err("Container does not hold a MIDI clip");
+        };
+
+        let clip = self.midi_clips.get_mut(&clip_id).ok_or("MIDI clip not found")?;
+        clip.quantize(quantizer, container, &self.tempo_map);
+        self.version += 1;
+        Ok(clip_id)
+    }
+
+    /// Grid lines between two positions, subdivided by the project grid size
+    pub fn grid(&self, start: TimePosition, end: TimePosition) -> Grid<'_> {
+        Grid::new(&self.tempo_map, start, end, self.settings.ppq)
+            .with_subdivision(self.settings.grid_subdivision())
+    }
+
+    /// Get the timecode of a timeline position
+    pub fn position_to_timecode(&self, position: &TimePosition) -> Timecode {
+        Timecode::from_position(position, &self.settings.timecode_start, self.settings.reference_sample_rate)
+    }
+
+    /// Get the timeline position of a timecode
+    pub fn timecode_to_position(&self, timecode: &Timecode) -> TimePosition {
+        timecode.to_position(&self.settings.timecode_start, self.settings.reference_sample_rate)
+    }
+
     /// Add an output endpoint configuration
     pub fn add_endpoint(&mut self, config: EndpointConfig) -> EndpointId {
         let id = config.id;
@@ -167,4 +272,42 @@ impl Project {
     pub fn endpoint_mut(&mut self, id: EndpointId) -> Option<&mut EndpointConfig> {
         self.endpoints.get_mut(&id)
     }
-}
\ No newline at end of file
+
+    /// Add a MIDI clip
+    pub fn add_midi_clip(&mut self, clip: MidiClip) -> MidiClipId {
+        let id = clip.id;
+        self.midi_clips.insert(id, clip);
+        self.version += 1;
+        id
+    }
+
+    /// Add a groove template
+    pub fn add_groove(&mut self, groove: GrooveTemplate) -> GrooveId {
+        let id = groove.id;
+        self.grooves.insert(id, groove);
+        self.version += 1;
+        id
+    }
+
+    /// Extract a groove template from a clip and add it to the project
+    pub fn extract_groove(
+        &mut self,
+        clip_id: MidiClipId,
+        name: String,
+        cycle_beats: u32,
+        steps_per_beat: u32,
+    ) -> Result<GrooveId, &'static str> {
+        let clip = self.midi_clips.get(&clip_id).ok_or("MIDI clip not found")?;
+        let groove = GrooveTemplate::extract(name, clip, cycle_beats, steps_per_beat);
+        Ok(self.add_groove(groove))
+    }
+
+    /// Apply a groove template to a clip at a strength from 0 to 100 percent
+    pub fn apply_groove(&mut self, groove_id: GrooveId, clip_id: MidiClipId, strength: f64) -> Result<(), &'static str> {
+        let groove = self.grooves.get(&groove_id).ok_or("Groove not found")?;
+        let clip = self.midi_clips.get_mut(&clip_id).ok_or("MIDI clip not found")?;
+        groove.apply(clip, strength);
+        self.version += 1;
+        Ok(())
+    }
+}
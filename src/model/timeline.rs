@@ -1,8 +1,10 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use uuid::Uuid;
-use crate::model::track::{Track, TrackId};
-use crate::model::container::{MediaContainer, ContainerId};
-use crate::tapestry::TimePosition;
+use crate::model::track::{PreserveMode, Timebase, Track, TrackId};
+use crate::model::container::{MediaContainer, MediaContent, ContainerId};
+use crate::model::marker::{CycleRange, Marker, MarkerId};
+use crate::model::arranger::Arrangement;
+use crate::tapestry::{Duration, TempoMap, TimeOffset, TimePosition};
 
 /// Unique identifier for a timeline
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -14,6 +16,16 @@ impl TimelineId {
     }
 }
 
+/// Containers on one track, ordered by position
+///
+/// The ID is part of the key so containers starting at the same position are all kept.
+pub type TrackContainers = BTreeSet<(TimePosition, ContainerId)>;
+
+/// First key at `position` in `TrackContainers`, for range lookups
+fn at(position: TimePosition) -> (TimePosition, ContainerId) {
+    (position, ContainerId::lowest())
+}
+
 /// Represents a timeline with tracks and containers
 #[derive(Debug, Clone)]
 pub struct Timeline {
@@ -32,7 +44,19 @@ pub struct Timeline {
 
     /// Maps track IDs to the containers on that track
     /// Containers within a track are ordered by position for efficient lookup
-    pub track_containers: HashMap<TrackId, BTreeMap<TimePosition, ContainerId>>,
+    pub track_containers: HashMap<TrackId, TrackContainers>,
+
+    /// Named positions
+    pub markers: HashMap<MarkerId, Marker>,
+
+    /// Numbered locators
+    pub locators: BTreeMap<u8, TimePosition>,
+
+    /// Loop region, if one has been set
+    pub cycle: Option<CycleRange>,
+
+    /// Arranger sections and playlist
+    pub arrangement: Arrangement,
 }
 
 impl Timeline {
@@ -43,6 +67,10 @@ impl Timeline {
             tracks: Vec::new(),
             containers: HashMap::new(),
             track_containers: HashMap::new(),
+            markers: HashMap::new(),
+            locators: BTreeMap::new(),
+            cycle: None,
+            arrangement: Arrangement::default(),
         }
     }
 
@@ -50,7 +78,7 @@ impl Timeline {
     pub fn add_track(&mut self, track: Track) -> TrackId {
         let id = track.id;
         self.tracks.push(track);
-        self.track_containers.insert(id, BTreeMap::new());
+        self.track_containers.insert(id, TrackContainers::new());
         id
     }
 
@@ -64,6 +92,12 @@ impl Timeline {
         self.tracks.iter_mut().find(|t| t.id == id)
     }
 
+    /// Tracks that play: not muted, and soloed if any track is
+    pub fn audible_tracks(&self) -> impl Iterator<Item = &Track> {
+        let soloing = self.tracks.iter().any(|track| track.is_solo);
+        self.tracks.iter().filter(move |track| !track.is_muted && (track.is_solo || !soloing))
+    }
+
     /// Add a container to a track
     pub fn add_container(&mut self, track_id: TrackId, container: MediaContainer) -> ContainerId {
         let id = container.id;
@@ -74,7 +108,7 @@ impl Timeline {
 
         // Add to the track's container map
         if let Some(track_map) = self.track_containers.get_mut(&track_id) {
-            track_map.insert(position, id);
+            track_map.insert((position, id));
         }
 
         id
@@ -120,8 +154,8 @@ impl Timeline {
 
             // Update the track's container map
             if let Some(track_map) = self.track_containers.get_mut(&tid) {
-                track_map.remove(&old_pos);
-                track_map.insert(new_position, id);
+                track_map.remove(&(old_pos, id));
+                track_map.insert((new_position, id));
                 return true;
             }
         }
@@ -129,6 +163,62 @@ impl Timeline {
         false
     }
 
+    /// Move a container by a signed offset, refusing moves before the start of the timeline
+    pub fn nudge_container(&mut self, id: ContainerId, offset: TimeOffset) -> bool {
+        let new_position = match self.containers.get(&id).and_then(|c| c.position.checked_offset(offset)) {
+            Some(position) => position,
+            None => return false,
+        };
+
+        self.move_container(id, new_position)
+    }
+
+    /// Time domain a container follows, taking its track's timebase unless overridden
+    pub fn container_timebase(&self, id: ContainerId) -> Option<Timebase> {
+        let container = self.containers.get(&id)?;
+        if let Some(timebase) = container.timebase {
+            return Some(timebase);
+        }
+
+        let track_id = self.track_containers.iter()
+            .find(|(_, containers)| containers.contains(&(container.position, id)))
+            .map(|(track_id, _)| *track_id)?;
+        self.track(track_id).map(|track| track.timebase)
+    }
+
+    /// Re-anchor every container after the tempo map changed from `old_map` to `new_map`
+    ///
+    /// Returns the containers whose position changed, with their new positions.
+    pub fn retime_containers(
+        &mut self,
+        old_map: &TempoMap,
+        new_map: &TempoMap,
+        preserve: PreserveMode,
+    ) -> Vec<(ContainerId, TimePosition)> {
+        let mut moved = Vec::new();
+
+        for track in &self.tracks {
+            let Some(track_map) = self.track_containers.get_mut(&track.id) else {
+                continue;
+            };
+
+            let mut retimed = TrackContainers::new();
+            for (old_position, container_id) in std::mem::take(track_map) {
+                if let Some(container) = self.containers.get_mut(&container_id) {
+                    let timebase = preserve.resolve(container.timebase.unwrap_or(track.timebase));
+                    container.retime(timebase, old_map, new_map);
+                    if container.position != old_position {
+                        moved.push((container_id, container.position));
+                    }
+                    retimed.insert((container.position, container_id));
+                }
+            }
+            *track_map = retimed;
+        }
+
+        moved
+    }
+
     /// Get all containers in a time range for a specific track
     pub fn track_containers_in_range(
         &self,
@@ -139,18 +229,18 @@ impl Timeline {
         let mut result = Vec::new();
 
         if let Some(track_map) = self.track_containers.get(&track_id) {
-            // Use BTreeMap range query to find containers that start in the range
-            for (_, container_id) in track_map.range(start..end) {
+            // Use a range query to find containers that start in the range
+            for (_, container_id) in track_map.range(at(*start)..at(*end)) {
                 if let Some(container) = self.containers.get(container_id) {
                     result.push(container);
                 }
             }
 
             // Also find containers that start before but extend into the range
-            for (_, container_id) in track_map.range(..start) {
+            for (_, container_id) in track_map.range(..at(*start)) {
                 if let Some(container) = self.containers.get(container_id) {
-                    // Calculate end position of the container
-                    let container_end = container.position + container.length;
+                    // Calculate end position of the container, clamped to the end of the timeline
+                    let container_end = container.position.saturating_add(container.length);
                     if &container_end > start {
                         result.push(container);
                     }
@@ -176,4 +266,150 @@ impl Timeline {
 
         result
     }
-}
\ No newline at end of file
+
+    /// Add a marker
+    pub fn add_marker(&mut self, marker: Marker) -> MarkerId {
+        let id = marker.id;
+        self.markers.insert(id, marker);
+        id
+    }
+
+    /// Get a marker by ID
+    pub fn marker(&self, id: MarkerId) -> Option<&Marker> {
+        self.markers.get(&id)
+    }
+
+    /// Move a marker to a new position
+    pub fn move_marker(&mut self, id: MarkerId, position: TimePosition) -> bool {
+        match self.markers.get_mut(&id) {
+            Some(marker) => {
+                marker.position = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a marker
+    pub fn remove_marker(&mut self, id: MarkerId) -> Option<Marker> {
+        self.markers.remove(&id)
+    }
+
+    /// All markers ordered by position
+    pub fn markers_by_position(&self) -> Vec<&Marker> {
+        let mut markers: Vec<&Marker> = self.markers.values().collect();
+        markers.sort_by_key(|marker| marker.position);
+        markers
+    }
+
+    /// Set a numbered locator, replacing any previous position
+    pub fn set_locator(&mut self, number: u8, position: TimePosition) {
+        self.locators.insert(number, position);
+    }
+
+    /// Get the position of a numbered locator
+    pub fn locator(&self, number: u8) -> Option<TimePosition> {
+        self.locators.get(&number).copied()
+    }
+
+    /// Remove a numbered locator
+    pub fn remove_locator(&mut self, number: u8) -> Option<TimePosition> {
+        self.locators.remove(&number)
+    }
+
+    /// Set the loop region, keeping whether cycling is enabled
+    pub fn set_cycle(&mut self, start: TimePosition, end: TimePosition) -> Result<(), &'static str> {
+        let mut cycle = CycleRange::new(start, end).ok_or("Cycle end must be after its start")?;
+        if let Some(previous) = self.cycle {
+            cycle.enabled = previous.enabled;
+        }
+        self.cycle = Some(cycle);
+        Ok(())
+    }
+
+    /// Turn looping over the cycle range on or off
+    pub fn set_cycle_enabled(&mut self, enabled: bool) -> bool {
+        match &mut self.cycle {
+            Some(cycle) => {
+                cycle.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the loop region
+    pub fn clear_cycle(&mut self) {
+        self.cycle = None;
+    }
+
+    /// Timeline ranges to play between two playback positions
+    ///
+    /// Playback positions are song time when the arrangement is active, and timeline
+    /// positions otherwise.
+    pub fn playback_ranges(&self, from: &TimePosition, to: &TimePosition) -> Vec<(TimePosition, TimePosition)> {
+        if self.arrangement.is_active() {
+            self.arrangement.timeline_ranges(from, to)
+        } else {
+            vec![(*from, *to)]
+        }
+    }
+
+    /// Replace the timeline contents with the playlist played out end to end
+    ///
+    /// Every span of the playlist gets copies of the containers playing in its section,
+    /// including those started before it, which are cropped to start with the section,
+    /// and those running past it, which are cut short at its end. `content_beats` gives
+    /// the length of a container's content, to crop loops. The copies keep their place
+    /// in beats from the start of their section on the tempo map laid out in song order,
+    /// which is returned to replace `tempo_map`. The arrangement is cleared afterwards;
+    /// markers are left as they are.
+    pub fn flatten_arrangement(
+        &mut self,
+        tempo_map: &TempoMap,
+        content_beats: impl Fn(&MediaContent) -> Option<f64>,
+    ) -> Result<TempoMap, &'static str> {
+        let spans = self.arrangement.spans();
+        if spans.is_empty() {
+            return Err("The arranger playlist is empty");
+        }
+        let song_map = self.arrangement.song_tempo_map(tempo_map)?;
+
+        let mut containers = HashMap::new();
+        let mut track_containers: HashMap<TrackId, TrackContainers> = self.track_containers.keys()
+            .map(|track_id| (*track_id, TrackContainers::new()))
+            .collect();
+
+        for span in spans {
+            let section_end = span.source_start.saturating_add(span.length);
+            let song_start_beats = song_map.position_to_beats(&span.song_start);
+
+            for track_id in self.track_containers.keys() {
+                for container in self.track_containers_in_range(*track_id, &span.source_start, &section_end) {
+                    for mut copy in container.played_from(span.source_start, content_beats(&container.content), tempo_map) {
+                        let beats = tempo_map.duration_to_beats(&span.source_start, &copy.position.duration_since(span.source_start));
+                        let start_offset = copy.start_offset.to_beats(&copy.position, tempo_map);
+                        copy.position = song_map.beats_to_position(song_start_beats + beats);
+                        copy.start_offset = Duration::from_beats(start_offset, &copy.position, &song_map);
+                        copy.refresh_length(&song_map);
+
+                        let remaining = span.song_end().duration_since(copy.position);
+                        if copy.length.ticks() > remaining.ticks() {
+                            copy.set_length(remaining, &song_map);
+                        }
+
+                        if let Some(flat_map) = track_containers.get_mut(track_id) {
+                            flat_map.insert((copy.position, copy.id));
+                        }
+                        containers.insert(copy.id, copy);
+                    }
+                }
+            }
+        }
+
+        self.containers = containers;
+        self.track_containers = track_containers;
+        self.arrangement = Arrangement::default();
+        Ok(song_map)
+    }
+}
@@ -1,4 +1,3 @@
-use std::sync::Arc;
 use uuid::Uuid;
 use crate::model::endpoint::EndpointId;
 
@@ -35,6 +34,42 @@ pub enum TrackType {
     Automation,
 }
 
+/// Defines which time domain an item is anchored to when the tempo map changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timebase {
+    /// Stays on the same bar and beat, moving in time (e.g. MIDI material)
+    #[default]
+    Beats,
+
+    /// Stays at the same absolute time, moving against the grid (e.g. sound effects)
+    Time,
+}
+
+/// Defines what containers hold on to when the tempo map is edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreserveMode {
+    /// Each container follows its own (or its track's) timebase
+    #[default]
+    FollowTimebase,
+
+    /// Every container keeps its bar and beat
+    Musical,
+
+    /// Every container keeps its absolute time
+    Absolute,
+}
+
+impl PreserveMode {
+    /// Timebase to re-anchor with, given the one the container would normally follow
+    pub fn resolve(&self, timebase: Timebase) -> Timebase {
+        match self {
+            PreserveMode::FollowTimebase => timebase,
+            PreserveMode::Musical => Timebase::Beats,
+            PreserveMode::Absolute => Timebase::Time,
+        }
+    }
+}
+
 /// Represents a track in the timeline
 #[derive(Debug, Clone)]
 pub struct Track {
@@ -61,6 +96,12 @@ pub struct Track {
 
     /// Track height in the UI (in pixels)
     pub height: u32,
+
+    /// Time domain containers on this track follow unless they override it
+    pub timebase: Timebase,
+
+    /// Re-trigger notes still held when playback starts or jumps into them
+    pub chase_notes: bool,
 }
 
 impl Track {
@@ -74,6 +115,11 @@ impl Track {
             is_muted: false,
             is_solo: false,
             height: 100,  // Default height
+            timebase: match track_type {
+                TrackType::Audio => Timebase::Time,
+                _ => Timebase::Beats,
+            },
+            chase_notes: false,
         }
     }
 
@@ -86,4 +132,14 @@ impl Track {
         self.output_id = Some(output_id);
         self
     }
+
+    pub fn with_timebase(mut self, timebase: Timebase) -> Self {
+        self.timebase = timebase;
+        self
+    }
+
+    pub fn with_chase_notes(mut self, chase_notes: bool) -> Self {
+        self.chase_notes = chase_notes;
+        self
+    }
 }
\ No newline at end of file
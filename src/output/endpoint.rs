@@ -1,6 +1,8 @@
 // src/output/endpoint.rs
 use std::error::Error;
+use std::time::Instant;
 use crate::output::event::OutputEvent;
+use crate::output::jitter::JitterStats;
 
 /// Trait for output endpoints that can receive events
 pub trait OutputEndpoint: Send + Sync {
@@ -21,4 +23,24 @@ pub trait OutputEndpoint: Send + Sync {
 
     /// Get the type of this endpoint
     fn endpoint_type(&self) -> crate::model::EndpointType;
+
+    /// Whether this endpoint can hold events and deliver them at their timestamps
+    fn schedules(&self) -> bool {
+        false
+    }
+
+    /// Queue an event for delivery at `at`
+    ///
+    /// Only called on endpoints that schedule; others get each event when it is due.
+    fn send_event_at(&mut self, event: &OutputEvent, _at: Instant) -> Result<(), Box<dyn Error>> {
+        self.send_event(event)
+    }
+
+    /// Drop events queued for later delivery
+    fn cancel_scheduled(&mut self) {}
+
+    /// How far from their timestamps queued events were delivered
+    fn jitter(&self) -> Option<JitterStats> {
+        None
+    }
 }
\ No newline at end of file
@@ -1,8 +1,9 @@
 // src/output/event.rs
 use std::sync::Arc;
+use crate::tapestry::Timecode;
 
 /// Types of output events that can be sent to endpoints
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OutputEventType {
     // MIDI events
     MidiNoteOn { channel: u8, note: u8, velocity: u8 },
@@ -20,25 +21,43 @@ pub enum OutputEventType {
     VstParameter { parameter_id: u32, value: f32 },
 
     // Clock-related events
+    /// One MIDI Beat Clock tick, 24 per quarter note
     SyncPulse,
+    SyncStart,
+    SyncStop,
+    SyncContinue,
+    /// Song Position Pointer, in sixteenth notes from the start
+    SongPosition { sixteenths: u16 },
+    /// One piece of the MTC quarter-frame sequence describing `timecode`
+    MtcQuarterFrame { piece: u8, timecode: Timecode },
+    /// MTC full-frame message, sent when the position jumps
+    MtcFullFrame { timecode: Timecode },
 
     // System events
     EndOfTrack,
 }
 
 /// An event to be sent to an output endpoint
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OutputEvent {
     /// The type of event
     pub event_type: OutputEventType,
 
     /// Target endpoint ID (if specific) or None for broadcast
     pub target: Option<crate::model::EndpointId>,
+
+    /// Track the event was played for, so its notes can be released when it is muted
+    pub source: Option<crate::model::TrackId>,
 }
 
 impl OutputEvent {
     pub fn new(event_type: OutputEventType, target: Option<crate::model::EndpointId>) -> Self {
-        Self { event_type, target }
+        Self { event_type, target, source: None }
+    }
+
+    pub fn with_source(mut self, track_id: crate::model::TrackId) -> Self {
+        self.source = Some(track_id);
+        self
     }
 
     pub fn midi_note_on(channel: u8, note: u8, velocity: u8, target: Option<crate::model::EndpointId>) -> Self {
@@ -65,6 +84,19 @@ impl OutputEvent {
         )
     }
 
+    /// Whether this is a sync message, sent only to endpoints that ask for sync
+    pub fn is_sync(&self) -> bool {
+        matches!(self.event_type,
+            OutputEventType::SyncPulse |
+            OutputEventType::SyncStart |
+            OutputEventType::SyncStop |
+            OutputEventType::SyncContinue |
+            OutputEventType::SongPosition { .. } |
+            OutputEventType::MtcQuarterFrame { .. } |
+            OutputEventType::MtcFullFrame { .. }
+        )
+    }
+
     pub fn is_audio(&self) -> bool {
         matches!(self.event_type, OutputEventType::AudioBuffer { .. })
     }
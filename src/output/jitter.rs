@@ -0,0 +1,76 @@
+// src/output/jitter.rs
+use std::time::Instant;
+
+/// How far from their timestamps events were actually delivered
+///
+/// Lateness is in seconds, negative for events delivered early.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JitterStats {
+    count: u64,
+    sum: f64,
+    sum_squares: f64,
+    earliest: f64,
+    latest: f64,
+}
+
+impl JitterStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event meant for `target` that went out at `delivered`
+    pub fn record_delivery(&mut self, target: Instant, delivered: Instant) {
+        let lateness = if delivered >= target {
+            (delivered - target).as_secs_f64()
+        } else {
+            -(target - delivered).as_secs_f64()
+        };
+        self.record(lateness);
+    }
+
+    /// Record an event delivered `lateness` seconds after its timestamp
+    pub fn record(&mut self, lateness: f64) {
+        if self.count == 0 {
+            self.earliest = lateness;
+            self.latest = lateness;
+        } else {
+            self.earliest = self.earliest.min(lateness);
+            self.latest = self.latest.max(lateness);
+        }
+        self.count += 1;
+        self.sum += lateness;
+        self.sum_squares += lateness * lateness;
+    }
+
+    /// Number of events recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Average lateness
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.sum / self.count as f64
+    }
+
+    /// Standard deviation of the lateness, the jitter proper
+    pub fn std_dev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        (self.sum_squares / self.count as f64 - mean * mean).max(0.0).sqrt()
+    }
+
+    /// Lateness of the earliest event
+    pub fn earliest(&self) -> f64 {
+        self.earliest
+    }
+
+    /// Lateness of the latest event
+    pub fn latest(&self) -> f64 {
+        self.latest
+    }
+}
@@ -1,17 +1,43 @@
 // src/output/midi.rs
+use std::collections::BTreeMap;
 use std::error::Error;
-use midir::{MidiOutput, MidiOutputPort, MidiOutputConnection};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use midir::{MidiOutput, MidiOutputConnection};
 
 use crate::model::{EndpointId, EndpointType};
+use crate::tapestry::FrameRate;
 use crate::output::event::{OutputEvent, OutputEventType};
 use crate::output::endpoint::OutputEndpoint;
+use crate::output::jitter::JitterStats;
+
+/// How long before a message is due the delivery thread stops sleeping and spins,
+/// since sleeps tend to overshoot
+const SPIN_AHEAD: Duration = Duration::from_millis(1);
 
 pub struct MidiOutputEndpoint {
+    #[allow(dead_code)]
     id: EndpointId,
     name: String,
+    #[allow(dead_code)]
     port_name: String,
     port_index: usize,
-    connection: Option<MidiOutputConnection>,
+    connection: Option<Connection>,
+    /// Timing of scheduled messages, kept across reconnections
+    jitter: Arc<Mutex<JitterStats>>,
+}
+
+/// An open port and the thread that sends timestamped messages to it
+struct Connection {
+    port: Arc<Mutex<MidiOutputConnection>>,
+    delivery: mpsc::Sender<Delivery>,
+    thread: JoinHandle<()>,
+}
+
+enum Delivery {
+    Message { at: Instant, bytes: Vec<u8> },
+    Cancel,
 }
 
 impl MidiOutputEndpoint {
@@ -22,12 +48,13 @@ impl MidiOutputEndpoint {
             port_name,
             port_index,
             connection: None,
+            jitter: Arc::new(Mutex::new(JitterStats::new())),
         }
     }
 
     fn send_midi_message(&mut self, message: &[u8]) -> Result<(), Box<dyn Error>> {
-        if let Some(conn) = &mut self.connection {
-            conn.send(message)?;
+        if let Some(conn) = &self.connection {
+            conn.port.lock().unwrap().send(message)?;
             Ok(())
         } else {
             Err("MIDI device not connected".into())
@@ -57,64 +84,179 @@ impl OutputEndpoint for MidiOutputEndpoint {
         }
 
         let port = &ports[self.port_index];
-        let conn = midi_out.connect(port, "loom-output")?;
+        let port = Arc::new(Mutex::new(midi_out.connect(port, "loom-output")?));
+
+        let (delivery, queue) = mpsc::channel();
+        let thread = {
+            let port = Arc::clone(&port);
+            let jitter = Arc::clone(&self.jitter);
+            thread::spawn(move || deliver(&port, &queue, &jitter))
+        };
 
-        self.connection = Some(conn);
+        self.connection = Some(Connection { port, delivery, thread });
         Ok(())
     }
 
     fn disconnect(&mut self) {
-        self.connection = None;
+        if let Some(Connection { delivery, thread, .. }) = self.connection.take() {
+            // Closing the queue stops the thread, dropping anything still waiting
+            drop(delivery);
+            let _ = thread.join();
+        }
     }
 
     fn send_event(&mut self, event: &OutputEvent) -> Result<(), Box<dyn Error>> {
-        match &event.event_type {
-            OutputEventType::MidiNoteOn { channel, note, velocity } => {
-                let message = [0x90 | (channel & 0x0F), *note, *velocity];
-                self.send_midi_message(&message)
-            }
+        let message = midi_message(event)?;
+        self.send_midi_message(&message)
+    }
 
-            OutputEventType::MidiNoteOff { channel, note } => {
-                let message = [0x80 | (channel & 0x0F), *note, 0];
-                self.send_midi_message(&message)
-            }
+    fn endpoint_type(&self) -> EndpointType {
+        EndpointType::Midi
+    }
 
-            OutputEventType::MidiControlChange { channel, controller, value } => {
-                let message = [0xB0 | (channel & 0x0F), *controller, *value];
-                self.send_midi_message(&message)
-            }
+    fn schedules(&self) -> bool {
+        self.is_connected()
+    }
 
-            OutputEventType::MidiProgramChange { channel, program } => {
-                let message = [0xC0 | (channel & 0x0F), *program];
-                self.send_midi_message(&message)
-            }
+    fn send_event_at(&mut self, event: &OutputEvent, at: Instant) -> Result<(), Box<dyn Error>> {
+        let Some(conn) = &self.connection else {
+            return Err("MIDI device not connected".into());
+        };
+        let bytes = midi_message(event)?;
+        conn.delivery.send(Delivery::Message { at, bytes })
+            .map_err(|_| "MIDI delivery thread has stopped".into())
+    }
 
-            OutputEventType::MidiPitchBend { channel, value } => {
-                // Convert i16 (-8192 to 8191) to 14-bit value (0 to 16383)
-                let bend_value = (*value + 8192) as u16;
-                let message = [
-                    0xE0 | (channel & 0x0F),
-                    (bend_value & 0x7F) as u8,         // LSB
-                    ((bend_value >> 7) & 0x7F) as u8   // MSB
-                ];
-                self.send_midi_message(&message)
-            }
+    fn cancel_scheduled(&mut self) {
+        if let Some(conn) = &self.connection {
+            let _ = conn.delivery.send(Delivery::Cancel);
+        }
+    }
 
-            OutputEventType::MidiAftertouch { channel, pressure } => {
-                let message = [0xD0 | (channel & 0x0F), *pressure];
-                self.send_midi_message(&message)
+    fn jitter(&self) -> Option<JitterStats> {
+        Some(*self.jitter.lock().unwrap())
+    }
+}
+
+/// Send queued messages to `port` at their timestamps until the queue is closed
+fn deliver(port: &Mutex<MidiOutputConnection>, queue: &mpsc::Receiver<Delivery>, jitter: &Mutex<JitterStats>) {
+    // Waiting messages by timestamp, then by arrival so equal times keep their order
+    let mut waiting: BTreeMap<(Instant, u64), Vec<u8>> = BTreeMap::new();
+    let mut arrivals = 0u64;
+
+    loop {
+        let received = match waiting.keys().next() {
+            Some(&(at, _)) => {
+                let wake = at.checked_sub(SPIN_AHEAD).unwrap_or(at);
+                queue.recv_timeout(wake.saturating_duration_since(Instant::now()))
             }
+            None => queue.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+        };
 
-            OutputEventType::MidiPolyAftertouch { channel, note, pressure } => {
-                let message = [0xA0 | (channel & 0x0F), *note, *pressure];
-                self.send_midi_message(&message)
+        match received {
+            Ok(Delivery::Message { at, bytes }) => {
+                waiting.insert((at, arrivals), bytes);
+                arrivals += 1;
+            }
+            Ok(Delivery::Cancel) => waiting.clear(),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let Some(((at, _), bytes)) = waiting.pop_first() else {
+                    continue;
+                };
+                while Instant::now() < at {
+                    thread::yield_now();
+                }
+                let delivered = Instant::now();
+                let _ = port.lock().unwrap().send(&bytes);
+                jitter.lock().unwrap().record_delivery(at, delivered);
             }
+        }
+    }
+}
+
+/// Bytes of the MIDI message for an event
+fn midi_message(event: &OutputEvent) -> Result<Vec<u8>, Box<dyn Error>> {
+    match &event.event_type {
+        OutputEventType::MidiNoteOn { channel, note, velocity } => {
+            Ok(vec![0x90 | (channel & 0x0F), *note, *velocity])
+        }
+
+        OutputEventType::MidiNoteOff { channel, note } => {
+            Ok(vec![0x80 | (channel & 0x0F), *note, 0])
+        }
+
+        OutputEventType::MidiControlChange { channel, controller, value } => {
+            Ok(vec![0xB0 | (channel & 0x0F), *controller, *value])
+        }
+
+        OutputEventType::MidiProgramChange { channel, program } => {
+            Ok(vec![0xC0 | (channel & 0x0F), *program])
+        }
+
+        OutputEventType::MidiPitchBend { channel, value } => {
+            // Convert i16 (-8192 to 8191) to 14-bit value (0 to 16383)
+            let bend_value = (*value + 8192) as u16;
+            Ok(vec![
+                0xE0 | (channel & 0x0F),
+                (bend_value & 0x7F) as u8,         // LSB
+                ((bend_value >> 7) & 0x7F) as u8   // MSB
+            ])
+        }
+
+        OutputEventType::MidiAftertouch { channel, pressure } => {
+            Ok(vec![0xD0 | (channel & 0x0F), *pressure])
+        }
+
+        OutputEventType::MidiPolyAftertouch { channel, note, pressure } => {
+            Ok(vec![0xA0 | (channel & 0x0F), *note, *pressure])
+        }
+
+        OutputEventType::SyncPulse => Ok(vec![0xF8]),
+        OutputEventType::SyncStart => Ok(vec![0xFA]),
+        OutputEventType::SyncContinue => Ok(vec![0xFB]),
+        OutputEventType::SyncStop => Ok(vec![0xFC]),
 
-            _ => Err("Unsupported event type for MIDI endpoint".into())
+        OutputEventType::SongPosition { sixteenths } => {
+            let position = (*sixteenths).min(0x3FFF);
+            Ok(vec![0xF2, (position & 0x7F) as u8, (position >> 7) as u8])
         }
+
+        OutputEventType::MtcQuarterFrame { piece, timecode } => {
+            let value = match piece & 0x07 {
+                0 => timecode.frames & 0x0F,
+                1 => timecode.frames >> 4,
+                2 => timecode.seconds & 0x0F,
+                3 => timecode.seconds >> 4,
+                4 => timecode.minutes & 0x0F,
+                5 => timecode.minutes >> 4,
+                6 => timecode.hours & 0x0F,
+                _ => (timecode.hours >> 4) | (mtc_rate_code(timecode.rate) << 1),
+            };
+            Ok(vec![0xF1, ((piece & 0x07) << 4) | value])
+        }
+
+        OutputEventType::MtcFullFrame { timecode } => {
+            Ok(vec![
+                0xF0, 0x7F, 0x7F, 0x01, 0x01,
+                (mtc_rate_code(timecode.rate) << 5) | timecode.hours,
+                timecode.minutes,
+                timecode.seconds,
+                timecode.frames,
+                0xF7,
+            ])
+        }
+
+        _ => Err("Unsupported event type for MIDI endpoint".into())
     }
+}
 
-    fn endpoint_type(&self) -> EndpointType {
-        EndpointType::Midi
+/// MTC rate code for a frame rate; pulled-down rates are sent as their nominal rate
+fn mtc_rate_code(rate: FrameRate) -> u8 {
+    match rate {
+        FrameRate::Fps23_976 | FrameRate::Fps24 => 0,
+        FrameRate::Fps25 => 1,
+        FrameRate::Fps29_97Drop => 2,
+        FrameRate::Fps29_97NonDrop | FrameRate::Fps30 => 3,
     }
-}
\ No newline at end of file
+}
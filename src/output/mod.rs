@@ -1,8 +1,12 @@
 pub mod event;
 pub mod endpoint;
+pub mod jitter;
 pub mod midi;
+pub mod notes;
 pub mod system;
 
 pub use endpoint::OutputEndpoint;
 pub use event::{OutputEvent, OutputEventType};
+pub use jitter::JitterStats;
+pub use notes::ActiveNotes;
 pub use system::OutputSystem;
\ No newline at end of file
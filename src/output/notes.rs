@@ -0,0 +1,127 @@
+// src/output/notes.rs
+use std::collections::BTreeMap;
+use std::time::Instant;
+use crate::model::TrackId;
+use crate::output::event::{OutputEvent, OutputEventType};
+
+/// Controller that silences every note on a channel at once
+pub const ALL_NOTES_OFF: u8 = 123;
+/// Controller that silences every note on a channel, cutting off release tails
+pub const ALL_SOUND_OFF: u8 = 120;
+/// Controller that returns every controller on a channel to its default, pedals included
+pub const RESET_ALL_CONTROLLERS: u8 = 121;
+
+/// Notes sounding on one endpoint
+///
+/// Notes are recorded as they are handed to the endpoint, with the time they are due,
+/// so a note queued for later only counts once its time has come. Cancelling the queue
+/// forgets what was still to come: a cancelled note-on never sounds, and a cancelled
+/// note-off leaves its note sounding.
+#[derive(Debug, Default)]
+pub struct ActiveNotes {
+    /// Track that played each sounding note, by channel and note
+    sounding: BTreeMap<(u8, u8), Option<TrackId>>,
+    /// Changes still to come, by when they are due and then by arrival
+    upcoming: BTreeMap<(Instant, u64), NoteChange>,
+    /// Changes recorded so far, to keep equal times in order
+    arrivals: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NoteChange {
+    On { channel: u8, note: u8, source: Option<TrackId> },
+    Off { channel: u8, note: u8 },
+    AllOff { channel: u8 },
+}
+
+impl ActiveNotes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event handed to the endpoint to go out at `at`
+    pub fn record(&mut self, event: &OutputEvent, at: Instant) {
+        let change = match event.event_type {
+            // A note-on with no velocity is a note-off
+            OutputEventType::MidiNoteOn { channel, note, velocity: 0 }
+            | OutputEventType::MidiNoteOff { channel, note } => NoteChange::Off { channel, note },
+            OutputEventType::MidiNoteOn { channel, note, .. } => NoteChange::On { channel, note, source: event.source },
+            OutputEventType::MidiControlChange { channel, controller: ALL_NOTES_OFF | ALL_SOUND_OFF, .. } => {
+                NoteChange::AllOff { channel }
+            }
+            _ => return,
+        };
+
+        self.upcoming.insert((at, self.arrivals), change);
+        self.arrivals += 1;
+    }
+
+    /// Forget the changes due after `now`, which will not be delivered
+    pub fn cancel(&mut self, now: Instant) {
+        self.settle(now);
+        self.upcoming.clear();
+    }
+
+    /// Take the notes played by `source`, or all notes if `None`, as channel and note
+    /// with when each has to be released
+    ///
+    /// Notes sounding at `now` are released then; notes still to start are released
+    /// as they start.
+    pub fn release(&mut self, now: Instant, source: Option<TrackId>) -> Vec<(Instant, u8, u8)> {
+        self.settle(now);
+        let matches = |played_by: Option<TrackId>| source.is_none() || played_by == source;
+
+        let mut released: Vec<_> = self.sounding.iter()
+            .filter(|(_, played_by)| matches(**played_by))
+            .map(|(&(channel, note), _)| (now, channel, note))
+            .collect();
+        self.sounding.retain(|_, played_by| !matches(*played_by));
+
+        self.upcoming.retain(|&(at, _), change| match *change {
+            NoteChange::On { channel, note, source: played_by } if matches(played_by) => {
+                released.push((at, channel, note));
+                false
+            }
+            _ => true,
+        });
+
+        released
+    }
+
+    /// Whether a note is sounding at `at`, counting the changes due by then
+    pub fn is_sounding(&self, channel: u8, note: u8, at: Instant) -> bool {
+        let mut sounding = self.sounding.contains_key(&(channel, note));
+        for ((due, _), change) in &self.upcoming {
+            if *due > at {
+                break;
+            }
+            match *change {
+                NoteChange::On { channel: on_channel, note: on_note, .. } if (on_channel, on_note) == (channel, note) => sounding = true,
+                NoteChange::Off { channel: off_channel, note: off_note } if (off_channel, off_note) == (channel, note) => sounding = false,
+                NoteChange::AllOff { channel: off_channel } if off_channel == channel => sounding = false,
+                _ => {}
+            }
+        }
+        sounding
+    }
+
+    /// Apply the changes due by `now`
+    fn settle(&mut self, now: Instant) {
+        while let Some(entry) = self.upcoming.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            match entry.remove() {
+                NoteChange::On { channel, note, source } => {
+                    self.sounding.insert((channel, note), source);
+                }
+                NoteChange::Off { channel, note } => {
+                    self.sounding.remove(&(channel, note));
+                }
+                NoteChange::AllOff { channel } => {
+                    self.sounding.retain(|&(sounding_channel, _), _| sounding_channel != channel);
+                }
+            }
+        }
+    }
+}
@@ -1,21 +1,36 @@
 // src/output/system.rs
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
-use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::model::{EndpointId, EndpointConfig, EndpointType, EndpointParameters};
+use crate::model::{EndpointId, EndpointConfig, EndpointType, EndpointParameters, TrackId};
 use crate::output::endpoint::OutputEndpoint;
 use crate::output::midi::MidiOutputEndpoint;
-use crate::output::event::OutputEvent;
+use crate::output::event::{OutputEvent, OutputEventType};
+use crate::output::jitter::JitterStats;
+use crate::output::notes::{ActiveNotes, ALL_NOTES_OFF, RESET_ALL_CONTROLLERS};
 
+#[derive(Default)]
 pub struct OutputSystem {
     endpoints: HashMap<EndpointId, Box<dyn OutputEndpoint>>,
+    /// Timestamped events for endpoints that cannot schedule, by timestamp then arrival
+    pending: BTreeMap<(Instant, u64), (EndpointId, OutputEvent)>,
+    /// Events added to `pending` so far, to keep equal timestamps in order
+    pending_count: u64,
+    /// Timing of events delivered from `pending`
+    jitter: HashMap<EndpointId, JitterStats>,
+    /// Notes sounding on each endpoint, so they can always be released
+    notes: HashMap<EndpointId, ActiveNotes>,
 }
 
 impl OutputSystem {
     pub fn new() -> Self {
         Self {
             endpoints: HashMap::new(),
+            pending: BTreeMap::new(),
+            pending_count: 0,
+            jitter: HashMap::new(),
+            notes: HashMap::new(),
         }
     }
 
@@ -42,8 +57,8 @@ impl OutputSystem {
     pub fn add_endpoint(&mut self, config: &EndpointConfig) -> Result<(), Box<dyn Error>> {
         match config.endpoint_type {
             EndpointType::Midi => {
-                let params = match &config.parameters {
-                    EndpointParameters::Midi { channel } => (channel.unwrap_or(0)),
+                let _params = match &config.parameters {
+                    EndpointParameters::Midi { channel } => channel.unwrap_or(0),
                     _ => return Err("Invalid parameters for MIDI endpoint".into()),
                 };
 
@@ -73,6 +88,11 @@ impl OutputSystem {
         }
     }
 
+    /// Add an endpoint that is not built from a configuration, replacing any with its ID
+    pub fn insert_endpoint(&mut self, id: EndpointId, endpoint: Box<dyn OutputEndpoint>) {
+        self.endpoints.insert(id, endpoint);
+    }
+
     /// Connect to an endpoint
     pub fn connect_endpoint(&mut self, id: EndpointId) -> Result<(), Box<dyn Error>> {
         if let Some(endpoint) = self.endpoints.get_mut(&id) {
@@ -82,11 +102,29 @@ impl OutputSystem {
         }
     }
 
-    /// Disconnect from an endpoint
-    pub fn disconnect_endpoint(&mut self, id: EndpointId) {
+    /// Disconnect from an endpoint, first releasing the notes sounding on it
+    ///
+    /// Returns false if there is no such endpoint.
+    pub fn disconnect_endpoint(&mut self, id: EndpointId) -> bool {
+        if !self.endpoints.contains_key(&id) {
+            return false;
+        }
+
+        let now = Instant::now();
+        self.pending.retain(|_, (pending_id, _)| *pending_id != id);
+        if let Some(endpoint) = self.endpoints.get_mut(&id) {
+            endpoint.cancel_scheduled();
+        }
+        if let Some(notes) = self.notes.get_mut(&id) {
+            notes.cancel(now);
+        }
+        let _ = self.release(id, None);
+
         if let Some(endpoint) = self.endpoints.get_mut(&id) {
             endpoint.disconnect();
         }
+        self.notes.remove(&id);
+        true
     }
 
     /// Check if endpoint is connected
@@ -98,6 +136,13 @@ impl OutputSystem {
 
     /// Send an event to a specific endpoint
     pub fn send_event_to_endpoint(&mut self, id: EndpointId, event: &OutputEvent) -> Result<(), Box<dyn Error>> {
+        self.deliver(id, event)?;
+        self.notes.entry(id).or_default().record(event, Instant::now());
+        Ok(())
+    }
+
+    /// Send an event to an endpoint without recording its notes
+    fn deliver(&mut self, id: EndpointId, event: &OutputEvent) -> Result<(), Box<dyn Error>> {
         if let Some(endpoint) = self.endpoints.get_mut(&id) {
             endpoint.send_event(event)
         } else {
@@ -114,29 +159,164 @@ impl OutputSystem {
             results.push(self.send_event_to_endpoint(target, event));
         } else {
             // Send to all compatible endpoints
-            for (id, endpoint) in &mut self.endpoints {
-                match event.event_type {
-                    OutputEvent::MidiNoteOn { .. } |
-                    OutputEvent::MidiNoteOff { .. } |
-                    OutputEvent::MidiControlChange { .. } |
-                    OutputEvent::MidiProgramChange { .. } |
-                    OutputEvent::MidiPitchBend { .. } |
-                    OutputEvent::MidiAftertouch { .. } |
-                    OutputEvent::MidiPolyAftertouch { .. }
-                    if endpoint.endpoint_type() == EndpointType::Midi => {
-                        results.push(endpoint.send_event(event));
-                    },
-
-                    OutputEvent::AudioBuffer { .. }
-                    if endpoint.endpoint_type() == EndpointType::Audio => {
-                        results.push(endpoint.send_event(event));
-                    },
-
-                    _ => {}
+            let now = Instant::now();
+            for (id, endpoint) in self.endpoints.iter_mut() {
+                if accepts(endpoint.as_ref(), event) {
+                    let result = endpoint.send_event(event);
+                    if result.is_ok() {
+                        self.notes.entry(*id).or_default().record(event, now);
+                    }
+                    results.push(result);
                 }
             }
         }
 
         results
     }
+
+    /// Send an event to be delivered at `at`
+    ///
+    /// Endpoints that schedule get it straight away and deliver it on time; the rest
+    /// are sent it by `deliver_due` once it is due.
+    pub fn send_event_at(&mut self, event: &OutputEvent, at: Instant) -> Vec<Result<(), Box<dyn Error>>> {
+        let targets: Vec<EndpointId> = match event.target {
+            Some(target) => vec![target],
+            None => self.endpoints.iter()
+                .filter(|(_, endpoint)| accepts(endpoint.as_ref(), event))
+                .map(|(id, _)| *id)
+                .collect(),
+        };
+
+        let mut results = Vec::new();
+        for id in targets {
+            match self.endpoints.get_mut(&id) {
+                Some(endpoint) if endpoint.schedules() => {
+                    let result = endpoint.send_event_at(event, at);
+                    if result.is_ok() {
+                        self.notes.entry(id).or_default().record(event, at);
+                    }
+                    results.push(result);
+                }
+                Some(_) => {
+                    self.pending.insert((at, self.pending_count), (id, event.clone()));
+                    self.pending_count += 1;
+                    self.notes.entry(id).or_default().record(event, at);
+                }
+                None => results.push(Err(format!("Endpoint {} not found", id).into())),
+            }
+        }
+
+        results
+    }
+
+    /// Send the events held for endpoints that cannot schedule which are now due
+    pub fn deliver_due(&mut self) -> Vec<Result<(), Box<dyn Error>>> {
+        let mut results = Vec::new();
+
+        while let Some(entry) = self.pending.first_entry() {
+            let (at, _) = *entry.key();
+            let delivered = Instant::now();
+            if at > delivered {
+                break;
+            }
+            let (id, event) = entry.remove();
+            // Its notes were recorded when it was queued
+            results.push(self.deliver(id, &event));
+            self.jitter.entry(id).or_default().record_delivery(at, delivered);
+        }
+
+        results
+    }
+
+    /// Drop every event still waiting for its timestamp, here and in the endpoints
+    pub fn cancel_scheduled(&mut self) {
+        let now = Instant::now();
+        self.pending.clear();
+        for endpoint in self.endpoints.values_mut() {
+            endpoint.cancel_scheduled();
+        }
+        for notes in self.notes.values_mut() {
+            notes.cancel(now);
+        }
+    }
+
+    /// Notes sounding on an endpoint, if anything has been played on it
+    pub fn active_notes(&self, id: EndpointId) -> Option<&ActiveNotes> {
+        self.notes.get(&id)
+    }
+
+    /// Send note-offs for the notes sounding on an endpoint
+    pub fn release_notes(&mut self, id: EndpointId) -> Vec<Result<(), Box<dyn Error>>> {
+        self.release(id, None)
+    }
+
+    /// Send note-offs for the notes a track is sounding, e.g. when it is muted
+    pub fn release_track_notes(&mut self, track_id: TrackId) -> Vec<Result<(), Box<dyn Error>>> {
+        let ids: Vec<EndpointId> = self.notes.keys().copied().collect();
+        ids.into_iter().flat_map(|id| self.release(id, Some(track_id))).collect()
+    }
+
+    /// Send note-offs for every note sounding on every endpoint
+    pub fn release_all_notes(&mut self) -> Vec<Result<(), Box<dyn Error>>> {
+        let ids: Vec<EndpointId> = self.notes.keys().copied().collect();
+        ids.into_iter().flat_map(|id| self.release(id, None)).collect()
+    }
+
+    /// Silence everything: release every note we know of, then send All Notes Off and
+    /// Reset All Controllers on every channel of every MIDI endpoint for those we don't
+    pub fn panic(&mut self) -> Vec<Result<(), Box<dyn Error>>> {
+        self.cancel_scheduled();
+        let mut results = self.release_all_notes();
+
+        for (id, endpoint) in self.endpoints.iter_mut() {
+            if endpoint.endpoint_type() != EndpointType::Midi {
+                continue;
+            }
+            for channel in 0..16 {
+                results.push(endpoint.send_event(&OutputEvent::midi_cc(channel, ALL_NOTES_OFF, 0, Some(*id))));
+                results.push(endpoint.send_event(&OutputEvent::midi_cc(channel, RESET_ALL_CONTROLLERS, 0, Some(*id))));
+            }
+        }
+
+        self.notes.clear();
+        results
+    }
+
+    /// Release the notes on an endpoint played by `source`, or all of them
+    ///
+    /// Notes that have yet to start are released as they start.
+    fn release(&mut self, id: EndpointId, source: Option<TrackId>) -> Vec<Result<(), Box<dyn Error>>> {
+        let now = Instant::now();
+        let Some(notes) = self.notes.get_mut(&id) else {
+            return Vec::new();
+        };
+
+        let mut results = Vec::new();
+        for (at, channel, note) in notes.release(now, source) {
+            let note_off = OutputEvent::midi_note_off(channel, note, Some(id));
+            if at <= now {
+                results.push(self.deliver(id, &note_off));
+            } else {
+                results.extend(self.send_event_at(&note_off, at));
+            }
+        }
+        results
+    }
+
+    /// How far from their timestamps an endpoint's events were delivered
+    pub fn jitter(&self, id: EndpointId) -> Option<JitterStats> {
+        let endpoint = self.endpoints.get(&id)?;
+        endpoint.jitter().or_else(|| self.jitter.get(&id).copied())
+    }
+}
+
+/// Whether an untargeted event goes to an endpoint
+fn accepts(endpoint: &dyn OutputEndpoint, event: &OutputEvent) -> bool {
+    match event.event_type {
+        // Sync only goes where it was asked for, so it is always targeted
+        _ if event.is_sync() => false,
+        _ if event.is_midi() => endpoint.endpoint_type() == EndpointType::Midi,
+        OutputEventType::AudioBuffer { .. } => endpoint.endpoint_type() == EndpointType::Audio,
+        _ => false,
+    }
 }
\ No newline at end of file
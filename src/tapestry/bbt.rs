@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use crate::tapestry::position::TimePosition;
+use crate::tapestry::tempo_map::TempoMap;
+
+/// Default resolution of BBT ticks, in pulses per quarter note
+pub const DEFAULT_PPQ: u32 = 960;
+
+/// Errors produced when parsing or converting a BBT position
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BbtError {
+    #[error("expected bar.beat.tick, got {0:?}")]
+    InvalidFormat(String),
+    #[error("invalid number {0:?} in BBT position")]
+    InvalidNumber(String),
+    #[error("bars and beats are numbered from 1")]
+    ZeroIndex,
+    #[error("beat {beat} does not exist in a bar of {beats_in_bar} beats")]
+    BeatOutOfRange { beat: u32, beats_in_bar: u32 },
+    #[error("tick {tick} does not exist in a beat of {ticks_per_beat} ticks")]
+    TickOutOfRange { tick: u32, ticks_per_beat: u32 },
+}
+
+/// Musical position as bars, beats and ticks, e.g. `12.3.240`
+///
+/// Bars and beats count from 1, ticks count from 0 and subdivide a beat at a
+/// caller-chosen PPQ. Beats are in the meter's beat unit, so a 6/8 bar has six
+/// beats of PPQ / 2 ticks each. Where a beat is not a whole number of ticks (PPQ
+/// 100 in x/64), each beat still starts exactly on its boundary and holds the
+/// ticks that start within it. The PPQ is not stored, so the same value must be
+/// used when converting to and from a `TimePosition`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BbtPosition {
+    /// Bar number, starting at 1
+    pub bar: u32,
+    /// Beat within the bar, starting at 1
+    pub beat: u32,
+    /// Tick within the beat, from 0 up to the ticks that start within one beat unit minus 1
+    pub tick: u32,
+}
+
+impl BbtPosition {
+    pub fn new(bar: u32, beat: u32, tick: u32) -> Self {
+        Self { bar, beat, tick }
+    }
+
+    /// The first beat of the first bar
+    pub fn start() -> Self {
+        Self::new(1, 1, 0)
+    }
+
+    /// Find the BBT position of a time position, following tempo and meter changes
+    pub fn from_position(position: &TimePosition, tempo_map: &TempoMap, ppq: u32) -> Self {
+        let (bars, quarters_into_bar) = tempo_map.position_to_bars_and_beats(position);
+        let beat_unit = Self::beat_unit(tempo_map, bars);
+        let units_per_beat = Self::units_per_beat(ppq);
+        let beats_in_bar = Self::beats_in_bar(tempo_map, bars);
+
+        let units_into_bar = (quarters_into_bar * ppq as f64 * beat_unit as f64).round() as u64;
+        let mut beat = (units_into_bar / units_per_beat) as u32;
+        let mut tick = ((units_into_bar % units_per_beat + beat_unit / 2) / beat_unit) as u32;
+        if tick as u64 * beat_unit >= units_per_beat {
+            // Rounded up onto the next beat
+            beat += 1;
+            tick = 0;
+        }
+
+        if beat >= beats_in_bar {
+            // Rounded up onto the next bar line
+            Self::new(bars + 2, 1, 0)
+        } else {
+            Self::new(bars + 1, beat + 1, tick)
+        }
+    }
+
+    /// Find the time position of this BBT position, following tempo and meter changes
+    pub fn to_position(&self, tempo_map: &TempoMap, ppq: u32) -> Result<TimePosition, BbtError> {
+        if self.bar == 0 || self.beat == 0 {
+            return Err(BbtError::ZeroIndex);
+        }
+
+        let bars = self.bar - 1;
+        let beat_unit = Self::beat_unit(tempo_map, bars);
+        let units_per_beat = Self::units_per_beat(ppq);
+        if self.tick as u64 * beat_unit >= units_per_beat {
+            let ticks_per_beat = u32::try_from(units_per_beat.div_ceil(beat_unit)).unwrap_or(u32::MAX);
+            return Err(BbtError::TickOutOfRange { tick: self.tick, ticks_per_beat });
+        }
+
+        let beats_in_bar = Self::beats_in_bar(tempo_map, bars);
+        if self.beat > beats_in_bar {
+            return Err(BbtError::BeatOutOfRange { beat: self.beat, beats_in_bar });
+        }
+
+        let units_into_bar = (self.beat - 1) as u64 * units_per_beat + self.tick as u64 * beat_unit;
+        let quarters_into_bar = units_into_bar as f64 / (ppq as f64 * beat_unit as f64);
+        Ok(tempo_map.bars_and_beats_to_position(bars, quarters_into_bar))
+    }
+
+    /// Beat unit of the given zero-based bar, e.g. 8 in 7/8
+    fn beat_unit(tempo_map: &TempoMap, bars: u32) -> u64 {
+        (tempo_map.time_signature_at_bar(bars).denominator as u64).max(1)
+    }
+
+    /// Length of one beat unit in units of 1 / beat unit of a tick
+    ///
+    /// Counting in these units keeps beats exact when the PPQ does not divide the
+    /// beat unit, e.g. a 64th at PPQ 100 is 6.25 ticks or 25 units of a 64th of a tick.
+    fn units_per_beat(ppq: u32) -> u64 {
+        (ppq as u64 * 4).max(1)
+    }
+
+    /// Number of beats (including a partial last beat) in the given zero-based bar
+    fn beats_in_bar(tempo_map: &TempoMap, bars: u32) -> u32 {
+        // Measured bar line to bar line, so bars cut short by a meter change are honoured
+        let quarters = tempo_map.bars_and_beats_to_beats(bars + 1, 0.0) - tempo_map.bars_and_beats_to_beats(bars, 0.0);
+        let beats = quarters * tempo_map.time_signature_at_bar(bars).denominator as f64 / 4.0;
+        (beats - 1e-9).ceil() as u32
+    }
+}
+
+impl fmt::Display for BbtPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.bar, self.beat, self.tick)
+    }
+}
+
+impl FromStr for BbtPosition {
+    type Err = BbtError;
+
+    /// Parse `bar`, `bar.beat` or `bar.beat.tick`, with omitted fields at their start
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.trim().split('.').collect();
+        if fields.len() > 3 {
+            return Err(BbtError::InvalidFormat(s.to_string()));
+        }
+
+        let parse = |field: &str| {
+            field.trim().parse::<u32>().map_err(|_| BbtError::InvalidNumber(field.to_string()))
+        };
+
+        let bar = parse(fields[0])?;
+        let beat = fields.get(1).map(|f| parse(f)).transpose()?.unwrap_or(1);
+        let tick = fields.get(2).map(|f| parse(f)).transpose()?.unwrap_or(0);
+
+        if bar == 0 || beat == 0 {
+            return Err(BbtError::ZeroIndex);
+        }
+
+        Ok(Self::new(bar, beat, tick))
+    }
+}
@@ -1,5 +1,6 @@
 // src/tapestry/duration.rs
 use std::ops::{Add, Sub, Mul, Div, AddAssign, SubAssign};
+use crate::tapestry::{TempoMap, TimePosition};
 
 /// Represents a duration of time, independent of sample rate
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -25,12 +26,14 @@ impl Duration {
         Self { ticks }
     }
 
-    /// Convert from beats to Duration using a tempo
-    pub fn from_beats(beats: f64) -> Self {
-        // For now, we'll use a simple conversion where 1 beat = 22050 ticks (0.5 sec at 44.1kHz)
-        // In a real implementation, this would use the tempo map
-        let ticks = (beats * 22050.0).round() as u64;
-        Self { ticks }
+    /// Convert from beats to Duration, following the tempo map from `start`
+    pub fn from_beats(beats: f64, start: &TimePosition, tempo_map: &TempoMap) -> Self {
+        tempo_map.beats_to_duration(start, beats)
+    }
+
+    /// Convert this duration to beats, following the tempo map from `start`
+    pub fn to_beats(&self, start: &TimePosition, tempo_map: &TempoMap) -> f64 {
+        tempo_map.duration_to_beats(start, self)
     }
 
     /// Convert this duration to seconds using the reference sample rate
@@ -42,16 +45,41 @@ impl Duration {
     pub fn ticks(&self) -> u64 {
         self.ticks
     }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.ticks.checked_add(other.ticks).map(Self::new)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.ticks.checked_sub(other.ticks).map(Self::new)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self::new(self.ticks.saturating_add(other.ticks))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self::new(self.ticks.saturating_sub(other.ticks))
+    }
+
+    pub fn wrapping_add(self, other: Self) -> Self {
+        Self::new(self.ticks.wrapping_add(other.ticks))
+    }
+
+    pub fn wrapping_sub(self, other: Self) -> Self {
+        Self::new(self.ticks.wrapping_sub(other.ticks))
+    }
 }
 
+// Arithmetic operators saturate at zero and at the largest duration;
+// use the checked or wrapping methods to choose otherwise
+
 // Implement addition of Durations
 impl Add for Duration {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        Self {
-            ticks: self.ticks + other.ticks,
-        }
+        self.saturating_add(other)
     }
 }
 
@@ -60,23 +88,21 @@ impl Sub for Duration {
     type Output = Self;
 
     fn sub(self, other: Self) -> Self {
-        Self {
-            ticks: self.ticks.saturating_sub(other.ticks),
-        }
+        self.saturating_sub(other)
     }
 }
 
 // Allow in-place addition
 impl AddAssign for Duration {
     fn add_assign(&mut self, other: Self) {
-        self.ticks += other.ticks;
+        *self = self.saturating_add(other);
     }
 }
 
 // Allow in-place subtraction
 impl SubAssign for Duration {
     fn sub_assign(&mut self, other: Self) {
-        self.ticks = self.ticks.saturating_sub(other.ticks);
+        *self = self.saturating_sub(other);
     }
 }
 
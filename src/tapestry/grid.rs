@@ -0,0 +1,162 @@
+use crate::tapestry::bbt::BbtPosition;
+use crate::tapestry::note_value::NoteValue;
+use crate::tapestry::position::TimePosition;
+use crate::tapestry::tempo_map::TempoMap;
+
+/// Tolerance used when deciding whether a grid line falls inside a bar
+const BEAT_EPSILON: f64 = 1e-9;
+
+/// Kind of musical boundary a grid line marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GridLevel {
+    /// Finer subdivision between beats
+    Subdivision,
+    /// Start of a beat in the meter's beat unit
+    Beat,
+    /// Start of a bar
+    Bar,
+}
+
+/// A single line of the musical grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridLine {
+    pub position: TimePosition,
+    /// Strongest boundary at this position
+    pub level: GridLevel,
+    pub bbt: BbtPosition,
+}
+
+/// Iterator over the bar, beat and subdivision lines in a range, following tempo and meter changes
+///
+/// Lines are counted from each bar line, so subdivisions restart at every bar even
+/// when a meter change cuts a bar short.
+pub struct Grid<'a> {
+    tempo_map: &'a TempoMap,
+    start: TimePosition,
+    end: TimePosition,
+    ppq: u32,
+    subdivision: Option<NoteValue>,
+    bar: BarState,
+}
+
+/// The bar currently being walked
+struct BarState {
+    /// Zero-based bar index
+    index: u32,
+    /// Quarter notes from zero to the bar line
+    start_beats: f64,
+    /// Length of the bar in quarter notes
+    length: f64,
+    /// Denominator of the bar's time signature
+    denominator: u64,
+    /// Distance between candidate lines in quarter notes
+    step: NoteValue,
+    /// Index of the next candidate line within the bar
+    next: u64,
+}
+
+impl<'a> Grid<'a> {
+    /// Lines at bars and beats from `start` (inclusive) to `end` (exclusive)
+    pub fn new(tempo_map: &'a TempoMap, start: TimePosition, end: TimePosition, ppq: u32) -> Self {
+        let (index, _) = tempo_map.position_to_bars_and_beats(&start);
+        let bar = Self::bar_state(tempo_map, index, None);
+        Self { tempo_map, start, end, ppq, subdivision: None, bar }
+    }
+
+    /// Add subdivision lines every `subdivision`, e.g. `NoteValue::SIXTEENTH`
+    pub fn with_subdivision(mut self, subdivision: NoteValue) -> Self {
+        let subdivision = (subdivision > NoteValue::zero()).then_some(subdivision);
+        self.subdivision = subdivision;
+        self.bar = Self::bar_state(self.tempo_map, self.bar.index, subdivision);
+        self
+    }
+
+    fn bar_state(tempo_map: &TempoMap, index: u32, subdivision: Option<NoteValue>) -> BarState {
+        let start_beats = tempo_map.bars_and_beats_to_beats(index, 0.0);
+        let length = tempo_map.bars_and_beats_to_beats(index + 1, 0.0) - start_beats;
+        let denominator = tempo_map.time_signature_at_bar(index).denominator.max(1) as u64;
+
+        let beat_unit = NoteValue::new(4, denominator);
+        let step = match subdivision {
+            Some(subdivision) => common_step(beat_unit, subdivision),
+            None => beat_unit,
+        };
+
+        BarState { index, start_beats, length, denominator, step, next: 0 }
+    }
+
+    /// Whether `offset` quarter notes into the bar is a whole number of `unit`s
+    fn is_multiple(offset: NoteValue, unit: NoteValue) -> bool {
+        let scaled = offset.numerator() as u128 * unit.denominator() as u128;
+        scaled.is_multiple_of(offset.denominator() as u128 * unit.numerator() as u128)
+    }
+}
+
+impl Iterator for Grid<'_> {
+    type Item = GridLine;
+
+    fn next(&mut self) -> Option<GridLine> {
+        loop {
+            let offset = self.bar.step * self.bar.next;
+            if offset.to_beats() >= self.bar.length - BEAT_EPSILON {
+                self.bar = Self::bar_state(self.tempo_map, self.bar.index + 1, self.subdivision);
+                continue;
+            }
+            self.bar.next += 1;
+
+            let beat_unit = NoteValue::new(4, self.bar.denominator);
+            let on_beat = Self::is_multiple(offset, beat_unit);
+            let on_subdivision = self.subdivision.is_some_and(|unit| Self::is_multiple(offset, unit));
+            if !on_beat && !on_subdivision {
+                continue;
+            }
+
+            let position = self.tempo_map.beats_to_position(self.bar.start_beats + offset.to_beats());
+            if position >= self.end {
+                return None;
+            }
+            if position < self.start {
+                continue;
+            }
+
+            let level = if offset == NoteValue::zero() {
+                GridLevel::Bar
+            } else if on_beat {
+                GridLevel::Beat
+            } else {
+                GridLevel::Subdivision
+            };
+
+            let ticks_per_beat = (self.ppq as u64 * 4 / self.bar.denominator).max(1);
+            let ticks_into_bar = offset.to_ticks(self.ppq);
+            let bbt = BbtPosition::new(
+                self.bar.index + 1,
+                (ticks_into_bar / ticks_per_beat) as u32 + 1,
+                (ticks_into_bar % ticks_per_beat) as u32,
+            );
+
+            return Some(GridLine { position, level, bbt });
+        }
+    }
+}
+
+/// Largest note value that both `a` and `b` are whole multiples of
+fn common_step(a: NoteValue, b: NoteValue) -> NoteValue {
+    // Over a common denominator, the step is the gcd of the numerators
+    let denominator = a.denominator() as u128 * b.denominator() as u128;
+    let numerator = gcd(
+        a.numerator() as u128 * b.denominator() as u128,
+        b.numerator() as u128 * a.denominator() as u128,
+    );
+    let divisor = gcd(numerator, denominator);
+    NoteValue::new((numerator / divisor) as u64, (denominator / divisor) as u64)
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
@@ -4,11 +4,21 @@ pub mod tempo_map;
 pub mod context;
 pub mod note_value;
 pub mod duration;
+pub mod bbt;
+pub mod timecode;
+pub mod offset;
+pub mod grid;
+pub mod quantize;
 
 // Re-export commonly used types
 pub use position::TimePosition;
-pub use tempo::{Tempo, TimeSignature};
-pub use tempo_map::TempoMap;
+pub use offset::TimeOffset;
+pub use tempo::{Tempo, TempoChange, TempoCurve, TimeSignature, Accent, MeterError, MAX_BEAT_GROUPS};
+pub use tempo_map::{TempoMap, TempoMapError};
 pub use context::TimeContext;
-pub use note_value::NoteValue;
-pub use duration::Duration;
\ No newline at end of file
+pub use note_value::{NoteValue, MAX_DOTS};
+pub use duration::Duration;
+pub use bbt::{BbtPosition, BbtError, DEFAULT_PPQ};
+pub use timecode::{Timecode, TimecodeError, FrameRate};
+pub use grid::{Grid, GridLine, GridLevel};
+pub use quantize::{Quantizer, QuantizeMode, QuantizeSpan};
\ No newline at end of file
@@ -1,36 +1,235 @@
-/// Represents standard musical note durations
-#[derive(Debug, Clone, Copy)]
-pub struct NoteValue(pub f64);
+use std::cmp::Ordering;
+use std::ops::{Add, AddAssign, Mul, Div};
+
+/// Largest denominator used when approximating a floating point beat count
+const MAX_APPROX_DENOMINATOR: u64 = 3840;
+
+/// Most augmentation dots whose scale still fits in a `u64`
+pub const MAX_DOTS: u32 = 62;
+
+/// Represents a musical note duration as an exact fraction of a quarter note
+///
+/// Values are kept in lowest terms, so equal durations compare equal however
+/// they were built (e.g. two tied triplet eighths equal a triplet quarter).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoteValue {
+    numerator: u64,
+    denominator: u64,
+}
 
 impl NoteValue {
-    // Standard note values as a fraction of a whole note
-    pub const WHOLE: NoteValue = NoteValue(4.0);
-    pub const HALF: NoteValue = NoteValue(2.0);
-    pub const QUARTER: NoteValue = NoteValue(1.0);
-    pub const EIGHTH: NoteValue = NoteValue(0.5);
-    pub const SIXTEENTH: NoteValue = NoteValue(0.25);
-    pub const THIRTY_SECOND: NoteValue = NoteValue(0.125);
-    pub const SIXTY_FOURTH: NoteValue = NoteValue(0.0625);
+    // Standard note values in beats (quarter notes)
+    pub const WHOLE: NoteValue = NoteValue::new(4, 1);
+    pub const HALF: NoteValue = NoteValue::new(2, 1);
+    pub const QUARTER: NoteValue = NoteValue::new(1, 1);
+    pub const EIGHTH: NoteValue = NoteValue::new(1, 2);
+    pub const SIXTEENTH: NoteValue = NoteValue::new(1, 4);
+    pub const THIRTY_SECOND: NoteValue = NoteValue::new(1, 8);
+    pub const SIXTY_FOURTH: NoteValue = NoteValue::new(1, 16);
 
     // Dotted versions (1.5x the duration)
-    pub const DOTTED_HALF: NoteValue = NoteValue(3.0);
-    pub const DOTTED_QUARTER: NoteValue = NoteValue(1.5);
-    pub const DOTTED_EIGHTH: NoteValue = NoteValue(0.75);
-    pub const DOTTED_SIXTEENTH: NoteValue = NoteValue(0.375);
+    pub const DOTTED_HALF: NoteValue = NoteValue::new(3, 1);
+    pub const DOTTED_QUARTER: NoteValue = NoteValue::new(3, 2);
+    pub const DOTTED_EIGHTH: NoteValue = NoteValue::new(3, 4);
+    pub const DOTTED_SIXTEENTH: NoteValue = NoteValue::new(3, 8);
 
     // Triplet versions (2/3 the duration)
-    pub const TRIPLET_HALF: NoteValue = NoteValue(4.0/3.0);
-    pub const TRIPLET_QUARTER: NoteValue = NoteValue(2.0/3.0);
-    pub const TRIPLET_EIGHTH: NoteValue = NoteValue(1.0/3.0);
-    pub const TRIPLET_SIXTEENTH: NoteValue = NoteValue(0.5/3.0);
+    pub const TRIPLET_HALF: NoteValue = NoteValue::new(4, 3);
+    pub const TRIPLET_QUARTER: NoteValue = NoteValue::new(2, 3);
+    pub const TRIPLET_EIGHTH: NoteValue = NoteValue::new(1, 3);
+    pub const TRIPLET_SIXTEENTH: NoteValue = NoteValue::new(1, 6);
+
+    /// Create a note value of `numerator / denominator` beats
+    ///
+    /// Panics if `denominator` is zero.
+    pub const fn new(numerator: u64, denominator: u64) -> Self {
+        assert!(denominator != 0, "note value denominator must not be zero");
+        let divisor = gcd(numerator, denominator);
+        Self {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    /// A note value of no length
+    pub const fn zero() -> Self {
+        Self::new(0, 1)
+    }
+
+    /// Numerator of the length in beats, in lowest terms
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    /// Denominator of the length in beats, in lowest terms
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    /// Lengthen by half (a single dot)
+    pub fn dotted(self) -> Self {
+        self.with_dots(1)
+    }
+
+    /// Lengthen by three quarters (two dots)
+    pub fn double_dotted(self) -> Self {
+        self.with_dots(2)
+    }
+
+    /// Apply `dots` augmentation dots, each adding half of the previous addition
+    ///
+    /// Panics if `dots` is more than [`MAX_DOTS`].
+    pub fn with_dots(self, dots: u32) -> Self {
+        assert!(dots <= MAX_DOTS, "a note value takes at most {MAX_DOTS} dots");
+        // Total length is (2^(n+1) - 1) / 2^n of the plain value
+        let scale = 1u64 << dots;
+        self.scaled(2 * scale - 1, scale)
+    }
+
+    /// Play `notes` of this value in the time of `in_time_of`, e.g. `tuplet(5, 4)` for quintuplets
+    ///
+    /// Panics if `notes` is zero.
+    pub fn tuplet(self, notes: u32, in_time_of: u32) -> Self {
+        self.scaled(in_time_of as u64, notes as u64)
+    }
+
+    /// Tie another note value onto this one
+    pub fn tied(self, other: Self) -> Self {
+        self + other
+    }
 
     /// Convert to beats (quarter notes)
     pub fn to_beats(&self) -> f64 {
-        self.0
+        self.numerator as f64 / self.denominator as f64
     }
 
     /// Create a note value from a custom beat duration
+    ///
+    /// The result is the closest fraction whose denominator divides evenly into
+    /// common tuplet and PPQ grids; prefer the exact constructors where possible.
     pub fn from_beats(beats: f64) -> Self {
-        Self(beats)
+        let beats = beats.max(0.0);
+        let numerator = (beats * MAX_APPROX_DENOMINATOR as f64).round() as u64;
+        Self::new(numerator, MAX_APPROX_DENOMINATOR)
+    }
+
+    /// Length in ticks at the given resolution, if it is a whole number of ticks
+    pub fn ticks_exact(&self, ppq: u32) -> Option<u64> {
+        let scaled = self.numerator as u128 * ppq as u128;
+        if scaled.is_multiple_of(self.denominator as u128) {
+            Some((scaled / self.denominator as u128) as u64)
+        } else {
+            None
+        }
+    }
+
+    /// Length in ticks at the given resolution, rounded to the nearest tick
+    pub fn to_ticks(&self, ppq: u32) -> u64 {
+        self.ticks_at(1, ppq)
+    }
+
+    /// Tick offset of the start of note `index` in a run of notes of this value
+    ///
+    /// Each boundary is rounded on its own rather than summing rounded lengths,
+    /// so a run of tuplets never drifts from the grid: five 5:4 sixteenths always
+    /// end exactly one beat after they start.
+    pub fn ticks_at(&self, index: u64, ppq: u32) -> u64 {
+        let scaled = index as u128 * self.numerator as u128 * ppq as u128;
+        let denominator = self.denominator as u128;
+        ((2 * scaled + denominator) / (2 * denominator)) as u64
+    }
+
+    /// Multiply by the fraction `numerator / denominator`
+    fn scaled(self, numerator: u64, denominator: u64) -> Self {
+        assert!(denominator != 0, "note value denominator must not be zero");
+        Self::from_wide(
+            self.numerator as u128 * numerator as u128,
+            self.denominator as u128 * denominator as u128,
+        )
+    }
+
+    /// Reduce a wide fraction back into a note value
+    fn from_wide(numerator: u128, denominator: u128) -> Self {
+        let divisor = gcd_wide(numerator, denominator);
+        let numerator = numerator / divisor;
+        let denominator = denominator / divisor;
+        Self {
+            numerator: u64::try_from(numerator).expect("note value numerator overflow"),
+            denominator: u64::try_from(denominator).expect("note value denominator overflow"),
+        }
+    }
+}
+
+impl Default for NoteValue {
+    fn default() -> Self {
+        Self::QUARTER
+    }
+}
+
+impl Ord for NoteValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = self.numerator as u128 * other.denominator as u128;
+        let right = other.numerator as u128 * self.denominator as u128;
+        left.cmp(&right)
+    }
+}
+
+impl PartialOrd for NoteValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Exact addition, e.g. for ties
+impl Add for NoteValue {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::from_wide(
+            self.numerator as u128 * other.denominator as u128 + other.numerator as u128 * self.denominator as u128,
+            self.denominator as u128 * other.denominator as u128,
+        )
+    }
+}
+
+impl AddAssign for NoteValue {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+// Repeat a note value a whole number of times
+impl Mul<u64> for NoteValue {
+    type Output = Self;
+
+    fn mul(self, count: u64) -> Self {
+        self.scaled(count, 1)
+    }
+}
+
+// Split a note value into equal parts
+impl Div<u64> for NoteValue {
+    type Output = Self;
+
+    fn div(self, parts: u64) -> Self {
+        self.scaled(1, parts)
+    }
+}
+
+const fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    if a == 0 { 1 } else { a }
+}
+
+fn gcd_wide(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
     }
-}
\ No newline at end of file
+    if a == 0 { 1 } else { a }
+}
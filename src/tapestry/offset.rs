@@ -0,0 +1,125 @@
+// src/tapestry/offset.rs
+use std::ops::{Add, Sub, Neg, AddAssign, SubAssign};
+use crate::tapestry::{Duration, TimePosition};
+
+/// Represents a signed distance in time, independent of sample rate
+///
+/// Unlike `Duration`, an offset can point backwards, e.g. to nudge a container
+/// earlier or to compensate a negative latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct TimeOffset {
+    /// Internal representation as ticks (at reference sample rate)
+    pub(crate) ticks: i64,
+}
+
+impl TimeOffset {
+    /// Create a new TimeOffset with the specified tick count
+    pub fn new(ticks: i64) -> Self {
+        Self { ticks }
+    }
+
+    /// Create a zero offset
+    pub fn zero() -> Self {
+        Self { ticks: 0 }
+    }
+
+    /// The signed distance from `from` to `to`, saturating at the limits of `i64`
+    pub fn between(from: TimePosition, to: TimePosition) -> Self {
+        let ticks = to.position_ticks as i128 - from.position_ticks as i128;
+        Self { ticks: ticks.clamp(i64::MIN as i128, i64::MAX as i128) as i64 }
+    }
+
+    /// Convert from seconds to TimeOffset using the reference sample rate
+    pub fn from_seconds(seconds: f64, reference_sample_rate: u32) -> Self {
+        let ticks = (seconds * reference_sample_rate as f64).round() as i64;
+        Self { ticks }
+    }
+
+    /// Convert this offset to seconds using the reference sample rate
+    pub fn to_seconds(&self, reference_sample_rate: u32) -> f64 {
+        self.ticks as f64 / reference_sample_rate as f64
+    }
+
+    /// Get the raw signed tick count
+    pub fn ticks(&self) -> i64 {
+        self.ticks
+    }
+
+    /// Whether this offset points backwards in time
+    pub fn is_negative(&self) -> bool {
+        self.ticks < 0
+    }
+
+    /// The unsigned length of this offset
+    pub fn abs(&self) -> Duration {
+        Duration::new(self.ticks.unsigned_abs())
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.ticks.checked_add(other.ticks).map(Self::new)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.ticks.checked_sub(other.ticks).map(Self::new)
+    }
+
+    pub fn saturating_add(self, other: Self) -> Self {
+        Self::new(self.ticks.saturating_add(other.ticks))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self::new(self.ticks.saturating_sub(other.ticks))
+    }
+
+    pub fn wrapping_add(self, other: Self) -> Self {
+        Self::new(self.ticks.wrapping_add(other.ticks))
+    }
+
+    pub fn wrapping_sub(self, other: Self) -> Self {
+        Self::new(self.ticks.wrapping_sub(other.ticks))
+    }
+}
+
+impl From<Duration> for TimeOffset {
+    /// Saturates at `i64::MAX` for durations that do not fit
+    fn from(duration: Duration) -> Self {
+        Self { ticks: i64::try_from(duration.ticks()).unwrap_or(i64::MAX) }
+    }
+}
+
+// Arithmetic operators saturate; use the checked or wrapping methods to choose otherwise
+impl Add for TimeOffset {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.saturating_add(other)
+    }
+}
+
+impl Sub for TimeOffset {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.saturating_sub(other)
+    }
+}
+
+impl Neg for TimeOffset {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(self.ticks.saturating_neg())
+    }
+}
+
+impl AddAssign for TimeOffset {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for TimeOffset {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
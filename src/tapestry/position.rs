@@ -1,6 +1,6 @@
 use std::cmp::Ordering;
 use std::ops::{Add, Sub, AddAssign, SubAssign};
-use crate::tapestry::Duration;
+use crate::tapestry::{Duration, TimeOffset};
 
 /// Represents a precise position in time, independent of sample rate
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -30,6 +30,60 @@ impl TimePosition {
     pub fn to_seconds(&self, reference_sample_rate: u32) -> f64 {
         self.position_ticks as f64 / reference_sample_rate as f64
     }
+
+    /// The latest representable position
+    pub fn max() -> Self {
+        Self { position_ticks: u64::MAX }
+    }
+
+    /// Signed distance from `earlier` to this position
+    pub fn offset_from(&self, earlier: TimePosition) -> TimeOffset {
+        TimeOffset::between(earlier, *self)
+    }
+
+    /// Time elapsed since `earlier`, or zero if `earlier` is after this position
+    pub fn duration_since(&self, earlier: TimePosition) -> Duration {
+        Duration::new(self.position_ticks.saturating_sub(earlier.position_ticks))
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        self.position_ticks.checked_add(duration.ticks()).map(Self::new)
+    }
+
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        self.position_ticks.checked_sub(duration.ticks()).map(Self::new)
+    }
+
+    pub fn saturating_add(self, duration: Duration) -> Self {
+        Self::new(self.position_ticks.saturating_add(duration.ticks()))
+    }
+
+    pub fn saturating_sub(self, duration: Duration) -> Self {
+        Self::new(self.position_ticks.saturating_sub(duration.ticks()))
+    }
+
+    pub fn wrapping_add(self, duration: Duration) -> Self {
+        Self::new(self.position_ticks.wrapping_add(duration.ticks()))
+    }
+
+    pub fn wrapping_sub(self, duration: Duration) -> Self {
+        Self::new(self.position_ticks.wrapping_sub(duration.ticks()))
+    }
+
+    /// Move by a signed offset, or `None` if the result would fall outside the timeline
+    pub fn checked_offset(self, offset: TimeOffset) -> Option<Self> {
+        self.position_ticks.checked_add_signed(offset.ticks()).map(Self::new)
+    }
+
+    /// Move by a signed offset, clamping to the start or end of the timeline
+    pub fn saturating_offset(self, offset: TimeOffset) -> Self {
+        Self::new(self.position_ticks.saturating_add_signed(offset.ticks()))
+    }
+
+    /// Move by a signed offset, wrapping around at the limits of `u64`
+    pub fn wrapping_offset(self, offset: TimeOffset) -> Self {
+        Self::new(self.position_ticks.wrapping_add_signed(offset.ticks()))
+    }
 }
 
 // Make TimePosition comparable for use in BTreeMap
@@ -45,13 +99,16 @@ impl PartialOrd for TimePosition {
     }
 }
 
+// Arithmetic operators saturate at the start and end of the timeline;
+// use the checked, wrapping or offset methods to choose otherwise
+
 // Allow addition of TimePositions
 impl Add for TimePosition {
     type Output = Self;
     
     fn add(self, other: Self) -> Self {
         Self {
-            position_ticks: self.position_ticks + other.position_ticks,
+            position_ticks: self.position_ticks.saturating_add(other.position_ticks),
         }
     }
 }
@@ -71,35 +128,31 @@ impl Add<Duration> for TimePosition {
     type Output = Self;
 
     fn add(self, duration: Duration) -> Self {
-        Self {
-            position_ticks: self.position_ticks + duration.ticks(),
-        }
+        self.saturating_add(duration)
     }
 }
 impl Sub<Duration> for TimePosition {
     type Output = Self;
 
     fn sub(self, duration: Duration) -> Self {
-        Self {
-            position_ticks: self.position_ticks.saturating_sub(duration.ticks()),
-        }
+        self.saturating_sub(duration)
     }
 }
 impl AddAssign<Duration> for TimePosition {
     fn add_assign(&mut self, duration: Duration) {
-        self.position_ticks += duration.ticks();
+        *self = self.saturating_add(duration);
     }
 }
 impl SubAssign<Duration> for TimePosition {
     fn sub_assign(&mut self, duration: Duration) {
-        self.position_ticks = self.position_ticks.saturating_sub(duration.ticks());
+        *self = self.saturating_sub(duration);
     }
 }
 
 // Allow in-place addition
 impl AddAssign for TimePosition {
     fn add_assign(&mut self, other: Self) {
-        self.position_ticks += other.position_ticks;
+        self.position_ticks = self.position_ticks.saturating_add(other.position_ticks);
     }
 }
 
@@ -108,4 +161,20 @@ impl SubAssign for TimePosition {
     fn sub_assign(&mut self, other: Self) {
         self.position_ticks = self.position_ticks.saturating_sub(other.position_ticks);
     }
-}
\ No newline at end of file
+}
+
+impl Add<TimeOffset> for TimePosition {
+    type Output = Self;
+
+    fn add(self, offset: TimeOffset) -> Self {
+        self.saturating_offset(offset)
+    }
+}
+
+impl Sub<TimeOffset> for TimePosition {
+    type Output = Self;
+
+    fn sub(self, offset: TimeOffset) -> Self {
+        self.saturating_offset(-offset)
+    }
+}
@@ -0,0 +1,178 @@
+use crate::tapestry::duration::Duration;
+use crate::tapestry::note_value::NoteValue;
+use crate::tapestry::position::TimePosition;
+use crate::tapestry::tempo_map::TempoMap;
+
+/// Which edges of an event are moved onto the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuantizeMode {
+    /// Move the start and keep the length
+    #[default]
+    StartOnly,
+    /// Move the start and the end independently
+    StartAndEnd,
+}
+
+/// Timing of a note or container being quantized, along with its unquantized timing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantizeSpan {
+    pub start: TimePosition,
+    pub length: Duration,
+    /// Start as played or placed, used as the base for non-iterative quantize
+    pub original_start: TimePosition,
+    /// Length as played or placed
+    pub original_length: Duration,
+}
+
+impl QuantizeSpan {
+    pub fn new(start: TimePosition, length: Duration) -> Self {
+        Self { start, length, original_start: start, original_length: length }
+    }
+
+    /// Undo any quantize that has not been made permanent by an iterative pass
+    pub fn unquantize(&mut self) {
+        self.start = self.original_start;
+        self.length = self.original_length;
+    }
+}
+
+/// Moves positions and lengths towards the musical grid
+///
+/// Grid lines are counted from each bar line, so odd meters and meter changes keep
+/// their own grid. Percentages run from 0 to 100.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantizer {
+    /// Distance between grid lines
+    pub grid: NoteValue,
+
+    /// How far to move towards the grid line, as a percentage of the distance
+    pub strength: f64,
+
+    /// How late every second grid line sits; 100 places it two thirds of the way
+    /// through the pair, a triplet feel
+    pub swing: f64,
+
+    /// How close an event must be to its grid line to be moved, as a percentage of
+    /// half a grid step; 100 captures everything
+    pub capture_window: f64,
+
+    /// Which edges are moved
+    pub mode: QuantizeMode,
+
+    /// Quantize from the current timing and keep the result as the new original,
+    /// instead of always starting again from the original timing
+    pub iterative: bool,
+}
+
+impl Quantizer {
+    /// Full-strength, straight quantize of starts to `grid`
+    pub fn new(grid: NoteValue) -> Self {
+        Self {
+            grid,
+            strength: 100.0,
+            swing: 0.0,
+            capture_window: 100.0,
+            mode: QuantizeMode::StartOnly,
+            iterative: false,
+        }
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength.clamp(0.0, 100.0);
+        self
+    }
+
+    pub fn with_swing(mut self, swing: f64) -> Self {
+        self.swing = swing.clamp(0.0, 100.0);
+        self
+    }
+
+    pub fn with_capture_window(mut self, capture_window: f64) -> Self {
+        self.capture_window = capture_window.clamp(0.0, 100.0);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: QuantizeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_iterative(mut self, iterative: bool) -> Self {
+        self.iterative = iterative;
+        self
+    }
+
+    /// Quantize a single position
+    pub fn quantize_position(&self, position: &TimePosition, tempo_map: &TempoMap) -> TimePosition {
+        let beats = tempo_map.position_to_beats(position);
+        tempo_map.beats_to_position(self.quantize_beats(beats, tempo_map))
+    }
+
+    /// Quantize a beat count (in quarter notes)
+    pub fn quantize_beats(&self, beats: f64, tempo_map: &TempoMap) -> f64 {
+        let step = self.grid.to_beats();
+        if step <= 0.0 {
+            return beats;
+        }
+
+        let target = self.nearest_line(beats, step, tempo_map);
+        let distance = target - beats;
+        let captured = self.capture_window >= 100.0 || distance.abs() <= step / 2.0 * self.capture_window / 100.0;
+        if !captured {
+            return beats;
+        }
+
+        beats + distance * self.strength / 100.0
+    }
+
+    /// Quantize a note or container according to the mode
+    pub fn quantize_span(&self, span: &mut QuantizeSpan, tempo_map: &TempoMap) {
+        let (base_start, base_length) = if self.iterative {
+            (span.start, span.length)
+        } else {
+            (span.original_start, span.original_length)
+        };
+
+        let start = self.quantize_position(&base_start, tempo_map);
+        let length = match self.mode {
+            QuantizeMode::StartOnly => base_length,
+            QuantizeMode::StartAndEnd => {
+                let end = self.quantize_position(&base_start.saturating_add(base_length), tempo_map);
+                if end > start {
+                    end.duration_since(start)
+                } else {
+                    // Never quantize a note away; keep at least one grid step
+                    tempo_map.note_value_to_duration(&start, self.grid)
+                }
+            }
+        };
+
+        span.start = start;
+        span.length = length;
+        if self.iterative {
+            span.original_start = start;
+            span.original_length = length;
+        }
+    }
+
+    /// Beat count of the grid line closest to `beats`, with swing applied
+    fn nearest_line(&self, beats: f64, step: f64, tempo_map: &TempoMap) -> f64 {
+        let (bars, _) = tempo_map.beats_to_bars_and_beats(beats);
+        let bar_start = tempo_map.bars_and_beats_to_beats(bars, 0.0);
+        let bar_end = tempo_map.bars_and_beats_to_beats(bars + 1, 0.0);
+
+        let line = |index: f64| {
+            let swing = if index % 2.0 == 1.0 { step / 3.0 * self.swing / 100.0 } else { 0.0 };
+            // Lines past the bar line fall on the next bar's first line
+            (bar_start + index * step + swing).min(bar_end)
+        };
+
+        let below = ((beats - bar_start) / step).floor().max(0.0);
+        [below - 1.0, below, below + 1.0]
+            .into_iter()
+            .filter(|index| *index >= 0.0)
+            .map(line)
+            .min_by(|a, b| (a - beats).abs().total_cmp(&(b - beats).abs()))
+            .unwrap_or(bar_start)
+    }
+}
@@ -1,5 +1,8 @@
+use thiserror::Error;
+use crate::tapestry::note_value::NoteValue;
+
 /// Represents musical tempo
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Tempo {
     /// Beats per minute
     pub bpm: f64,
@@ -16,22 +19,199 @@ impl Tempo {
     }
 }
 
+/// Shape of the transition from one tempo change to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TempoCurve {
+    /// Jump to the new tempo and hold it until the next change
+    #[default]
+    Step,
+
+    /// Move towards the next tempo by a constant number of BPM per second
+    Linear,
+
+    /// Move towards the next tempo by a constant ratio per second
+    Exponential,
+}
+
+/// A tempo change on the tempo map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    /// Tempo at the position of the change
+    pub tempo: Tempo,
+    /// How the tempo travels to the following change
+    pub curve: TempoCurve,
+}
+
+impl TempoChange {
+    pub fn new(tempo: Tempo, curve: TempoCurve) -> Self {
+        Self { tempo, curve }
+    }
+
+    /// A change that holds its tempo until the next change
+    pub fn step(tempo: Tempo) -> Self {
+        Self::new(tempo, TempoCurve::Step)
+    }
+}
+
+/// Most groups a bar can be split into
+pub const MAX_BEAT_GROUPS: usize = 16;
+
+/// Errors produced when building a time signature
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MeterError {
+    #[error("beat groups add up to {sum}, but the bar has {numerator} beats")]
+    GroupingMismatch { sum: u32, numerator: u8 },
+    #[error("a bar can be split into at most {MAX_BEAT_GROUPS} groups, got {0}")]
+    TooManyGroups(usize),
+    #[error("beat groups must not be empty")]
+    EmptyGroup,
+    #[error("a bar needs at least one beat")]
+    NoBeats,
+    #[error("the beat unit must be a whole note divided by a power of two, got {0}")]
+    InvalidBeatUnit(u8),
+}
+
+/// How strongly a beat is felt within its bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Accent {
+    /// Any other beat
+    Weak,
+    /// First beat of a group other than the first
+    GroupStart,
+    /// First beat of the bar
+    Downbeat,
+}
+
+impl Accent {
+    /// Relative strength from 0.0 to 1.0, e.g. for metronome or groove velocity
+    pub fn strength(&self) -> f32 {
+        match self {
+            Accent::Downbeat => 1.0,
+            Accent::GroupStart => 0.7,
+            Accent::Weak => 0.4,
+        }
+    }
+}
+
 /// Represents musical time signature
-#[derive(Debug, Clone, Copy)]
+///
+/// The numerator counts beats of the unit given by the denominator, so 6/8 is six
+/// eighth notes (three quarter notes) long. Beats are gathered into groups that set
+/// where accents fall, e.g. 2+2+3 for a 7/8 bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TimeSignature {
     /// Top number (how many beats per bar)
     pub numerator: u8,
     /// Bottom number (what note value gets one beat)
     pub denominator: u8,
+    /// Beats in each group, summing to the numerator; only the first `group_count` are used
+    groups: [u8; MAX_BEAT_GROUPS],
+    group_count: u8,
 }
 
 impl TimeSignature {
-    pub fn new(numerator: u8, denominator: u8) -> Self {
-        Self { numerator, denominator }
+    /// Create a time signature with the conventional grouping for its meter
+    ///
+    /// Compound meters (6/8, 9/8, 12/8) group in threes, odd eighth-note meters in
+    /// twos with a closing three (7/8 as 2+2+3), and simple meters beat by beat.
+    /// Bars too long to group that way form a single group. Bars without beats and
+    /// beat units other than a whole note divided by a power of two are rejected.
+    pub fn new(numerator: u8, denominator: u8) -> Result<Self, MeterError> {
+        if numerator == 0 {
+            return Err(MeterError::NoBeats);
+        }
+        if !denominator.is_power_of_two() {
+            return Err(MeterError::InvalidBeatUnit(denominator));
+        }
+        Ok(Self::with_conventional_grouping(numerator, denominator))
     }
-    
-    /// Get number of beats in a bar
+
+    /// 4/4, the meter every map starts in
+    pub fn common_time() -> Self {
+        Self::with_conventional_grouping(4, 4)
+    }
+
+    fn with_conventional_grouping(numerator: u8, denominator: u8) -> Self {
+        let mut groups = [0; MAX_BEAT_GROUPS];
+        let mut group_count = 0;
+        let mut push = |size: u8| {
+            if group_count < MAX_BEAT_GROUPS {
+                groups[group_count] = size;
+            }
+            group_count += 1;
+        };
+
+        let eighths_or_shorter = denominator >= 8 && numerator > 3;
+        if eighths_or_shorter && numerator.is_multiple_of(3) {
+            (0..numerator / 3).for_each(|_| push(3));
+        } else if eighths_or_shorter && !numerator.is_multiple_of(2) {
+            (0..(numerator - 3) / 2).for_each(|_| push(2));
+            push(3);
+        } else {
+            (0..numerator).for_each(|_| push(1));
+        }
+
+        if group_count > MAX_BEAT_GROUPS {
+            groups = [0; MAX_BEAT_GROUPS];
+            groups[0] = numerator;
+            group_count = 1;
+        }
+
+        Self { numerator, denominator, groups, group_count: group_count as u8 }
+    }
+
+    /// Use a custom beat grouping, e.g. `&[3, 2, 2]` for a 7/8 bar felt as 3+2+2
+    pub fn with_grouping(mut self, groups: &[u8]) -> Result<Self, MeterError> {
+        if groups.len() > MAX_BEAT_GROUPS {
+            return Err(MeterError::TooManyGroups(groups.len()));
+        }
+        if groups.contains(&0) {
+            return Err(MeterError::EmptyGroup);
+        }
+        let sum = groups.iter().map(|&size| size as u32).sum();
+        if sum != self.numerator as u32 {
+            return Err(MeterError::GroupingMismatch { sum, numerator: self.numerator });
+        }
+
+        self.groups = [0; MAX_BEAT_GROUPS];
+        self.groups[..groups.len()].copy_from_slice(groups);
+        self.group_count = groups.len() as u8;
+        Ok(self)
+    }
+
+    /// Beats in each group
+    pub fn grouping(&self) -> &[u8] {
+        &self.groups[..self.group_count as usize]
+    }
+
+    /// Note value of one beat, e.g. an eighth note in 7/8
+    pub fn beat_unit(&self) -> NoteValue {
+        NoteValue::new(4, self.denominator as u64)
+    }
+
+    /// Get number of beats in a bar, counted in the beat unit
     pub fn beats_per_bar(&self) -> f64 {
         self.numerator as f64
     }
-}
\ No newline at end of file
+
+    /// Length of a bar in quarter notes, the unit `TempoMap` counts beats in
+    pub fn quarter_notes_per_bar(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+
+    /// Accent of the zero-based beat within the bar, following the grouping
+    pub fn accent_at(&self, beat: u32) -> Accent {
+        if beat == 0 {
+            return Accent::Downbeat;
+        }
+
+        let mut group_start = 0;
+        for &size in self.grouping() {
+            if beat == group_start {
+                return Accent::GroupStart;
+            }
+            group_start += size as u32;
+        }
+        Accent::Weak
+    }
+}
@@ -1,6 +1,23 @@
 use std::collections::BTreeMap;
+use thiserror::Error;
+use crate::tapestry::duration::Duration;
+use crate::tapestry::note_value::NoteValue;
 use crate::tapestry::position::TimePosition;
-use crate::tapestry::tempo::{Tempo, TimeSignature};
+use crate::tapestry::tempo::{Tempo, TempoChange, TempoCurve, TimeSignature};
+
+/// Tolerance used when snapping beat counts onto bar lines
+const BEAT_EPSILON: f64 = 1e-9;
+
+/// Errors produced when editing a tempo map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum TempoMapError {
+    #[error("no change at tick {}", .0.position_ticks)]
+    NotFound(TimePosition),
+    #[error("the initial change at position zero cannot be removed or moved")]
+    InitialChange,
+    #[error("a change already exists at tick {}", .0.position_ticks)]
+    Occupied(TimePosition),
+}
 
 /// Maps between different time domains: ticks, beats, bars, etc.
 #[derive(Debug, Clone)]
@@ -10,9 +27,13 @@ pub struct TempoMap {
     /// Current playback sample rate
     playback_sample_rate: u32,
     /// Tempo changes keyed by position
-    tempo_changes: BTreeMap<TimePosition, Tempo>,
+    tempo_changes: BTreeMap<TimePosition, TempoChange>,
     /// Time signature changes keyed by position
     time_signature_changes: BTreeMap<TimePosition, TimeSignature>,
+    /// Tempo segments derived from `tempo_changes`, ordered by position
+    tempo_segments: Vec<TempoSegment>,
+    /// Meter segments derived from `time_signature_changes`, ordered by position
+    meter_segments: Vec<MeterSegment>,
 }
 
 impl TempoMap {
@@ -20,173 +41,438 @@ impl TempoMap {
     pub fn new(reference_sample_rate: u32, playback_sample_rate: u32) -> Self {
         let mut tempo_changes = BTreeMap::new();
         let mut time_signature_changes = BTreeMap::new();
-        
+
         // Default to 120 BPM, 4/4 time at position zero
-        tempo_changes.insert(TimePosition::zero(), Tempo::new(120.0));
-        time_signature_changes.insert(TimePosition::zero(), TimeSignature::new(4, 4));
-        
-        Self {
+        tempo_changes.insert(TimePosition::zero(), TempoChange::step(Tempo::new(120.0)));
+        time_signature_changes.insert(TimePosition::zero(), TimeSignature::common_time());
+
+        let mut map = Self {
             reference_sample_rate,
             playback_sample_rate,
             tempo_changes,
             time_signature_changes,
-        }
+            tempo_segments: Vec::new(),
+            meter_segments: Vec::new(),
+        };
+        map.rebuild_segments();
+        map
     }
-    
+
     /// Set the playback sample rate without changing time positions
     pub fn set_playback_sample_rate(&mut self, new_playback_sample_rate: u32) {
         self.playback_sample_rate = new_playback_sample_rate;
     }
-    
+
     /// Add a tempo change at the specified position
     pub fn add_tempo_change(&mut self, position: TimePosition, tempo: Tempo) {
-        self.tempo_changes.insert(position, tempo);
+        self.tempo_changes.insert(position, TempoChange::step(tempo));
+        self.rebuild_segments();
     }
-    
+
+    /// Add a tempo change that ramps towards the following tempo change
+    pub fn add_tempo_ramp(&mut self, position: TimePosition, tempo: Tempo, curve: TempoCurve) {
+        self.tempo_changes.insert(position, TempoChange::new(tempo, curve));
+        self.rebuild_segments();
+    }
+
     /// Add a time signature change at the specified position
+    ///
+    /// A time signature change always starts a new bar. If it falls inside a bar
+    /// of the previous meter, that bar is cut short.
     pub fn add_time_signature_change(&mut self, position: TimePosition, time_signature: TimeSignature) {
         self.time_signature_changes.insert(position, time_signature);
+        self.rebuild_segments();
+    }
+
+    /// Replace or insert a tempo change, keeping its curve as given
+    pub fn set_tempo_change(&mut self, position: TimePosition, change: TempoChange) {
+        self.tempo_changes.insert(position, change);
+        self.rebuild_segments();
+    }
+
+    /// Remove the tempo change at exactly `position`
+    pub fn remove_tempo_change(&mut self, position: TimePosition) -> Result<TempoChange, TempoMapError> {
+        let change = Self::remove_change(&mut self.tempo_changes, position)?;
+        self.rebuild_segments();
+        Ok(change)
+    }
+
+    /// Move the tempo change at `from` to `to`
+    pub fn move_tempo_change(&mut self, from: TimePosition, to: TimePosition) -> Result<(), TempoMapError> {
+        Self::move_change(&mut self.tempo_changes, from, to)?;
+        self.rebuild_segments();
+        Ok(())
     }
-    
-    /// Get the tempo at a specific position
+
+    /// The tempo change starting at exactly `position`, if any
+    pub fn tempo_change(&self, position: &TimePosition) -> Option<&TempoChange> {
+        self.tempo_changes.get(position)
+    }
+
+    /// All tempo changes in position order
+    pub fn tempo_changes(&self) -> impl Iterator<Item = (&TimePosition, &TempoChange)> {
+        self.tempo_changes.iter()
+    }
+
+    /// Remove the time signature change at exactly `position`
+    pub fn remove_time_signature_change(&mut self, position: TimePosition) -> Result<TimeSignature, TempoMapError> {
+        let time_signature = Self::remove_change(&mut self.time_signature_changes, position)?;
+        self.rebuild_segments();
+        Ok(time_signature)
+    }
+
+    /// Move the time signature change at `from` to `to`
+    pub fn move_time_signature_change(&mut self, from: TimePosition, to: TimePosition) -> Result<(), TempoMapError> {
+        Self::move_change(&mut self.time_signature_changes, from, to)?;
+        self.rebuild_segments();
+        Ok(())
+    }
+
+    /// The time signature change starting at exactly `position`, if any
+    pub fn time_signature_change(&self, position: &TimePosition) -> Option<&TimeSignature> {
+        self.time_signature_changes.get(position)
+    }
+
+    /// All time signature changes in position order
+    pub fn time_signature_changes(&self) -> impl Iterator<Item = (&TimePosition, &TimeSignature)> {
+        self.time_signature_changes.iter()
+    }
+
+    /// Get the tempo at a specific position, following any ramp in progress
     pub fn tempo_at(&self, position: &TimePosition) -> Tempo {
-        // Find the last tempo change before or at the given position
-        match self.tempo_changes.range(..=position).next_back() {
-            Some((_, tempo)) => *tempo,
-            None => panic!("No tempo defined"), // Should never happen as we always have a default
-        }
+        let segment = self.tempo_segment_at_ticks(position.position_ticks);
+        let secs = self.ticks_to_secs(position.position_ticks - segment.start_ticks);
+        Tempo::new(segment.ramp.bpm_at(secs))
     }
-    
+
     /// Get the time signature at a specific position
     pub fn time_signature_at(&self, position: &TimePosition) -> TimeSignature {
-        // Find the last time signature change before or at the given position
-        match self.time_signature_changes.range(..=position).next_back() {
-            Some((_, time_sig)) => *time_sig,
-            None => panic!("No time signature defined"), // Should never happen as we always have a default
-        }
+        self.meter_segment_at_ticks(position.position_ticks).time_signature
+    }
+
+    /// Get the time signature of a bar, counted from zero as in `position_to_bars_and_beats`
+    pub fn time_signature_at_bar(&self, bars: u32) -> TimeSignature {
+        self.meter_segment_at_bar(bars).time_signature
     }
-    
+
     /// Convert from internal ticks to actual playback samples
     pub fn ticks_to_playback_samples(&self, position: &TimePosition) -> u64 {
-        (position.position_ticks as f64 * self.playback_sample_rate as f64 / 
+        (position.position_ticks as f64 * self.playback_sample_rate as f64 /
          self.reference_sample_rate as f64).round() as u64
     }
-    
+
     /// Convert from actual playback samples to internal ticks
     pub fn playback_samples_to_ticks(&self, samples: u64) -> TimePosition {
         TimePosition {
-            position_ticks: (samples as f64 * self.reference_sample_rate as f64 / 
+            position_ticks: (samples as f64 * self.reference_sample_rate as f64 /
                             self.playback_sample_rate as f64).round() as u64
         }
     }
-    
+
+    /// Convert time position to seconds
+    pub fn position_to_seconds(&self, position: &TimePosition) -> f64 {
+        self.ticks_to_secs(position.position_ticks)
+    }
+
+    /// Convert seconds to time position
+    pub fn seconds_to_position(&self, seconds: f64) -> TimePosition {
+        TimePosition::new(self.secs_to_ticks(seconds.max(0.0)))
+    }
+
     /// Convert time position to beats
     pub fn position_to_beats(&self, position: &TimePosition) -> f64 {
-        // We need to process each tempo segment separately
-        let mut result = 0.0;
-        let mut last_position = TimePosition::zero();
-        let mut last_tempo = self.tempo_at(&last_position);
-        
-        // Process each tempo change segment
-        for (change_position, tempo) in self.tempo_changes.range(&TimePosition::zero()..=position) {
-            if change_position > &last_position {
-                // Calculate beats in the segment with consistent tempo
-                let segment_duration_secs = (change_position.position_ticks - last_position.position_ticks) as f64 / 
-                                           self.reference_sample_rate as f64;
-                result += segment_duration_secs / last_tempo.beat_duration_secs();
-                
-                // Update for next segment
-                last_position = *change_position;
-                last_tempo = *tempo;
-            }
-        }
-        
-        // Process final segment up to the target position
-        if position > &last_position {
-            let final_duration_secs = (position.position_ticks - last_position.position_ticks) as f64 / 
-                                     self.reference_sample_rate as f64;
-            result += final_duration_secs / last_tempo.beat_duration_secs();
-        }
-        
-        result
+        let segment = self.tempo_segment_at_ticks(position.position_ticks);
+        let secs = self.ticks_to_secs(position.position_ticks - segment.start_ticks);
+        segment.start_beats + segment.ramp.beats_at(secs)
     }
-    
+
     /// Convert beats to time position
     pub fn beats_to_position(&self, beats: f64) -> TimePosition {
-        let mut remaining_beats = beats;
-        let mut current_position = TimePosition::zero();
-        let mut current_tempo = self.tempo_at(&current_position);
-        let mut iter = self.tempo_changes.iter();
-        
-        // Skip the first entry which is at position zero
-        let _ = iter.next();
-        
-        // Process each tempo segment
-        for (change_position, next_tempo) in iter {
-            // Calculate beats until the next tempo change
-            let current_beats = self.position_to_beats(change_position);
-            
-            if remaining_beats <= current_beats {
-                // Target is within this tempo segment
-                break;
-            }
-            
-            // Move to the tempo change position and update tempo
-            current_position = *change_position;
-            current_tempo = *next_tempo;
-            remaining_beats -= current_beats;
-        }
-        
-        // Calculate final position within the current tempo segment
-        let segment_duration_secs = remaining_beats * current_tempo.beat_duration_secs();
-        let additional_ticks = (segment_duration_secs * self.reference_sample_rate as f64).round() as u64;
-        
-        TimePosition {
-            position_ticks: current_position.position_ticks + additional_ticks
-        }
+        let beats = beats.max(0.0);
+        let segment = self.tempo_segment_at_beats(beats);
+        let secs = segment.ramp.secs_at(beats - segment.start_beats);
+        TimePosition::new(segment.start_ticks + self.secs_to_ticks(secs))
+    }
+
+    /// Length of `beats` beats starting at `start`
+    pub fn beats_to_duration(&self, start: &TimePosition, beats: f64) -> Duration {
+        let end = self.beats_to_position(self.position_to_beats(start) + beats);
+        end.duration_since(*start)
     }
-    
+
+    /// Number of beats covered by `duration` starting at `start`
+    pub fn duration_to_beats(&self, start: &TimePosition, duration: &Duration) -> f64 {
+        let end = start.saturating_add(*duration);
+        self.position_to_beats(&end) - self.position_to_beats(start)
+    }
+
+    /// Length of a musical note value starting at `start`
+    pub fn note_value_to_duration(&self, start: &TimePosition, note_value: NoteValue) -> Duration {
+        self.beats_to_duration(start, note_value.to_beats())
+    }
+
+    /// Convert seconds to beats
+    pub fn seconds_to_beats(&self, seconds: f64) -> f64 {
+        self.position_to_beats(&self.seconds_to_position(seconds))
+    }
+
+    /// Convert beats to seconds
+    pub fn beats_to_seconds(&self, beats: f64) -> f64 {
+        self.position_to_seconds(&self.beats_to_position(beats))
+    }
+
     /// Convert time position to bars and beats
+    ///
+    /// Returns the number of complete bars before the position and the offset into the
+    /// current bar in quarter notes, whatever the meter's beat unit.
     pub fn position_to_bars_and_beats(&self, position: &TimePosition) -> (u32, f64) {
-        let total_beats = self.position_to_beats(position);
-        let mut remaining_beats = total_beats;
-        let mut bars = 0;
-        
-        // Accumulate bars based on time signature changes
-        let mut last_sig_position = TimePosition::zero();
-        let mut last_sig = self.time_signature_at(&last_sig_position);
-        
-        for (sig_position, time_sig) in self.time_signature_changes.range(..=position) {
-            let beats_at_sig_change = self.position_to_beats(sig_position);
-            
-            if beats_at_sig_change > 0.0 {
-                // Process complete bars in the current time signature
-                let beats_in_segment = beats_at_sig_change - self.position_to_beats(&last_sig_position);
-                let bars_in_segment = (beats_in_segment / last_sig.beats_per_bar()).floor();
-                
-                bars += bars_in_segment as u32;
-                remaining_beats -= bars_in_segment * last_sig.beats_per_bar();
-                
-                // Update for next segment
-                last_sig_position = *sig_position;
-                last_sig = *time_sig;
-            }
-        }
-        
-        // Process the final segment
-        let final_bars = (remaining_beats / last_sig.beats_per_bar()).floor() as u32;
-        let beat_in_bar = remaining_beats - (final_bars as f64 * last_sig.beats_per_bar());
-        
-        (bars + final_bars, beat_in_bar)
-    }
-    
+        self.beats_to_bars_and_beats(self.position_to_beats(position))
+    }
+
+    /// Convert a beat count to bars and beats
+    pub fn beats_to_bars_and_beats(&self, beats: f64) -> (u32, f64) {
+        let segment = self.meter_segment_at_beats(beats);
+        let beats_per_bar = segment.time_signature.quarter_notes_per_bar();
+        let beats_into_segment = (beats - segment.start_beats).max(0.0);
+
+        let bars_into_segment = ((beats_into_segment + BEAT_EPSILON) / beats_per_bar).floor();
+        let beat_in_bar = (beats_into_segment - bars_into_segment * beats_per_bar).max(0.0);
+
+        (segment.start_bar + bars_into_segment as u32, beat_in_bar)
+    }
+
+    /// Convert bars and beats (as returned by `position_to_bars_and_beats`) to a beat count
+    pub fn bars_and_beats_to_beats(&self, bars: u32, beat_in_bar: f64) -> f64 {
+        let segment = self.meter_segment_at_bar(bars);
+        let beats_per_bar = segment.time_signature.quarter_notes_per_bar();
+
+        segment.start_beats + (bars - segment.start_bar) as f64 * beats_per_bar + beat_in_bar
+    }
+
+    /// Convert bars and beats (as returned by `position_to_bars_and_beats`) to a time position
+    pub fn bars_and_beats_to_position(&self, bars: u32, beat_in_bar: f64) -> TimePosition {
+        self.beats_to_position(self.bars_and_beats_to_beats(bars, beat_in_bar))
+    }
+
     /// Get the reference sample rate
     pub fn reference_sample_rate(&self) -> u32 {
         self.reference_sample_rate
     }
-    
+
     /// Get the playback sample rate
     pub fn playback_sample_rate(&self) -> u32 {
         self.playback_sample_rate
     }
-}
\ No newline at end of file
+
+    /// Remove a change, refusing the one at zero that the map always needs
+    fn remove_change<T>(changes: &mut BTreeMap<TimePosition, T>, position: TimePosition) -> Result<T, TempoMapError> {
+        if position == TimePosition::zero() {
+            return Err(TempoMapError::InitialChange);
+        }
+        changes.remove(&position).ok_or(TempoMapError::NotFound(position))
+    }
+
+    /// Move a change, refusing to move the one at zero or to overwrite another
+    fn move_change<T>(changes: &mut BTreeMap<TimePosition, T>, from: TimePosition, to: TimePosition) -> Result<(), TempoMapError> {
+        if from == to {
+            return if changes.contains_key(&from) { Ok(()) } else { Err(TempoMapError::NotFound(from)) };
+        }
+        if changes.contains_key(&to) {
+            return Err(TempoMapError::Occupied(to));
+        }
+
+        let change = Self::remove_change(changes, from)?;
+        changes.insert(to, change);
+        Ok(())
+    }
+
+    fn ticks_to_secs(&self, ticks: u64) -> f64 {
+        ticks as f64 / self.reference_sample_rate as f64
+    }
+
+    fn secs_to_ticks(&self, secs: f64) -> u64 {
+        (secs * self.reference_sample_rate as f64).round() as u64
+    }
+
+    fn tempo_segment_at_ticks(&self, ticks: u64) -> &TempoSegment {
+        let index = self.tempo_segments.partition_point(|s| s.start_ticks <= ticks);
+        &self.tempo_segments[index.saturating_sub(1)]
+    }
+
+    fn tempo_segment_at_beats(&self, beats: f64) -> &TempoSegment {
+        let index = self.tempo_segments.partition_point(|s| s.start_beats <= beats);
+        &self.tempo_segments[index.saturating_sub(1)]
+    }
+
+    fn meter_segment_at_ticks(&self, ticks: u64) -> &MeterSegment {
+        let index = self.meter_segments.partition_point(|s| s.start_ticks <= ticks);
+        &self.meter_segments[index.saturating_sub(1)]
+    }
+
+    fn meter_segment_at_bar(&self, bars: u32) -> &MeterSegment {
+        let index = self.meter_segments.partition_point(|s| s.start_bar <= bars);
+        &self.meter_segments[index.saturating_sub(1)]
+    }
+
+    fn meter_segment_at_beats(&self, beats: f64) -> &MeterSegment {
+        let index = self.meter_segments.partition_point(|s| s.start_beats <= beats);
+        &self.meter_segments[index.saturating_sub(1)]
+    }
+
+    /// Recompute the tempo and meter segment tables after the map changed
+    fn rebuild_segments(&mut self) {
+        let mut tempo_segments = Vec::with_capacity(self.tempo_changes.len());
+        let mut changes = self.tempo_changes.iter().peekable();
+        let mut start_beats = 0.0;
+
+        while let Some((start, change)) = changes.next() {
+            let next = changes.peek().copied();
+            let ramp = match next {
+                Some((end, next_change)) if change.curve != TempoCurve::Step => TempoRamp {
+                    start_bpm: change.tempo.bpm,
+                    end_bpm: next_change.tempo.bpm,
+                    span_secs: self.ticks_to_secs(end.position_ticks - start.position_ticks),
+                    curve: change.curve,
+                },
+                _ => TempoRamp::constant(change.tempo.bpm),
+            };
+
+            tempo_segments.push(TempoSegment {
+                start_ticks: start.position_ticks,
+                start_beats,
+                ramp,
+            });
+
+            if let Some((end, _)) = next {
+                start_beats += ramp.beats_at(self.ticks_to_secs(end.position_ticks - start.position_ticks));
+            }
+        }
+        self.tempo_segments = tempo_segments;
+
+        // Meter segments need the tempo segments in place to place themselves in beats
+        let mut meter_segments: Vec<MeterSegment> = Vec::with_capacity(self.time_signature_changes.len());
+        for (start, time_signature) in &self.time_signature_changes {
+            let start_beats = self.position_to_beats(start);
+            let start_bar = match meter_segments.last() {
+                Some(previous) => {
+                    // An incomplete bar before a meter change still counts as a bar
+                    let bars = (start_beats - previous.start_beats) / previous.time_signature.quarter_notes_per_bar();
+                    previous.start_bar + (bars - BEAT_EPSILON).ceil().max(0.0) as u32
+                }
+                None => 0,
+            };
+
+            meter_segments.push(MeterSegment {
+                start_ticks: start.position_ticks,
+                start_beats,
+                start_bar,
+                time_signature: *time_signature,
+            });
+        }
+        self.meter_segments = meter_segments;
+    }
+}
+
+/// A span of the tempo map governed by a single tempo change
+#[derive(Debug, Clone, Copy)]
+struct TempoSegment {
+    start_ticks: u64,
+    /// Beats elapsed from zero up to the start of this segment
+    start_beats: f64,
+    ramp: TempoRamp,
+}
+
+/// A span of the tempo map governed by a single time signature
+#[derive(Debug, Clone, Copy)]
+struct MeterSegment {
+    start_ticks: u64,
+    start_beats: f64,
+    /// Complete bars from zero up to the start of this segment
+    start_bar: u32,
+    time_signature: TimeSignature,
+}
+
+/// Tempo behaviour between two tempo changes
+#[derive(Debug, Clone, Copy)]
+struct TempoRamp {
+    start_bpm: f64,
+    end_bpm: f64,
+    /// Length of the segment in seconds
+    span_secs: f64,
+    curve: TempoCurve,
+}
+
+impl TempoRamp {
+    fn constant(bpm: f64) -> Self {
+        Self {
+            start_bpm: bpm,
+            end_bpm: bpm,
+            span_secs: 0.0,
+            curve: TempoCurve::Step,
+        }
+    }
+
+    /// Whether the tempo actually changes across this segment
+    fn is_ramping(&self) -> bool {
+        self.curve != TempoCurve::Step && self.span_secs > 0.0 && self.start_bpm != self.end_bpm
+    }
+
+    /// Instantaneous tempo `secs` seconds into the segment
+    fn bpm_at(&self, secs: f64) -> f64 {
+        if !self.is_ramping() {
+            return self.start_bpm;
+        }
+
+        let progress = (secs / self.span_secs).clamp(0.0, 1.0);
+        match self.curve {
+            TempoCurve::Linear => self.start_bpm + (self.end_bpm - self.start_bpm) * progress,
+            TempoCurve::Exponential => self.start_bpm * (self.end_bpm / self.start_bpm).powf(progress),
+            TempoCurve::Step => self.start_bpm,
+        }
+    }
+
+    /// Number of beats elapsed `secs` seconds into the segment
+    fn beats_at(&self, secs: f64) -> f64 {
+        let start_rate = self.start_bpm / 60.0;
+        if !self.is_ramping() {
+            return secs * start_rate;
+        }
+
+        let end_rate = self.end_bpm / 60.0;
+        match self.curve {
+            TempoCurve::Linear => {
+                // Integral of r0 + (r1 - r0) * t / T
+                start_rate * secs + (end_rate - start_rate) * secs * secs / (2.0 * self.span_secs)
+            }
+            TempoCurve::Exponential => {
+                // Integral of r0 * e^(k * t) with k = ln(r1 / r0) / T
+                let k = (end_rate / start_rate).ln() / self.span_secs;
+                start_rate * (k * secs).exp_m1() / k
+            }
+            TempoCurve::Step => secs * start_rate,
+        }
+    }
+
+    /// Seconds into the segment at which `beats` beats have elapsed
+    fn secs_at(&self, beats: f64) -> f64 {
+        let start_rate = self.start_bpm / 60.0;
+        if !self.is_ramping() {
+            return beats / start_rate;
+        }
+
+        let end_rate = self.end_bpm / 60.0;
+        match self.curve {
+            TempoCurve::Linear => {
+                // Positive root of a*t^2 + r0*t - beats = 0, in a form that stays stable as a -> 0
+                let a = (end_rate - start_rate) / (2.0 * self.span_secs);
+                let discriminant = (start_rate * start_rate + 4.0 * a * beats).max(0.0);
+                2.0 * beats / (start_rate + discriminant.sqrt())
+            }
+            TempoCurve::Exponential => {
+                let k = (end_rate / start_rate).ln() / self.span_secs;
+                (k * beats / start_rate).ln_1p() / k
+            }
+            TempoCurve::Step => beats / start_rate,
+        }
+    }
+}
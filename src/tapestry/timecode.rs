@@ -0,0 +1,271 @@
+use std::fmt;
+use thiserror::Error;
+use crate::tapestry::position::TimePosition;
+
+/// Number of subframes in one frame
+pub const SUBFRAMES_PER_FRAME: u32 = 100;
+
+/// Errors produced when parsing or building a timecode
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TimecodeError {
+    #[error("expected HH:MM:SS:FF(.sub), got {0:?}")]
+    InvalidFormat(String),
+    #[error("invalid number {0:?} in timecode")]
+    InvalidNumber(String),
+    #[error("{field} value {value} is out of range")]
+    OutOfRange { field: &'static str, value: u32 },
+    #[error("frame {frames} is dropped at {minutes} minutes in drop-frame timecode")]
+    DroppedFrame { minutes: u8, frames: u8 },
+}
+
+/// SMPTE frame rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameRate {
+    /// 24 frame labels per second, running 0.1% slow (film pulled down for NTSC)
+    Fps23_976,
+    Fps24,
+    Fps25,
+    /// 30 frame labels per second running 0.1% slow, skipping labels to stay on wall-clock time
+    Fps29_97Drop,
+    /// 30 frame labels per second running 0.1% slow
+    Fps29_97NonDrop,
+    Fps30,
+}
+
+impl FrameRate {
+    /// Number of frame labels per timecode second
+    pub fn frames_per_second(&self) -> u32 {
+        match self {
+            FrameRate::Fps23_976 | FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps29_97Drop | FrameRate::Fps29_97NonDrop | FrameRate::Fps30 => 30,
+        }
+    }
+
+    /// Whether frame labels are skipped to keep timecode on wall-clock time
+    pub fn is_drop_frame(&self) -> bool {
+        matches!(self, FrameRate::Fps29_97Drop)
+    }
+
+    /// Actual frames per wall-clock second as an exact (numerator, denominator) pair
+    pub fn rate(&self) -> (u64, u64) {
+        match self {
+            FrameRate::Fps23_976 => (24000, 1001),
+            FrameRate::Fps24 => (24, 1),
+            FrameRate::Fps25 => (25, 1),
+            FrameRate::Fps29_97Drop | FrameRate::Fps29_97NonDrop => (30000, 1001),
+            FrameRate::Fps30 => (30, 1),
+        }
+    }
+
+    /// Actual frames per wall-clock second
+    pub fn fps(&self) -> f64 {
+        let (numerator, denominator) = self.rate();
+        numerator as f64 / denominator as f64
+    }
+
+    /// Number of frames in 24 hours of timecode
+    pub fn frames_per_day(&self) -> u64 {
+        if self.is_drop_frame() {
+            // 17982 frames in every ten minutes
+            17982 * 6 * 24
+        } else {
+            self.frames_per_second() as u64 * 60 * 60 * 24
+        }
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FrameRate::Fps23_976 => "23.976",
+            FrameRate::Fps24 => "24",
+            FrameRate::Fps25 => "25",
+            FrameRate::Fps29_97Drop => "29.97 DF",
+            FrameRate::Fps29_97NonDrop => "29.97 NDF",
+            FrameRate::Fps30 => "30",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// SMPTE timecode label, e.g. `01:00:00:00`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    /// Fraction of a frame in 1/100ths
+    pub subframes: u8,
+    /// Frame rate the label is counted in
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    /// Create a timecode, rejecting labels that do not exist at the given rate
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Result<Self, TimecodeError> {
+        Self::with_subframes(hours, minutes, seconds, frames, 0, rate)
+    }
+
+    /// Create a timecode with a subframe offset
+    pub fn with_subframes(
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+        subframes: u8,
+        rate: FrameRate,
+    ) -> Result<Self, TimecodeError> {
+        let check = |field, value: u8, limit: u32| {
+            if (value as u32) < limit {
+                Ok(())
+            } else {
+                Err(TimecodeError::OutOfRange { field, value: value as u32 })
+            }
+        };
+        check("hours", hours, 24)?;
+        check("minutes", minutes, 60)?;
+        check("seconds", seconds, 60)?;
+        check("frames", frames, rate.frames_per_second())?;
+        check("subframes", subframes, SUBFRAMES_PER_FRAME)?;
+
+        if rate.is_drop_frame() && seconds == 0 && frames < 2 && !minutes.is_multiple_of(10) {
+            return Err(TimecodeError::DroppedFrame { minutes, frames });
+        }
+
+        Ok(Self { hours, minutes, seconds, frames, subframes, rate })
+    }
+
+    /// Midnight at the given rate
+    pub fn zero(rate: FrameRate) -> Self {
+        Self { hours: 0, minutes: 0, seconds: 0, frames: 0, subframes: 0, rate }
+    }
+
+    /// Build the label for a frame count since midnight, wrapping at 24 hours
+    pub fn from_frame_count(frame_count: u64, rate: FrameRate) -> Self {
+        let mut frame_count = frame_count % rate.frames_per_day();
+
+        if rate.is_drop_frame() {
+            // Add back the labels skipped so far: 2 per minute, except every tenth minute
+            let tens_of_minutes = frame_count / 17982;
+            let remainder = frame_count % 17982;
+            frame_count += 18 * tens_of_minutes;
+            if remainder > 1 {
+                frame_count += 2 * ((remainder - 2) / 1798);
+            }
+        }
+
+        let fps = rate.frames_per_second() as u64;
+        Self {
+            hours: (frame_count / (fps * 3600)) as u8,
+            minutes: (frame_count / (fps * 60) % 60) as u8,
+            seconds: (frame_count / fps % 60) as u8,
+            frames: (frame_count % fps) as u8,
+            subframes: 0,
+            rate,
+        }
+    }
+
+    /// Number of frames since midnight, ignoring subframes
+    pub fn frame_count(&self) -> u64 {
+        let fps = self.rate.frames_per_second() as u64;
+        let total_minutes = self.hours as u64 * 60 + self.minutes as u64;
+        let labels = (total_minutes * 60 + self.seconds as u64) * fps + self.frames as u64;
+
+        if self.rate.is_drop_frame() {
+            labels - 2 * (total_minutes - total_minutes / 10)
+        } else {
+            labels
+        }
+    }
+
+    /// Number of subframes since midnight
+    pub fn subframe_count(&self) -> u64 {
+        self.frame_count() * SUBFRAMES_PER_FRAME as u64 + self.subframes as u64
+    }
+
+    /// Convert a timeline position to timecode, given the timecode at position zero
+    pub fn from_position(position: &TimePosition, start: &Timecode, reference_sample_rate: u32) -> Self {
+        let rate = start.rate;
+        let (numerator, denominator) = rate.rate();
+
+        let elapsed = position.position_ticks as u128 * numerator as u128 * SUBFRAMES_PER_FRAME as u128
+            / (reference_sample_rate as u128 * denominator as u128);
+        let subframes_per_day = rate.frames_per_day() as u128 * SUBFRAMES_PER_FRAME as u128;
+        let total = (start.subframe_count() as u128 + elapsed) % subframes_per_day;
+
+        let mut timecode = Self::from_frame_count((total / SUBFRAMES_PER_FRAME as u128) as u64, rate);
+        timecode.subframes = (total % SUBFRAMES_PER_FRAME as u128) as u8;
+        timecode
+    }
+
+    /// Convert to a timeline position, given the timecode at position zero
+    ///
+    /// Timecode earlier than `start` is taken to be on the following day. The result is
+    /// the first tick inside this subframe, so converting back yields the same label.
+    pub fn to_position(&self, start: &Timecode, reference_sample_rate: u32) -> TimePosition {
+        // Work with exact fractions of a tick so differing rates and pull-downs stay exact
+        let (own_ticks, own_scale) = Self::ticks_fraction(self.subframe_count(), self.rate, reference_sample_rate);
+        let (start_ticks, start_scale) = Self::ticks_fraction(start.subframe_count(), start.rate, reference_sample_rate);
+        let (day_ticks, _) = Self::ticks_fraction(
+            self.rate.frames_per_day() * SUBFRAMES_PER_FRAME as u64,
+            self.rate,
+            reference_sample_rate,
+        );
+
+        let scale = own_scale * start_scale;
+        let elapsed = (own_ticks * start_scale - start_ticks * own_scale).rem_euclid(day_ticks * start_scale);
+
+        TimePosition::new(((elapsed + scale - 1) / scale) as u64)
+    }
+
+    /// Wall-clock ticks for a subframe count as an exact (numerator, denominator) pair
+    fn ticks_fraction(subframes: u64, rate: FrameRate, reference_sample_rate: u32) -> (i128, i128) {
+        let (numerator, denominator) = rate.rate();
+        (
+            subframes as i128 * reference_sample_rate as i128 * denominator as i128,
+            numerator as i128 * SUBFRAMES_PER_FRAME as i128,
+        )
+    }
+
+    /// Parse `HH:MM:SS:FF`, optionally with `;` before the frames and a `.sub` suffix
+    pub fn parse(s: &str, rate: FrameRate) -> Result<Self, TimecodeError> {
+        let s = s.trim();
+        let (label, subframes) = match s.split_once('.') {
+            Some((label, subframes)) => (label, Some(subframes)),
+            None => (s, None),
+        };
+
+        let fields: Vec<&str> = label.split([':', ';']).collect();
+        if fields.len() != 4 {
+            return Err(TimecodeError::InvalidFormat(s.to_string()));
+        }
+
+        let parse = |field: &str| {
+            field.parse::<u8>().map_err(|_| TimecodeError::InvalidNumber(field.to_string()))
+        };
+
+        Self::with_subframes(
+            parse(fields[0])?,
+            parse(fields[1])?,
+            parse(fields[2])?,
+            parse(fields[3])?,
+            subframes.map(parse).transpose()?.unwrap_or(0),
+            rate,
+        )
+    }
+}
+
+impl fmt::Display for Timecode {
+    /// Formats as `HH:MM:SS:FF`, using `;` before the frames for drop-frame rates
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate.is_drop_frame() { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{}{:02}", self.hours, self.minutes, self.seconds, separator, self.frames)?;
+
+        if self.subframes != 0 {
+            write!(f, ".{:02}", self.subframes)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use loom::tapestry::{BbtError, BbtPosition, TempoMap, TimeSignature, DEFAULT_PPQ};
+
+const RATE: u32 = 44100;
+
+/// Two bars of 4/4, two of 7/8, then 3/4
+fn mixed_meter_map() -> TempoMap {
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_time_signature_change(map.bars_and_beats_to_position(2, 0.0), TimeSignature::new(7, 8).unwrap());
+    map.add_time_signature_change(map.bars_and_beats_to_position(4, 0.0), TimeSignature::new(3, 4).unwrap());
+    map
+}
+
+#[test]
+fn parses_and_formats_bar_beat_tick() {
+    for text in ["1.1.0", "12.3.240", "999.7.959"] {
+        let bbt: BbtPosition = text.parse().unwrap();
+        assert_eq!(bbt.to_string(), text);
+    }
+
+    assert_eq!("12.3.240".parse(), Ok(BbtPosition::new(12, 3, 240)));
+    // Omitted fields are at their start
+    assert_eq!("5".parse(), Ok(BbtPosition::new(5, 1, 0)));
+    assert_eq!(" 5.2 ".parse(), Ok(BbtPosition::new(5, 2, 0)));
+
+    assert_eq!("0.1.0".parse::<BbtPosition>(), Err(BbtError::ZeroIndex));
+    assert_eq!("1.0".parse::<BbtPosition>(), Err(BbtError::ZeroIndex));
+    assert_eq!("1.2.3.4".parse::<BbtPosition>(), Err(BbtError::InvalidFormat("1.2.3.4".to_string())));
+    assert_eq!("1.x".parse::<BbtPosition>(), Err(BbtError::InvalidNumber("x".to_string())));
+}
+
+#[test]
+fn positions_round_trip_across_meter_changes() {
+    let map = mixed_meter_map();
+    let beats_in_bar = |bar: u32| match bar {
+        1..=2 => 4,
+        3..=4 => 7,
+        _ => 3,
+    };
+    // Beats are eighths in 7/8, so half as many ticks
+    let ticks_per_beat = |bar: u32| if (3..=4).contains(&bar) { DEFAULT_PPQ / 2 } else { DEFAULT_PPQ };
+
+    let mut previous = None;
+    for bar in 1..=6 {
+        for beat in 1..=beats_in_bar(bar) {
+            for tick in [0, 1, ticks_per_beat(bar) / 2, ticks_per_beat(bar) - 1] {
+                let bbt = BbtPosition::new(bar, beat, tick);
+                let position = bbt.to_position(&map, DEFAULT_PPQ).unwrap();
+                assert_eq!(BbtPosition::from_position(&position, &map, DEFAULT_PPQ), bbt);
+                assert_eq!(bbt.to_string().parse(), Ok(bbt));
+
+                assert!(previous.is_none_or(|previous| position > previous), "{bbt} out of order");
+                previous = Some(position);
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_beats_and_ticks_the_bar_does_not_have() {
+    let map = mixed_meter_map();
+
+    assert_eq!(
+        BbtPosition::new(3, 8, 0).to_position(&map, DEFAULT_PPQ),
+        Err(BbtError::BeatOutOfRange { beat: 8, beats_in_bar: 7 }),
+    );
+    assert_eq!(
+        BbtPosition::new(3, 1, 480).to_position(&map, DEFAULT_PPQ),
+        Err(BbtError::TickOutOfRange { tick: 480, ticks_per_beat: 480 }),
+    );
+    assert!(BbtPosition::new(2, 1, 480).to_position(&map, DEFAULT_PPQ).is_ok());
+    assert_eq!(BbtPosition::new(1, 0, 0).to_position(&map, DEFAULT_PPQ), Err(BbtError::ZeroIndex));
+}
+
+#[test]
+fn round_trips_where_the_ppq_does_not_divide_the_beat_unit() {
+    // A 64th at PPQ 100 is 6.25 ticks, so each beat holds ticks 0 to 6
+    let ppq = 100;
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_time_signature_change(map.bars_and_beats_to_position(1, 0.0), TimeSignature::new(5, 64).unwrap());
+
+    let mut previous = None;
+    for bar in 2..=4 {
+        for beat in 1..=5 {
+            for tick in 0..=6 {
+                let bbt = BbtPosition::new(bar, beat, tick);
+                let position = bbt.to_position(&map, ppq).unwrap();
+                assert_eq!(BbtPosition::from_position(&position, &map, ppq), bbt);
+                assert!(previous.is_none_or(|previous| position > previous), "{bbt} out of order");
+                previous = Some(position);
+            }
+        }
+    }
+
+    // Beats start on their exact boundary: the fifth is a quarter of a beat in
+    let fifth = BbtPosition::new(2, 5, 0).to_position(&map, ppq).unwrap();
+    assert_eq!(fifth, map.bars_and_beats_to_position(1, 0.25));
+    assert_eq!(
+        BbtPosition::new(2, 1, 7).to_position(&map, ppq),
+        Err(BbtError::TickOutOfRange { tick: 7, ticks_per_beat: 7 }),
+    );
+    assert!(BbtPosition::new(2, 1, 0).to_position(&map, u32::MAX).is_ok());
+}
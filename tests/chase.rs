@@ -0,0 +1,239 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use loom::controller::event::create_event_channel;
+use loom::engine::{chase, ClockSource, ClockSourceType, ManualClock, PlaybackEngine};
+use loom::model::{EndpointConfig, EndpointId, EndpointType, MediaContainer, MediaContent, MidiClip, MidiControl, MidiNote};
+use loom::model::{Project, Track, TrackType};
+use loom::output::{OutputEndpoint, OutputEvent, OutputEventType, OutputSystem};
+use loom::tapestry::{NoteValue, TimePosition, DEFAULT_PPQ};
+
+const PPQ: u64 = DEFAULT_PPQ as u64;
+/// Ticks per beat at the default 120 BPM
+const BEAT: u64 = 22050;
+
+fn beats(beats: f64) -> TimePosition {
+    TimePosition::new((beats * BEAT as f64) as u64)
+}
+
+/// A project with one MIDI track playing `clip` in a bar-long container at the start
+fn project_with(clip: MidiClip, track: impl FnOnce(Track) -> Track, looped: bool) -> (Project, EndpointId) {
+    let mut project = Project::new("Chase".to_string());
+    let output_id = project.add_endpoint(EndpointConfig::new_midi("Synth".to_string(), "0:Synth".to_string()));
+    let clip_id = project.add_midi_clip(clip);
+
+    let mut container = MediaContainer::new(TimePosition::zero(), MediaContent::MidiClip(clip_id), &project.tempo_map)
+        .with_musical_length(NoteValue::WHOLE, &project.tempo_map);
+    if looped {
+        container = container.with_loop(None);
+    }
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(track(Track::new("Keys".to_string(), TrackType::Midi).with_output(output_id)));
+    timeline.add_container(track_id, container);
+    (project, output_id)
+}
+
+/// A MIDI endpoint that takes everything and sends it nowhere
+struct Sink;
+
+impl OutputEndpoint for Sink {
+    fn name(&self) -> &str {
+        "Sink"
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn connect(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {}
+
+    fn send_event(&mut self, _event: &OutputEvent) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn endpoint_type(&self) -> EndpointType {
+        EndpointType::Midi
+    }
+}
+
+/// A MIDI endpoint that keeps everything sent to it
+struct Recorder(Arc<Mutex<Vec<OutputEvent>>>);
+
+impl OutputEndpoint for Recorder {
+    fn name(&self) -> &str {
+        "Recorder"
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn connect(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {}
+
+    fn send_event(&mut self, event: &OutputEvent) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+
+    fn endpoint_type(&self) -> EndpointType {
+        EndpointType::Midi
+    }
+}
+
+fn note_ons(events: &[OutputEvent]) -> Vec<u8> {
+    events.iter()
+        .filter_map(|event| match event.event_type {
+            OutputEventType::MidiNoteOn { note, .. } => Some(note),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn chases_latest_program_and_controllers() {
+    let mut clip = MidiClip::new("Pads".to_string(), 4 * PPQ);
+    clip.add_control(MidiControl::controller(0, 0, 7, 100));
+    clip.add_control(MidiControl::program(0, 0, 5));
+    clip.add_control(MidiControl::controller(0, 0, 0, 1));
+    clip.add_control(MidiControl::controller(PPQ, 0, 7, 80));
+    clip.add_control(MidiControl::controller(2 * PPQ, 0, 64, 127));
+    // Still to come at the target
+    clip.add_control(MidiControl::controller(3 * PPQ, 0, 7, 20));
+    let (project, output_id) = project_with(clip, |track| track, false);
+    let track_id = project.active_timeline().unwrap().tracks[0].id;
+
+    let events = chase(&project, beats(2.5), true);
+
+    let target = Some(output_id);
+    let expected: Vec<_> = vec![
+        // Bank select ahead of the program change it applies to
+        OutputEvent::midi_cc(0, 0, 1, target),
+        OutputEvent::new(OutputEventType::MidiProgramChange { channel: 0, program: 5 }, target),
+        OutputEvent::midi_cc(0, 7, 80, target),
+        OutputEvent::midi_cc(0, 64, 127, target),
+    ];
+    assert_eq!(events, expected.into_iter().map(|event| event.with_source(track_id)).collect::<Vec<_>>());
+}
+
+#[test]
+fn retriggers_held_notes_only_where_asked() {
+    let mut clip = MidiClip::new("Chords".to_string(), 4 * PPQ);
+    clip.add_note(MidiNote::new(0, PPQ, 60, 90));
+    clip.add_note(MidiNote::new(PPQ, 2 * PPQ, 64, 100));
+    clip.add_note(MidiNote::new(PPQ, 2 * PPQ, 67, 100).with_channel(1));
+
+    let (project, _) = project_with(clip.clone(), |track| track.with_chase_notes(true), false);
+    assert_eq!(note_ons(&chase(&project, beats(1.5), true)), vec![64, 67]);
+    // Not while stopped
+    assert!(note_ons(&chase(&project, beats(1.5), false)).is_empty());
+    // Released by the target
+    assert!(note_ons(&chase(&project, beats(3.5), true)).is_empty());
+
+    let (project, _) = project_with(clip.clone(), |track| track, false);
+    assert!(note_ons(&chase(&project, beats(1.5), true)).is_empty());
+
+    let (mut project, _) = project_with(clip, |track| track.with_chase_notes(true), false);
+    project.active_timeline_mut().unwrap().tracks[0].is_muted = true;
+    assert!(chase(&project, beats(1.5), true).is_empty());
+}
+
+#[test]
+fn follows_looped_containers() {
+    let mut clip = MidiClip::new("Stab".to_string(), PPQ);
+    clip.add_note(MidiNote::new(0, PPQ / 2, 48, 100));
+    clip.add_control(MidiControl::controller(PPQ / 4, 0, 1, 10));
+    let (project, _) = project_with(clip, |track| track.with_chase_notes(true), true);
+
+    // Third pass: the note plays from beat 2 to 2.5
+    assert_eq!(note_ons(&chase(&project, beats(2.25), true)), vec![48]);
+    assert!(note_ons(&chase(&project, beats(2.75), true)).is_empty());
+    // Past the end of the container only the controller is left
+    assert_eq!(chase(&project, beats(6.25), true).len(), 1);
+    assert!(note_ons(&chase(&project, beats(6.25), true)).is_empty());
+}
+
+#[test]
+fn seeking_while_playing_keeps_the_chased_notes() {
+    let mut clip = MidiClip::new("Pad".to_string(), 4 * PPQ);
+    clip.add_note(MidiNote::new(0, 4 * PPQ, 48, 100));
+    let (project, output_id) = project_with(clip, |track| track.with_chase_notes(true), false);
+
+    let output = Arc::new(RwLock::new(OutputSystem::new()));
+    output.write().unwrap().insert_endpoint(output_id, Box::new(Sink));
+    let (event_sender, _events) = create_event_channel();
+    let mut engine = PlaybackEngine::new(Arc::new(RwLock::new(project)), event_sender, Arc::clone(&output));
+    let sounding = || output.read().unwrap().active_notes(output_id).is_some_and(|notes| notes.is_sounding(0, 48, Instant::now()));
+
+    engine.play();
+    // Seek once the playback thread has rendered ahead
+    thread::sleep(Duration::from_millis(20));
+    engine.seek(beats(2.0));
+    // Let the playback thread make a few passes at the new position
+    thread::sleep(Duration::from_millis(50));
+    let held = sounding();
+    engine.stop();
+
+    assert!(held);
+    assert!(!sounding());
+}
+
+#[test]
+fn jumps_made_by_the_clock_are_chased() {
+    let mut clip = MidiClip::new("Pad".to_string(), 4 * PPQ);
+    clip.add_control(MidiControl::program(0, 0, 5));
+    clip.add_control(MidiControl::program(2 * PPQ, 0, 9));
+    clip.add_note(MidiNote::new(0, 4 * PPQ, 48, 100));
+    let (project, output_id) = project_with(clip, |track| track.with_chase_notes(true), false);
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let output = Arc::new(RwLock::new(OutputSystem::new()));
+    output.write().unwrap().insert_endpoint(output_id, Box::new(Recorder(Arc::clone(&sent))));
+    let (event_sender, _events) = create_event_channel();
+    let mut engine = PlaybackEngine::new(Arc::new(RwLock::new(project)), event_sender, output);
+
+    // A clock that only moves when the test moves it, standing in for a master
+    let clock = Arc::new(ManualClock::new(44100));
+    engine.register_clock_source(ClockSourceType::MidiClock, clock.clone());
+    engine.select_clock_source(ClockSourceType::MidiClock).unwrap();
+    engine.play();
+    thread::sleep(Duration::from_millis(20));
+
+    // Sent once the playback thread has made a few passes after the clock jumped to `to`
+    let jump = |to: f64| -> Vec<OutputEventType> {
+        sent.lock().unwrap().clear();
+        clock.locate(beats(to));
+        thread::sleep(Duration::from_millis(30));
+        sent.lock().unwrap().iter().map(|event| event.event_type.clone()).collect()
+    };
+    let programs = |events: &[OutputEventType]| -> Vec<u8> {
+        events.iter()
+            .filter_map(|event| match event {
+                OutputEventType::MidiProgramChange { program, .. } => Some(*program),
+                _ => None,
+            })
+            .collect()
+    };
+    let note_on = OutputEventType::MidiNoteOn { channel: 0, note: 48, velocity: 100 };
+    let note_off = OutputEventType::MidiNoteOff { channel: 0, note: 48 };
+
+    // Forwards past the second program change, then back before it
+    let forwards = jump(3.0);
+    assert_eq!(programs(&forwards), vec![9]);
+    assert_eq!(forwards.iter().filter(|event| **event == note_on).count(), 1);
+    assert!(forwards.iter().position(|event| *event == note_off) < forwards.iter().position(|event| *event == note_on));
+
+    let backwards = jump(0.5);
+    assert_eq!(programs(&backwards), vec![5]);
+    assert_eq!(backwards.iter().filter(|event| **event == note_on).count(), 1);
+    engine.stop();
+}
@@ -0,0 +1,81 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use loom::engine::{ClockSource, InternalClock};
+use loom::tapestry::TimePosition;
+
+const RATE: u32 = 44100;
+const WAIT: Duration = Duration::from_millis(30);
+
+fn secs(seconds: f64) -> TimePosition {
+    TimePosition::new((seconds * RATE as f64).round() as u64)
+}
+
+/// Check `clock` has moved on from `from` by at least `WAIT` and at most the time since `since`
+fn assert_ran(clock: &InternalClock, from: TimePosition, since: Instant) {
+    let position = clock.current_time().position_ticks;
+    let most = from.position_ticks + secs(since.elapsed().as_secs_f64()).position_ticks + 1;
+    let least = from.position_ticks + secs(WAIT.as_secs_f64()).position_ticks;
+    assert!((least..=most).contains(&position), "{position} ticks, expected {least} to {most}");
+}
+
+#[test]
+fn locating_while_paused_stays_paused_at_the_new_position() {
+    let clock = InternalClock::new(RATE);
+    clock.start();
+    sleep(WAIT);
+    clock.pause();
+
+    clock.locate(secs(5.0));
+    sleep(WAIT);
+    assert!(!clock.is_running());
+    assert_eq!(clock.current_time(), secs(5.0));
+}
+
+#[test]
+fn resuming_continues_from_where_the_clock_paused() {
+    let clock = InternalClock::new(RATE);
+    let started = Instant::now();
+    clock.start();
+    sleep(WAIT);
+    clock.pause();
+    let paused_at = clock.current_time();
+    assert_ran(&clock, TimePosition::zero(), started);
+
+    // Time spent paused does not count
+    sleep(WAIT);
+    assert_eq!(clock.current_time(), paused_at);
+
+    let resumed = Instant::now();
+    clock.start();
+    assert!(clock.is_running());
+    sleep(WAIT);
+    assert_ran(&clock, paused_at, resumed);
+
+    // Starting again while running changes nothing
+    let position = clock.current_time();
+    clock.start();
+    assert!(clock.current_time() >= position);
+}
+
+#[test]
+fn start_after_locate_begins_at_the_located_position() {
+    let clock = InternalClock::new(RATE);
+    clock.locate(secs(10.0));
+    assert_eq!(clock.current_time(), secs(10.0));
+
+    let started = Instant::now();
+    clock.start();
+    sleep(WAIT);
+    assert_ran(&clock, secs(10.0), started);
+
+    // Locating while running carries on from the new position
+    let located = Instant::now();
+    clock.locate(secs(2.0));
+    assert!(clock.is_running());
+    sleep(WAIT);
+    assert_ran(&clock, secs(2.0), located);
+
+    clock.stop();
+    assert!(!clock.is_running());
+    assert_eq!(clock.current_time(), TimePosition::zero());
+}
@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use loom::controller::event::{create_event_channel, Event, EventReceiver};
+use loom::engine::{ClockManager, ClockSource, ClockSourceType, LockState};
+use loom::tapestry::TimePosition;
+
+const RATE: u64 = 44100;
+
+/// An external master whose position, lock and transport are set by the test
+struct Master {
+    position: Mutex<TimePosition>,
+    lock: Mutex<LockState>,
+    running: AtomicBool,
+}
+
+impl Master {
+    fn new(position: TimePosition, lock: LockState, running: bool) -> Arc<Self> {
+        Arc::new(Self {
+            position: Mutex::new(position),
+            lock: Mutex::new(lock),
+            running: AtomicBool::new(running),
+        })
+    }
+
+    fn set(&self, position: TimePosition, lock: LockState) {
+        *self.position.lock().unwrap() = position;
+        *self.lock.lock().unwrap() = lock;
+    }
+}
+
+impl ClockSource for Master {
+    fn current_time(&self) -> TimePosition {
+        *self.position.lock().unwrap()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        RATE as u32
+    }
+
+    fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    fn follows_master(&self) -> bool {
+        true
+    }
+
+    fn lock_state(&self) -> LockState {
+        *self.lock.lock().unwrap()
+    }
+}
+
+/// A manager gliding out differences so slowly they stay put for the test
+fn manager() -> (ClockManager, EventReceiver) {
+    let (event_sender, events) = create_event_channel();
+    (ClockManager::new(RATE as u32, event_sender).with_glide(Duration::from_secs(3600)), events)
+}
+
+/// Clock events sent so far
+fn clock_events(events: &EventReceiver) -> Vec<String> {
+    std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event {
+            Event::ClockLocked { source_type } => Some(format!("locked {source_type:?}")),
+            Event::ClockUnlocked { source_type } => Some(format!("unlocked {source_type:?}")),
+            Event::ClockSourceChanged { source_type } => Some(format!("changed to {source_type:?}")),
+            _ => None,
+        })
+        .collect()
+}
+
+fn secs(seconds: f64) -> TimePosition {
+    TimePosition::new((seconds * RATE as f64) as u64)
+}
+
+#[test]
+fn fails_over_to_internal_and_back_on_relock() {
+    let (manager, events) = manager();
+    let master = Master::new(secs(10.0), LockState::Locked, true);
+    manager.register(ClockSourceType::Mtc, master.clone());
+
+    manager.select(ClockSourceType::Mtc).unwrap();
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Mtc);
+    assert_eq!(manager.current_time(), secs(10.0));
+    assert_eq!(clock_events(&events), vec!["changed to Mtc", "locked Mtc"]);
+
+    // The feed drops: the internal clock carries on from the same place, running
+    master.set(secs(10.0), LockState::Unlocked);
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Internal);
+    assert_eq!(manager.selected(), ClockSourceType::Mtc);
+    assert!(manager.is_running());
+    let position = manager.current_time();
+    assert!(position >= secs(10.0) && position < secs(10.5), "{position:?}");
+    assert_eq!(clock_events(&events), vec!["unlocked Mtc", "changed to Internal"]);
+
+    // Back on the selected source once it locks again
+    master.set(secs(30.0), LockState::Locked);
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Mtc);
+    assert_eq!(manager.current_time(), secs(30.0));
+    assert_eq!(clock_events(&events), vec!["locked Mtc", "changed to Mtc"]);
+}
+
+#[test]
+fn selecting_an_unlocked_source_waits_for_lock() {
+    let (manager, events) = manager();
+    let master = Master::new(secs(5.0), LockState::Locking, false);
+    manager.register(ClockSourceType::Ltc, master.clone());
+
+    manager.select(ClockSourceType::Ltc).unwrap();
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Internal);
+    assert!(clock_events(&events).is_empty());
+
+    master.set(secs(5.0), LockState::Locked);
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Ltc);
+    assert_eq!(clock_events(&events), vec!["locked Ltc", "changed to Ltc"]);
+
+    assert!(manager.select(ClockSourceType::MidiClock).is_err());
+}
+
+#[test]
+fn small_differences_glide_and_large_ones_relocate() {
+    // Stopped, so the internal clock holds exactly where it was put
+    let (manager, _events) = manager();
+    let master = Master::new(secs(0.0), LockState::Unlocked, false);
+    manager.register(ClockSourceType::Mtc, master.clone());
+    manager.select(ClockSourceType::Mtc).unwrap();
+    manager.locate(secs(10.0));
+
+    // Just inside the glide limit: the position stays where it was and glides over
+    master.set(secs(10.09), LockState::Locked);
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Mtc);
+    let glided = manager.current_time();
+    assert!(glided.position_ticks.abs_diff(secs(10.0).position_ticks) <= 1, "{glided:?}");
+
+    // Just outside it: the transport jumps to the master
+    master.set(secs(10.0), LockState::Unlocked);
+    manager.poll();
+    assert_eq!(manager.active(), ClockSourceType::Internal);
+    manager.locate(secs(10.0));
+    master.set(secs(10.11), LockState::Locked);
+    manager.poll();
+    assert_eq!(manager.current_time(), secs(10.11));
+}
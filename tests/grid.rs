@@ -0,0 +1,90 @@
+use loom::tapestry::{BbtPosition, Grid, GridLevel, NoteValue, Tempo, TempoCurve, TempoMap, TimePosition, TimeSignature, DEFAULT_PPQ};
+
+const RATE: u32 = 44100;
+/// Ticks per quarter note at 60 BPM
+const QUARTER: u64 = RATE as u64;
+
+/// Lines as (ticks, level, bar.beat.tick)
+fn lines(grid: Grid) -> Vec<(u64, GridLevel, String)> {
+    grid.map(|line| (line.position.position_ticks, line.level, line.bbt.to_string())).collect()
+}
+
+#[test]
+fn triplets_follow_a_meter_change() {
+    // A bar of 4/4 at 60 BPM, then 3/8
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_tempo_change(TimePosition::zero(), Tempo::new(60.0));
+    map.add_time_signature_change(TimePosition::new(4 * QUARTER), TimeSignature::new(3, 8).unwrap());
+
+    // From the last beat of the 4/4 bar up to the third bar line
+    let start = TimePosition::new(3 * QUARTER);
+    let end = TimePosition::new(11 * QUARTER / 2);
+    let grid = Grid::new(&map, start, end, DEFAULT_PPQ).with_subdivision(NoteValue::TRIPLET_EIGHTH);
+
+    use GridLevel::*;
+    let third = QUARTER / 3;
+    assert_eq!(lines(grid), vec![
+        (3 * QUARTER, Beat, "1.4.0".to_string()),
+        (3 * QUARTER + third, Subdivision, "1.4.320".to_string()),
+        (3 * QUARTER + 2 * third, Subdivision, "1.4.640".to_string()),
+        // Eighth-note beats of 480 ticks, with the triplets in between
+        (4 * QUARTER, Bar, "2.1.0".to_string()),
+        (4 * QUARTER + third, Subdivision, "2.1.320".to_string()),
+        (4 * QUARTER + QUARTER / 2, Beat, "2.2.0".to_string()),
+        (4 * QUARTER + 2 * third, Subdivision, "2.2.160".to_string()),
+        (5 * QUARTER, Beat, "2.3.0".to_string()),
+        (5 * QUARTER + third, Subdivision, "2.3.320".to_string()),
+    ]);
+
+    // The end is exclusive, the start inclusive
+    let grid = Grid::new(&map, end, TimePosition::new(end.position_ticks + 1), DEFAULT_PPQ);
+    assert_eq!(lines(grid), vec![(end.position_ticks, Bar, "3.1.0".to_string())]);
+    let grid = Grid::new(&map, TimePosition::new(start.position_ticks + 1), end, DEFAULT_PPQ);
+    assert_eq!(lines(grid).first().map(|line| line.0), Some(4 * QUARTER));
+}
+
+#[test]
+fn dotted_eighths_follow_a_tempo_ramp() {
+    // 60 BPM rising linearly to 180 BPM over ten seconds: twenty beats, five bars of 4/4
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_tempo_ramp(TimePosition::zero(), Tempo::new(60.0), TempoCurve::Linear);
+    map.add_tempo_change(TimePosition::new(10 * RATE as u64), Tempo::new(180.0));
+
+    // One second in is 1.1 beats, so the first line is half way through beat 2
+    let start = TimePosition::new(RATE as u64);
+    let end = TimePosition::new(10 * RATE as u64);
+    let grid: Vec<_> = Grid::new(&map, start, end, DEFAULT_PPQ).with_subdivision(NoteValue::DOTTED_EIGHTH).collect();
+
+    // Beats and every dotted eighth from the bar line: 0, 0.75, 1, 1.5, 2, 2.25, 3 and 3.75
+    let in_bar = [0.0, 0.75, 1.0, 1.5, 2.0, 2.25, 3.0, 3.75];
+    let expected: Vec<f64> = (0..5)
+        .flat_map(|bar| in_bar.iter().map(move |beat| bar as f64 * 4.0 + beat))
+        .filter(|beats| *beats >= 1.1)
+        .collect();
+    assert_eq!(grid.len(), expected.len());
+    assert_eq!(grid.first().unwrap().bbt, BbtPosition::new(1, 2, 480));
+    assert_eq!(grid.last().unwrap().bbt, BbtPosition::new(5, 4, 720));
+
+    for (line, beats) in grid.iter().zip(&expected) {
+        assert_eq!(line.position, map.beats_to_position(*beats), "{}", line.bbt);
+        // Beats b into the ramp fall at t seconds where b = t + t² / 10
+        let seconds = 5.0 * ((1.0 + 0.4 * beats).sqrt() - 1.0);
+        let ticks = seconds * RATE as f64;
+        assert!((line.position.position_ticks as f64 - ticks).abs() <= 1.0, "{} at {ticks}", line.bbt);
+
+        let level = match beats.fract() {
+            0.0 if beats % 4.0 == 0.0 => GridLevel::Bar,
+            0.0 => GridLevel::Beat,
+            _ => GridLevel::Subdivision,
+        };
+        assert_eq!(line.level, level, "{}", line.bbt);
+    }
+
+    // Lines crowd together as the tempo rises
+    let beats: Vec<_> = grid.iter().filter(|line| line.level != GridLevel::Subdivision).collect();
+    for pair in beats.windows(3) {
+        let first = pair[1].position.position_ticks - pair[0].position.position_ticks;
+        let second = pair[2].position.position_ticks - pair[1].position.position_ticks;
+        assert!(second < first, "{} to {}", pair[1].bbt, pair[2].bbt);
+    }
+}
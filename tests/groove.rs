@@ -0,0 +1,97 @@
+use loom::model::{GrooveTemplate, MidiClip, MidiNote, Project};
+
+/// Two beats of sixteenths with every second one late by a third of a sixteenth and softer
+fn swung_clip() -> MidiClip {
+    let mut clip = MidiClip::new("Swung".to_string(), 2 * 960);
+    for step in 0..8 {
+        let (late, velocity) = if step % 2 == 1 { (80, 60) } else { (0, 100) };
+        clip.add_note(MidiNote::new(step * 240 + late, 120, 36, velocity));
+    }
+    clip
+}
+
+/// Two beats of straight sixteenths at PPQ 480
+fn straight_clip() -> MidiClip {
+    let mut clip = MidiClip::new("Straight".to_string(), 2 * 480).with_ppq(480);
+    for step in 0..8 {
+        clip.add_note(MidiNote::new(step * 120, 60, 38, 80));
+    }
+    clip
+}
+
+fn starts(clip: &MidiClip) -> Vec<u64> {
+    clip.notes.iter().map(|note| note.start).collect()
+}
+
+fn velocities(clip: &MidiClip) -> Vec<u8> {
+    clip.notes.iter().map(|note| note.velocity).collect()
+}
+
+#[test]
+fn extracts_timing_and_velocity_from_a_swung_clip() {
+    let groove = GrooveTemplate::extract("Swing".to_string(), &swung_clip(), 1, 4);
+
+    assert_eq!(groove.steps.len(), 4);
+    let timing: Vec<f64> = groove.steps.iter().map(|step| step.timing_offset).collect();
+    assert_eq!(timing, vec![0.0, 1.0 / 3.0, 0.0, 1.0 / 3.0]);
+    // Relative to the clip's average velocity of 80
+    let velocity: Vec<f64> = groove.steps.iter().map(|step| step.velocity_scale).collect();
+    assert_eq!(velocity, vec![1.25, 0.75, 1.25, 0.75]);
+    assert!(groove.steps.iter().all(|step| step.length_scale == 1.0));
+}
+
+#[test]
+fn applies_a_groove_to_a_straight_clip_at_another_ppq() {
+    let groove = GrooveTemplate::extract("Swing".to_string(), &swung_clip(), 1, 4);
+
+    let mut clip = straight_clip();
+    groove.apply(&mut clip, 100.0);
+    assert_eq!(starts(&clip), vec![0, 160, 240, 400, 480, 640, 720, 880]);
+    assert_eq!(velocities(&clip), vec![100, 60, 100, 60, 100, 60, 100, 60]);
+    assert!(clip.notes.iter().all(|note| note.length == 60));
+
+    // Half way there
+    let mut clip = straight_clip();
+    groove.apply(&mut clip, 50.0);
+    assert_eq!(starts(&clip), vec![0, 140, 240, 380, 480, 620, 720, 860]);
+    assert_eq!(velocities(&clip), vec![90, 70, 90, 70, 90, 70, 90, 70]);
+
+    let mut clip = straight_clip();
+    groove.apply(&mut clip, 0.0);
+    assert_eq!(starts(&clip), starts(&straight_clip()));
+    assert_eq!(velocities(&clip), velocities(&straight_clip()));
+}
+
+#[test]
+fn notes_off_the_grid_are_left_alone() {
+    let groove = GrooveTemplate::extract("Swing".to_string(), &swung_clip(), 1, 4);
+    let mut clip = MidiClip::new("Mixed".to_string(), 480).with_ppq(480);
+    // A straight sixteenth, a note between sixteenths and a triplet eighth
+    clip.add_note(MidiNote::new(120, 60, 38, 80));
+    clip.add_note(MidiNote::new(55, 60, 42, 80));
+    clip.add_note(MidiNote::new(320, 60, 42, 80));
+
+    groove.apply(&mut clip, 100.0);
+    let notes: Vec<(u64, u8, u8)> = clip.notes.iter().map(|note| (note.start, note.pitch, note.velocity)).collect();
+    assert_eq!(notes, vec![(55, 42, 80), (160, 38, 60), (320, 42, 80)]);
+}
+
+#[test]
+fn project_grooves_are_shared_between_clips() {
+    let mut project = Project::new("Groove".to_string());
+    let source = project.add_midi_clip(swung_clip());
+    let first = project.add_midi_clip(straight_clip());
+    let second = project.add_midi_clip(straight_clip());
+
+    let groove_id = project.extract_groove(source, "Swing".to_string(), 1, 4).unwrap();
+    assert_eq!(project.grooves[&groove_id].name, "Swing");
+    project.apply_groove(groove_id, first, 100.0).unwrap();
+    project.apply_groove(groove_id, second, 100.0).unwrap();
+    assert_eq!(starts(&project.midi_clips[&first]), starts(&project.midi_clips[&second]));
+    assert_eq!(starts(&project.midi_clips[&first])[1], 160);
+
+    let missing = GrooveTemplate::new("Missing".to_string(), 1, 4).id;
+    assert_eq!(project.apply_groove(missing, first, 100.0), Err("Groove not found"));
+    let missing = MidiClip::new("Missing".to_string(), 0).id;
+    assert_eq!(project.extract_groove(missing, "None".to_string(), 1, 4), Err("MIDI clip not found"));
+}
@@ -0,0 +1,229 @@
+use std::time::{Duration, Instant};
+use loom::engine::{LockState, LtcClock, LtcDecoder};
+use loom::tapestry::{FrameRate, TimePosition, Timecode};
+
+const INPUT_RATE: u32 = 48000;
+const USER_BITS: u32 = 0x8765_4321;
+
+/// The 80 bits of an LTC frame, bit 0 first
+fn frame_bits(timecode: &Timecode, user_bits: u32) -> [bool; 80] {
+    let mut bits = [false; 80];
+    let mut set = |first: usize, width: usize, value: u32| {
+        for i in 0..width {
+            bits[first + i] = value >> i & 1 == 1;
+        }
+    };
+
+    set(0, 4, timecode.frames as u32 % 10);
+    set(8, 2, timecode.frames as u32 / 10);
+    set(10, 1, timecode.rate.is_drop_frame() as u32);
+    set(16, 4, timecode.seconds as u32 % 10);
+    set(24, 3, timecode.seconds as u32 / 10);
+    set(32, 4, timecode.minutes as u32 % 10);
+    set(40, 3, timecode.minutes as u32 / 10);
+    set(48, 4, timecode.hours as u32 % 10);
+    set(56, 2, timecode.hours as u32 / 10);
+    for group in 0..8 {
+        set(4 + group * 8, 4, user_bits >> (group * 4) & 0x0F);
+    }
+    // Sync word
+    set(64, 16, 0b1011_1111_1111_1100);
+
+    bits
+}
+
+/// Biphase-mark encode `frames` frames of LTC from `start`, as an LTC generator would
+///
+/// The signal holds a steady level for a bit's length at each end, so that the first
+/// and last bits are bounded by transitions whichever way it is read.
+fn encode(start: Timecode, frames: u64, level: f32) -> Vec<f32> {
+    let bit_length = INPUT_RATE as f64 / (80.0 * start.rate.fps());
+    let mut samples = Vec::new();
+    let mut level = -level;
+    let mut time = bit_length;
+
+    let hold = |samples: &mut Vec<f32>, level: f32, until: f64| {
+        while (samples.len() as f64) < until.round() {
+            samples.push(level);
+        }
+    };
+    hold(&mut samples, level, time);
+
+    for frame in 0..frames {
+        let timecode = Timecode::from_frame_count(start.frame_count() + frame, start.rate);
+        for bit in frame_bits(&timecode, USER_BITS) {
+            level = -level;
+            if bit {
+                hold(&mut samples, level, time + bit_length / 2.0);
+                level = -level;
+            }
+            time += bit_length;
+            hold(&mut samples, level, time);
+        }
+    }
+    hold(&mut samples, -level, time + bit_length);
+
+    samples
+}
+
+fn timecode(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Timecode {
+    Timecode::new(hours, minutes, seconds, frames, rate).unwrap()
+}
+
+/// Feed a signal to a clock in small buffers as an audio input would, returning when
+/// the last buffer arrived
+fn feed(clock: &LtcClock, base: Instant, samples: &[f32]) -> Instant {
+    let mut at = base;
+    for (index, buffer) in samples.chunks(256).enumerate() {
+        at = base + Duration::from_secs_f64((index * 256) as f64 / INPUT_RATE as f64);
+        clock.receive(buffer, 1, at);
+    }
+    at
+}
+
+#[test]
+fn decodes_forward_frames() {
+    let start = timecode(10, 0, 0, 0, FrameRate::Fps25);
+    let mut decoder = LtcDecoder::new(INPUT_RATE);
+
+    let frames = decoder.decode(&encode(start, 50, 0.5));
+
+    assert_eq!(frames.len(), 50);
+    for (index, frame) in frames.iter().enumerate() {
+        assert!(!frame.reverse);
+        assert_eq!(frame.user_bits, USER_BITS);
+        assert_eq!(frame.timecode.frame_count(), start.frame_count() + index as u64);
+    }
+    assert_eq!(frames.last().unwrap().timecode, timecode(10, 0, 1, 24, FrameRate::Fps25));
+    assert_eq!(decoder.frame_rate(), Some(FrameRate::Fps25));
+}
+
+#[test]
+fn decodes_quiet_inverted_signal_in_small_buffers() {
+    let start = timecode(1, 2, 3, 4, FrameRate::Fps24);
+    let mut decoder = LtcDecoder::new(INPUT_RATE);
+
+    let signal = encode(start, 10, -0.05);
+    let frames: Vec<_> = signal.chunks(100).flat_map(|buffer| decoder.decode(buffer)).collect();
+
+    assert_eq!(frames.len(), 10);
+    assert_eq!(frames[0].timecode, start);
+}
+
+#[test]
+fn reads_reverse_playback() {
+    let start = timecode(0, 10, 0, 0, FrameRate::Fps30);
+    let mut decoder = LtcDecoder::new(INPUT_RATE);
+
+    let mut signal = encode(start, 30, 0.5);
+    signal.reverse();
+    let frames = decoder.decode(&signal);
+
+    assert_eq!(frames.len(), 30);
+    for (index, frame) in frames.iter().enumerate() {
+        assert!(frame.reverse);
+        assert_eq!(frame.timecode.frame_count(), start.frame_count() + 29 - index as u64);
+    }
+}
+
+#[test]
+fn detects_frame_rates() {
+    let rates = [
+        FrameRate::Fps23_976,
+        FrameRate::Fps24,
+        FrameRate::Fps25,
+        FrameRate::Fps29_97NonDrop,
+        FrameRate::Fps29_97Drop,
+        FrameRate::Fps30,
+    ];
+
+    for rate in rates {
+        let mut decoder = LtcDecoder::new(INPUT_RATE);
+        decoder.decode(&encode(Timecode::zero(rate), 60, 0.5));
+        assert_eq!(decoder.frame_rate(), Some(rate), "decoding {}", rate);
+    }
+}
+
+#[test]
+fn follows_drop_frame_labels_across_a_minute() {
+    let start = timecode(0, 0, 59, 28, FrameRate::Fps29_97Drop);
+    let mut decoder = LtcDecoder::new(INPUT_RATE);
+
+    let frames = decoder.decode(&encode(start, 4, 0.5));
+
+    let labels: Vec<_> = frames.iter().map(|frame| frame.timecode.to_string()).collect();
+    assert_eq!(labels, ["00:00:59;28", "00:00:59;29", "00:01:00;02", "00:01:00;03"]);
+}
+
+#[test]
+fn clock_locks_and_tracks_forward_timecode() {
+    let start = timecode(1, 0, 0, 0, FrameRate::Fps25);
+    let clock = LtcClock::new(INPUT_RATE, INPUT_RATE, start);
+    let base = Instant::now();
+
+    // Two seconds of timecode starting at 01:00:00:00
+    let signal = encode(start, 50, 0.5);
+    let last = feed(&clock, base, &signal);
+
+    assert_eq!(clock.frame_rate(), Some(FrameRate::Fps25));
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+
+    // The timeline position follows the signal sample for sample
+    let now = base + Duration::from_secs_f64(2.0);
+    let position = clock.position_at(now).position_ticks;
+    assert!(position.abs_diff(2 * INPUT_RATE as u64) <= 24, "position {}", position);
+}
+
+#[test]
+fn clock_runs_backwards_with_reverse_timecode() {
+    let start = timecode(1, 0, 0, 0, FrameRate::Fps25);
+    let clock = LtcClock::new(INPUT_RATE, INPUT_RATE, start);
+    let base = Instant::now();
+
+    // 01:00:02:24 back to 01:00:01:00
+    let mut signal = encode(timecode(1, 0, 1, 0, FrameRate::Fps25), 50, 0.5);
+    signal.reverse();
+    let last = feed(&clock, base, &signal);
+
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+    let earlier = clock.position_at(last);
+    let later = clock.position_at(last + Duration::from_millis(10));
+    assert!(later < earlier);
+    assert!(earlier.position_ticks.abs_diff(INPUT_RATE as u64) <= INPUT_RATE as u64 / 25);
+}
+
+#[test]
+fn clock_freewheels_then_unlocks_when_timecode_stops() {
+    let start = timecode(1, 0, 0, 0, FrameRate::Fps25);
+    let clock = LtcClock::new(INPUT_RATE, INPUT_RATE, start).with_freewheel(Duration::from_millis(200));
+    let base = Instant::now();
+
+    let last = feed(&clock, base, &encode(start, 25, 0.5));
+    assert!(clock.position_at(last) > TimePosition::zero());
+
+    let during = last + Duration::from_millis(150);
+    assert_eq!(clock.lock_state_at(during), LockState::Freewheeling);
+    assert!(clock.position_at(during) > clock.position_at(last));
+
+    let after = last + Duration::from_secs(2);
+    assert_eq!(clock.lock_state_at(after), LockState::Unlocked);
+    assert_eq!(clock.position_at(after), clock.position_at(after + Duration::from_secs(1)));
+}
+
+#[test]
+fn clock_reads_its_channel_of_interleaved_buffers() {
+    let start = timecode(1, 0, 0, 0, FrameRate::Fps25);
+    let clock = LtcClock::new(INPUT_RATE, INPUT_RATE, start).with_channel(1);
+    let now = Instant::now();
+
+    // Programme audio on the left, timecode on the right
+    let signal = encode(start, 10, 0.5);
+    let interleaved: Vec<f32> = signal.iter()
+        .enumerate()
+        .flat_map(|(index, &sample)| [(index as f32 * 0.05).sin(), sample])
+        .collect();
+    clock.receive(&interleaved, 2, now);
+
+    assert_eq!(clock.frame_rate(), Some(FrameRate::Fps25));
+    assert_eq!(clock.lock_state_at(now), LockState::Locked);
+}
@@ -0,0 +1,143 @@
+use std::sync::{Arc, RwLock};
+use loom::controller::command::{create_command_channel, Command};
+use loom::controller::event::{create_event_channel, Event, EventHub};
+use loom::controller::Controller;
+use loom::engine::PlaybackEngine;
+use loom::model::{Color, MarkerId, Project};
+use loom::output::OutputSystem;
+use loom::tapestry::TimePosition;
+
+fn at(ticks: u64) -> TimePosition {
+    TimePosition::new(ticks)
+}
+
+/// A project and its playback engine, edited through a controller
+struct Session {
+    project: Arc<RwLock<Project>>,
+    engine: Arc<RwLock<PlaybackEngine>>,
+    output: Arc<RwLock<OutputSystem>>,
+}
+
+impl Session {
+    fn new() -> Self {
+        let project = Arc::new(RwLock::new(Project::new("Markers".to_string())));
+        let output = Arc::new(RwLock::new(OutputSystem::new()));
+        let (event_sender, _) = create_event_channel();
+        let engine = PlaybackEngine::new(Arc::clone(&project), event_sender, Arc::clone(&output));
+        Self { project, engine: Arc::new(RwLock::new(engine)), output }
+    }
+
+    /// Run `commands` through a controller, returning the events it sends
+    fn run(&self, commands: Vec<Command>) -> Vec<Event> {
+        let (command_sender, command_receiver) = create_command_channel();
+        let (event_sender, events) = create_event_channel();
+        let mut event_hub = EventHub::new();
+        event_hub.add_receiver(event_sender);
+        let mut controller = Controller::new(
+            command_receiver,
+            event_hub,
+            Arc::clone(&self.project),
+            Arc::clone(&self.engine),
+            Arc::clone(&self.output),
+        );
+
+        for command in commands {
+            command_sender.send(command).unwrap();
+        }
+        drop(command_sender);
+        controller.run();
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    fn add_marker(&self, name: &str, position: TimePosition) -> MarkerId {
+        let events = self.run(vec![Command::AddMarker { name: name.to_string(), color: Color::new(0, 0, 255), position }]);
+        match events.as_slice() {
+            [Event::MarkerAdded { marker_id, position: added }] if *added == position => *marker_id,
+            _ => panic!("expected the marker to be added, got {events:?}"),
+        }
+    }
+
+    /// Marker names and positions in timeline order
+    fn markers(&self) -> Vec<(String, TimePosition)> {
+        let project = self.project.read().unwrap();
+        project.active_timeline().unwrap().markers_by_position().into_iter()
+            .map(|marker| (marker.name.clone(), marker.position))
+            .collect()
+    }
+
+    fn position(&self) -> TimePosition {
+        self.engine.read().unwrap().current_position()
+    }
+}
+
+#[test]
+fn markers_are_added_moved_and_removed() {
+    let session = Session::new();
+    let verse = session.add_marker("Verse", at(4000));
+    let chorus = session.add_marker("Chorus", at(8000));
+    assert_eq!(session.markers(), vec![("Verse".to_string(), at(4000)), ("Chorus".to_string(), at(8000))]);
+    let color = session.project.read().unwrap().active_timeline().unwrap().marker(verse).unwrap().color;
+    assert_eq!(color, Color::new(0, 0, 255));
+
+    // Moving the chorus ahead of the verse reorders them
+    let events = session.run(vec![Command::MoveMarker { marker_id: chorus, position: at(1000) }]);
+    assert!(matches!(events.as_slice(), [Event::MarkerMoved { marker_id, position }] if *marker_id == chorus && *position == at(1000)));
+    assert_eq!(session.markers(), vec![("Chorus".to_string(), at(1000)), ("Verse".to_string(), at(4000))]);
+
+    let events = session.run(vec![Command::RemoveMarker { marker_id: verse }]);
+    assert!(matches!(events.as_slice(), [Event::MarkerRemoved { marker_id }] if *marker_id == verse));
+    assert_eq!(session.markers(), vec![("Chorus".to_string(), at(1000))]);
+
+    // A marker that is gone can be neither moved nor removed
+    assert!(session.run(vec![
+        Command::MoveMarker { marker_id: verse, position: at(2000) },
+        Command::RemoveMarker { marker_id: verse },
+    ]).is_empty());
+    assert_eq!(session.markers(), vec![("Chorus".to_string(), at(1000))]);
+}
+
+#[test]
+fn locators_are_set_replaced_and_removed() {
+    let session = Session::new();
+    let locators = || session.project.read().unwrap().active_timeline().unwrap().locators.clone().into_iter().collect::<Vec<_>>();
+
+    let events = session.run(vec![
+        Command::SetLocator { number: 1, position: at(500) },
+        Command::SetLocator { number: 2, position: at(900) },
+        Command::SetLocator { number: 1, position: at(700) },
+    ]);
+    assert_eq!(events.len(), 3);
+    assert!(matches!(events[2], Event::LocatorSet { number: 1, position } if position == at(700)));
+    assert_eq!(locators(), vec![(1, at(700)), (2, at(900))]);
+
+    let events = session.run(vec![Command::RemoveLocator { number: 1 }, Command::RemoveLocator { number: 1 }]);
+    assert!(matches!(events.as_slice(), [Event::LocatorRemoved { number: 1 }]));
+    assert_eq!(locators(), vec![(2, at(900))]);
+}
+
+#[test]
+fn seeking_to_markers_and_locators() {
+    let session = Session::new();
+    let bridge = session.add_marker("Bridge", at(30000));
+    session.run(vec![Command::SetLocator { number: 4, position: at(12000) }]);
+
+    let events = session.run(vec![Command::SeekToMarker { marker_id: bridge }]);
+    assert!(matches!(events.as_slice(), [Event::PlaybackPositionChanged { position }] if *position == at(30000)));
+    assert_eq!(session.position(), at(30000));
+
+    let events = session.run(vec![Command::SeekToLocator { number: 4 }]);
+    assert!(matches!(events.as_slice(), [Event::PlaybackPositionChanged { position }] if *position == at(12000)));
+    assert_eq!(session.position(), at(12000));
+
+    // Missing targets report an error and leave the position alone
+    let events = session.run(vec![Command::SeekToMarker { marker_id: MarkerId::new() }]);
+    assert!(matches!(events.as_slice(), [Event::Error { message }] if message == "Marker not found"));
+    let events = session.run(vec![Command::SeekToLocator { number: 5 }]);
+    assert!(matches!(events.as_slice(), [Event::Error { message }] if message == "Locator 5 is not set"));
+    assert_eq!(session.position(), at(12000));
+
+    // Seeking follows a marker that has moved
+    session.run(vec![Command::MoveMarker { marker_id: bridge, position: at(20000) }]);
+    session.run(vec![Command::SeekToMarker { marker_id: bridge }]);
+    assert_eq!(session.position(), at(20000));
+}
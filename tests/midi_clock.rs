@@ -0,0 +1,132 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use loom::engine::{LockState, MidiBeatClock};
+use loom::model::Project;
+use loom::tapestry::{Tempo, TimePosition};
+
+const CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+
+/// Whether two positions in beats agree to well within a clock
+fn near(beats: f64, expected: f64) -> bool {
+    (beats - expected).abs() < 0.01
+}
+
+/// A clock following a project at the default 120 BPM
+fn clock() -> MidiBeatClock {
+    MidiBeatClock::new(Arc::new(RwLock::new(Project::new("Sync".to_string()))))
+}
+
+fn period(bpm: f64) -> Duration {
+    Duration::from_secs_f64(60.0 / bpm / 24.0)
+}
+
+/// Send `count` clocks at `bpm` after `at`, each up to half a millisecond out, and
+/// return when the last one arrived
+fn send_clocks(clock: &MidiBeatClock, at: Instant, bpm: f64, count: u32) -> Instant {
+    let mut on_time = at;
+    let mut arrived = at;
+    for n in 0..count {
+        on_time += period(bpm);
+        let jitter = Duration::from_micros([0, 500, 200, 400, 100][n as usize % 5]);
+        arrived = on_time + jitter;
+        clock.receive(&[CLOCK], arrived);
+    }
+    arrived
+}
+
+#[test]
+fn filter_follows_the_master_tempo_through_jitter() {
+    let clock = clock();
+    let start = Instant::now();
+    clock.receive(&[START], start);
+
+    let last = send_clocks(&clock, start, 100.0, 23);
+    assert_eq!(clock.lock_state_at(last), LockState::Locking);
+    let last = send_clocks(&clock, last, 100.0, 200);
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+    assert!((clock.tempo().unwrap().bpm - 100.0).abs() < 0.5);
+
+    // A gradual change is followed without losing lock
+    let mut last = last;
+    for bpm in [104.0, 108.0, 112.0, 116.0, 120.0] {
+        last = send_clocks(&clock, last, bpm, 24);
+    }
+    let last = send_clocks(&clock, last, 120.0, 240);
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+    assert!((clock.tempo().unwrap().bpm - 120.0).abs() < 0.5);
+
+    // The master going quiet drops the lock
+    assert_eq!(clock.lock_state_at(last + Duration::from_millis(200)), LockState::Unlocked);
+}
+
+#[test]
+fn speed_is_the_master_tempo_against_the_project() {
+    let project = Arc::new(RwLock::new(Project::new("Sync".to_string())));
+    project.write().unwrap().tempo_map.add_tempo_change(TimePosition::zero(), Tempo::new(100.0));
+    let clock = MidiBeatClock::new(project);
+    let start = Instant::now();
+    assert_eq!(clock.speed_at(start), 1.0);
+
+    clock.receive(&[START], start);
+    let last = send_clocks(&clock, start, 120.0, 240);
+    assert!((clock.speed_at(last) - 1.2).abs() < 0.01);
+}
+
+#[test]
+fn start_plays_from_the_top_and_stop_holds_the_position() {
+    let clock = clock();
+    let start = Instant::now();
+
+    // Clocks before Start only measure the tempo
+    let last = send_clocks(&clock, start, 120.0, 48);
+    assert!(near(clock.beats_at(last), 0.0));
+
+    clock.receive(&[START], last);
+    // The first clock after Start is the downbeat
+    let last = send_clocks(&clock, last, 120.0, 1);
+    assert!(near(clock.beats_at(last), 0.0));
+    let last = send_clocks(&clock, last, 120.0, 48);
+    assert!(near(clock.beats_at(last), 2.0));
+
+    clock.receive(&[STOP], last);
+    let stopped = send_clocks(&clock, last, 120.0, 48);
+    assert!(near(clock.beats_at(stopped), 2.0));
+
+    // Continue carries on from where the master stopped
+    clock.receive(&[CONTINUE], stopped);
+    let last = send_clocks(&clock, stopped, 120.0, 1);
+    assert!(near(clock.beats_at(last), 2.0));
+    let last = send_clocks(&clock, last, 120.0, 24);
+    assert!(near(clock.beats_at(last), 3.0));
+
+    // Start goes back to the top
+    clock.receive(&[STOP, START], last);
+    let last = send_clocks(&clock, last, 120.0, 1);
+    assert!(near(clock.beats_at(last), 0.0));
+}
+
+#[test]
+fn song_position_relocates_before_continue() {
+    let clock = clock();
+    let start = Instant::now();
+    let last = send_clocks(&clock, start, 120.0, 48);
+
+    // 272 sixteenths in, split across two reads
+    clock.receive(&[0xF2, 0x10], last);
+    clock.receive(&[0x02], last);
+    assert!(near(clock.beats_at(last), 68.0));
+    assert_eq!(clock.position_at(last), TimePosition::new(68 * 22050));
+
+    clock.receive(&[CONTINUE], last);
+    let last = send_clocks(&clock, last, 120.0, 1);
+    assert!(near(clock.beats_at(last), 68.0));
+    let last = send_clocks(&clock, last, 120.0, 12);
+    assert!(near(clock.beats_at(last), 68.5));
+
+    // A real-time message inside the song position does not break it up
+    clock.receive(&[STOP, 0xF2, 0x08, CLOCK, 0x00], last);
+    assert!(near(clock.beats_at(last), 2.0));
+}
@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+use loom::engine::{ClockSource, LockState, MtcClock};
+use loom::tapestry::{FrameRate, TimePosition, Timecode};
+
+const RATE: u32 = 48000;
+
+/// One quarter-frame sequence for 01:00:00:00 at 25 fps, as captured from a video deck
+const RECORDED_SEQUENCE: [u8; 16] = [
+    0xF1, 0x00, 0xF1, 0x10, 0xF1, 0x20, 0xF1, 0x30,
+    0xF1, 0x40, 0xF1, 0x50, 0xF1, 0x61, 0xF1, 0x72,
+];
+
+fn clock_at_one_hour(rate: FrameRate) -> MtcClock {
+    MtcClock::new(RATE, Timecode::new(1, 0, 0, 0, rate).unwrap())
+}
+
+/// Quarter-frame messages for `sequences` sequences starting at `start`, each with its
+/// arrival time measured from the first message
+fn quarter_frames(start: Timecode, rate_code: u8, sequences: u64) -> Vec<(Duration, [u8; 2])> {
+    let quarter = Duration::from_secs_f64(1.0 / start.rate.fps() / 4.0);
+    let first = start.frame_count();
+    let mut messages = Vec::new();
+
+    for sequence in 0..sequences {
+        let timecode = Timecode::from_frame_count(first + sequence * 2, start.rate);
+        let pieces = [
+            timecode.frames & 0x0F,
+            timecode.frames >> 4,
+            timecode.seconds & 0x0F,
+            timecode.seconds >> 4,
+            timecode.minutes & 0x0F,
+            timecode.minutes >> 4,
+            timecode.hours & 0x0F,
+            (timecode.hours >> 4) | (rate_code << 1),
+        ];
+        for (piece, value) in pieces.into_iter().enumerate() {
+            let index = sequence * 8 + piece as u64;
+            messages.push((quarter * index as u32, [0xF1, ((piece as u8) << 4) | value]));
+        }
+    }
+
+    messages
+}
+
+fn feed(clock: &MtcClock, base: Instant, messages: &[(Duration, [u8; 2])]) -> Instant {
+    let mut last = base;
+    for (offset, bytes) in messages {
+        last = base + *offset;
+        clock.receive(bytes, last);
+    }
+    last
+}
+
+fn ticks_for_frames(frames: f64, rate: FrameRate) -> u64 {
+    (frames / rate.fps() * RATE as f64).round() as u64
+}
+
+#[test]
+fn full_frame_locates_without_running() {
+    let clock = clock_at_one_hour(FrameRate::Fps25);
+    let now = Instant::now();
+
+    // 01:00:02:05 at 25 fps
+    clock.receive(&[0xF0, 0x7F, 0x7F, 0x01, 0x01, 0x21, 0x00, 0x02, 0x05, 0xF7], now);
+
+    assert_eq!(clock.frame_rate(), Some(FrameRate::Fps25));
+    assert_eq!(clock.position_at(now + Duration::from_secs(1)), TimePosition::new(2 * RATE as u64 + ticks_for_frames(5.0, FrameRate::Fps25)));
+    assert_eq!(clock.lock_state_at(now), LockState::Unlocked);
+    assert!(!clock.is_running());
+}
+
+#[test]
+fn recorded_sequence_applies_latency_correction() {
+    let clock = clock_at_one_hour(FrameRate::Fps25);
+    let now = Instant::now();
+
+    clock.receive(&RECORDED_SEQUENCE, now);
+
+    // The last piece arrives seven quarter frames after the frame the sequence describes
+    assert_eq!(clock.frame_rate(), Some(FrameRate::Fps25));
+    assert_eq!(clock.position_at(now), TimePosition::new(ticks_for_frames(1.75, FrameRate::Fps25)));
+    assert_eq!(clock.lock_state_at(now), LockState::Locking);
+}
+
+#[test]
+fn running_timecode_locks_and_tracks() {
+    let start = Timecode::new(1, 0, 0, 0, FrameRate::Fps25).unwrap();
+    let clock = clock_at_one_hour(FrameRate::Fps25);
+    let base = Instant::now();
+
+    let messages = quarter_frames(start, 1, 20);
+    let last = feed(&clock, base, &messages);
+
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+
+    // Wall-clock time since the first message equals timeline time since 01:00:00:00
+    let later = last + Duration::from_millis(5);
+    let expected = (later - base).as_secs_f64() * RATE as f64;
+    let position = clock.position_at(later).position_ticks as f64;
+    assert!((position - expected).abs() <= 1.0, "position {} expected {}", position, expected);
+}
+
+#[test]
+fn detects_drop_frame_rate() {
+    let start = Timecode::new(0, 1, 0, 2, FrameRate::Fps29_97Drop).unwrap();
+    let clock = MtcClock::new(RATE, Timecode::zero(FrameRate::Fps29_97Drop));
+    let base = Instant::now();
+
+    let last = feed(&clock, base, &quarter_frames(start, 2, 3));
+
+    assert_eq!(clock.frame_rate(), Some(FrameRate::Fps29_97Drop));
+    assert_eq!(clock.lock_state_at(last), LockState::Locked);
+
+    // The last sequence described 00:01:00;06, seven quarter frames before `last`
+    let described = Timecode::new(0, 1, 0, 6, FrameRate::Fps29_97Drop).unwrap();
+    let expected = described.to_position(&Timecode::zero(FrameRate::Fps29_97Drop), RATE).position_ticks
+        + ticks_for_frames(1.75, FrameRate::Fps29_97Drop);
+    let position = clock.position_at(last).position_ticks;
+    assert!(position.abs_diff(expected) <= 1, "position {} expected {}", position, expected);
+}
+
+#[test]
+fn freewheels_then_unlocks_after_dropout() {
+    let start = Timecode::new(1, 0, 0, 0, FrameRate::Fps25).unwrap();
+    let clock = clock_at_one_hour(FrameRate::Fps25).with_freewheel(Duration::from_millis(200));
+    let base = Instant::now();
+
+    let last = feed(&clock, base, &quarter_frames(start, 1, 5));
+    let at_last = clock.position_at(last);
+
+    // A short dropout keeps the clock running
+    let during = last + Duration::from_millis(100);
+    assert_eq!(clock.lock_state_at(during), LockState::Freewheeling);
+    assert_eq!(clock.position_at(during).position_ticks, at_last.position_ticks + RATE as u64 / 10);
+
+    // A long one stops it where the freewheel ran out
+    let after = last + Duration::from_secs(2);
+    let held = clock.position_at(after);
+    assert_eq!(clock.lock_state_at(after), LockState::Unlocked);
+    assert_eq!(clock.position_at(after + Duration::from_secs(1)), held);
+    assert!(held.position_ticks < at_last.position_ticks + RATE as u64 / 2);
+}
+
+#[test]
+fn tolerates_split_messages_and_realtime_bytes() {
+    let clock = clock_at_one_hour(FrameRate::Fps25);
+    let now = Instant::now();
+
+    // MIDI clock bytes interleaved, and the stream split mid-message
+    let mut stream = Vec::new();
+    for pair in RECORDED_SEQUENCE.chunks(2) {
+        stream.extend_from_slice(&[pair[0], 0xF8, pair[1]]);
+    }
+    let (first, second) = stream.split_at(7);
+    clock.receive(first, now);
+    clock.receive(second, now);
+
+    assert_eq!(clock.position_at(now), TimePosition::new(ticks_for_frames(1.75, FrameRate::Fps25)));
+}
@@ -0,0 +1,66 @@
+use loom::tapestry::{NoteValue, DEFAULT_PPQ, MAX_DOTS};
+
+#[test]
+fn tuplets_fill_their_span_exactly() {
+    assert_eq!(NoteValue::EIGHTH.tuplet(3, 2) * 3, NoteValue::QUARTER);
+    assert_eq!(NoteValue::EIGHTH.tuplet(3, 2), NoteValue::TRIPLET_EIGHTH);
+    assert_eq!(NoteValue::SIXTEENTH.tuplet(5, 4) * 5, NoteValue::QUARTER);
+    assert_eq!(NoteValue::QUARTER.tuplet(7, 4) * 7, NoteValue::WHOLE);
+    // A quintuplet inside a triplet
+    assert_eq!(NoteValue::TRIPLET_EIGHTH.tuplet(5, 4) * 15, NoteValue::WHOLE);
+    assert_eq!(NoteValue::QUARTER.tuplet(7, 4), NoteValue::WHOLE / 7);
+}
+
+#[test]
+fn dots_add_half_of_the_last_addition() {
+    assert_eq!(NoteValue::QUARTER.dotted(), NoteValue::DOTTED_QUARTER);
+    assert_eq!(NoteValue::QUARTER.double_dotted(), NoteValue::new(7, 4));
+    assert_eq!(NoteValue::QUARTER.with_dots(3), NoteValue::new(15, 8));
+    assert_eq!(NoteValue::HALF.with_dots(0), NoteValue::HALF);
+    assert_eq!(NoteValue::EIGHTH.dotted() + NoteValue::SIXTEENTH, NoteValue::QUARTER);
+    // Dotted triplets stay exact too
+    assert_eq!(NoteValue::TRIPLET_QUARTER.dotted(), NoteValue::QUARTER);
+}
+
+#[test]
+fn ties_add_exactly_in_lowest_terms() {
+    let tied = NoteValue::TRIPLET_EIGHTH.tied(NoteValue::TRIPLET_EIGHTH);
+    assert_eq!(tied, NoteValue::TRIPLET_QUARTER);
+    assert_eq!((tied.numerator(), tied.denominator()), (2, 3));
+
+    assert_eq!(NoteValue::QUARTER.tied(NoteValue::TRIPLET_EIGHTH), NoteValue::TRIPLET_HALF);
+    let mut bar = NoteValue::zero();
+    for value in [NoteValue::DOTTED_QUARTER, NoteValue::EIGHTH, NoteValue::TRIPLET_QUARTER, NoteValue::TRIPLET_QUARTER, NoteValue::TRIPLET_QUARTER] {
+        bar += value;
+    }
+    assert_eq!(bar, NoteValue::WHOLE);
+    assert!(NoteValue::TRIPLET_QUARTER < NoteValue::QUARTER && NoteValue::QUARTER < NoteValue::DOTTED_QUARTER);
+}
+
+#[test]
+fn ticks_are_exact_where_the_grid_allows() {
+    assert_eq!(NoteValue::TRIPLET_EIGHTH.ticks_exact(DEFAULT_PPQ), Some(320));
+    assert_eq!(NoteValue::SIXTEENTH.tuplet(5, 4).ticks_exact(DEFAULT_PPQ), Some(192));
+
+    // Septuplets fall between ticks, but a run of them never drifts off the grid
+    let septuplet = NoteValue::QUARTER.tuplet(7, 4);
+    assert_eq!(septuplet.ticks_exact(DEFAULT_PPQ), None);
+    let boundaries: Vec<u64> = (0..=7).map(|index| septuplet.ticks_at(index, DEFAULT_PPQ)).collect();
+    assert_eq!(boundaries, vec![0, 549, 1097, 1646, 2194, 2743, 3291, 3840]);
+    assert_eq!(septuplet.to_ticks(DEFAULT_PPQ), 549);
+
+    // Approximating from beats keeps exact triplets but not septuplets
+    assert_eq!(NoteValue::from_beats(1.0 / 3.0), NoteValue::TRIPLET_EIGHTH);
+    assert_ne!(NoteValue::from_beats(septuplet.to_beats()), septuplet);
+}
+
+#[test]
+fn dots_up_to_the_limit_fit() {
+    assert_eq!(NoteValue::QUARTER.with_dots(MAX_DOTS), NoteValue::new((1 << (MAX_DOTS + 1)) - 1, 1 << MAX_DOTS));
+}
+
+#[test]
+#[should_panic(expected = "at most 62 dots")]
+fn too_many_dots_panic() {
+    NoteValue::QUARTER.with_dots(MAX_DOTS + 1);
+}
@@ -0,0 +1,209 @@
+use std::error::Error;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use loom::controller::command::{create_command_channel, Command};
+use loom::controller::event::{create_event_channel, EventHub};
+use loom::controller::Controller;
+use loom::engine::PlaybackEngine;
+use loom::model::{EndpointConfig, EndpointId, EndpointType, Project, Track, TrackId, TrackType};
+use loom::output::notes::ALL_NOTES_OFF;
+use loom::output::{ActiveNotes, OutputEndpoint, OutputEvent, OutputEventType, OutputSystem};
+
+fn note_on(note: u8, track_id: TrackId) -> OutputEvent {
+    OutputEvent::midi_note_on(0, note, 100, None).with_source(track_id)
+}
+
+fn notes(released: &[(Instant, u8, u8)]) -> Vec<u8> {
+    let mut notes: Vec<u8> = released.iter().map(|(_, _, note)| *note).collect();
+    notes.sort();
+    notes
+}
+
+#[test]
+fn releases_only_notes_still_sounding() {
+    let track = TrackId::new();
+    let now = Instant::now();
+    let mut active = ActiveNotes::new();
+
+    active.record(&note_on(60, track), now);
+    active.record(&note_on(64, track), now);
+    active.record(&note_on(67, track), now);
+    active.record(&OutputEvent::midi_note_off(0, 60, None), now);
+    // A note-on with no velocity ends a note too
+    active.record(&OutputEvent::midi_note_on(0, 64, 0, None), now);
+
+    assert_eq!(notes(&active.release(now, None)), vec![67]);
+    assert!(active.release(now, None).is_empty());
+
+    active.record(&note_on(48, track), now);
+    active.record(&OutputEvent::midi_cc(0, ALL_NOTES_OFF, 0, None), now);
+    assert!(active.release(now, None).is_empty());
+}
+
+#[test]
+fn cancelled_changes_never_happen() {
+    let track = TrackId::new();
+    let now = Instant::now();
+    let later = now + Duration::from_millis(10);
+    let mut active = ActiveNotes::new();
+
+    active.record(&note_on(60, track), now);
+    active.record(&OutputEvent::midi_note_off(0, 60, None), later);
+    active.record(&note_on(62, track), later);
+    active.cancel(now);
+
+    // The note-off was cancelled, so the note is still sounding; the note-on never sounds
+    assert_eq!(notes(&active.release(later, None)), vec![60]);
+}
+
+#[test]
+fn releases_by_track_and_upcoming_notes_as_they_start() {
+    let keys = TrackId::new();
+    let bass = TrackId::new();
+    let now = Instant::now();
+    let later = now + Duration::from_millis(10);
+    let mut active = ActiveNotes::new();
+
+    active.record(&note_on(60, keys), now);
+    active.record(&note_on(36, bass), now);
+    active.record(&note_on(64, keys), later);
+
+    let released = active.release(now, Some(keys));
+    assert_eq!(released, vec![(now, 0, 60), (later, 0, 64)]);
+    assert_eq!(notes(&active.release(later, None)), vec![36]);
+}
+
+/// A MIDI endpoint that keeps everything sent to it
+struct Recorder(Arc<Mutex<Vec<OutputEvent>>>);
+
+impl OutputEndpoint for Recorder {
+    fn name(&self) -> &str {
+        "Recorder"
+    }
+
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn connect(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {}
+
+    fn send_event(&mut self, event: &OutputEvent) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+
+    fn endpoint_type(&self) -> EndpointType {
+        EndpointType::Midi
+    }
+}
+
+/// Three tracks, each holding a note on one endpoint: 60 on keys, 36 on bass and 48 on pads
+struct Band {
+    project: Arc<RwLock<Project>>,
+    output: Arc<RwLock<OutputSystem>>,
+    sent: Arc<Mutex<Vec<OutputEvent>>>,
+    tracks: [TrackId; 3],
+}
+
+impl Band {
+    fn new() -> Self {
+        let mut project = Project::new("Band".to_string());
+        let output_id = project.add_endpoint(EndpointConfig::new_midi("Synth".to_string(), "0:Synth".to_string()));
+        let timeline = project.active_timeline_mut().unwrap();
+        let tracks = ["Keys", "Bass", "Pads"].map(|name| {
+            timeline.add_track(Track::new(name.to_string(), TrackType::Midi).with_output(output_id))
+        });
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut output = OutputSystem::new();
+        output.insert_endpoint(output_id, Box::new(Recorder(Arc::clone(&sent))));
+        for (track_id, note) in tracks.iter().zip([60, 36, 48]) {
+            let _ = output.send_event(&OutputEvent::midi_note_on(0, note, 100, Some(output_id)).with_source(*track_id));
+        }
+
+        let band = Self { project: Arc::new(RwLock::new(project)), output: Arc::new(RwLock::new(output)), sent, tracks };
+        band.sent.lock().unwrap().clear();
+        band
+    }
+
+    /// Run `commands` through a controller, returning the notes released
+    fn run(&self, commands: Vec<Command>) -> Vec<u8> {
+        let (command_sender, command_receiver) = create_command_channel();
+        let (event_sender, _events) = create_event_channel();
+        let engine = PlaybackEngine::new(Arc::clone(&self.project), event_sender.clone(), Arc::clone(&self.output));
+        let mut event_hub = EventHub::new();
+        event_hub.add_receiver(event_sender);
+        let mut controller = Controller::new(
+            command_receiver,
+            event_hub,
+            Arc::clone(&self.project),
+            Arc::new(RwLock::new(engine)),
+            Arc::clone(&self.output),
+        );
+
+        for command in commands {
+            command_sender.send(command).unwrap();
+        }
+        // The controller runs until every command is handled and the sender is gone
+        drop(command_sender);
+        controller.run();
+
+        let mut sent = self.sent.lock().unwrap();
+        sent.drain(..)
+            .filter_map(|event| match event.event_type {
+                OutputEventType::MidiNoteOff { note, .. } => Some(note),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn sounding(&self, output_id: EndpointId) -> Vec<u8> {
+        let output = self.output.read().unwrap();
+        let notes = output.active_notes(output_id).unwrap();
+        [60, 36, 48].into_iter().filter(|note| notes.is_sounding(0, *note, Instant::now())).collect()
+    }
+
+    fn output_id(&self) -> EndpointId {
+        *self.project.read().unwrap().endpoints.keys().next().unwrap()
+    }
+}
+
+#[test]
+fn muting_a_track_releases_its_notes() {
+    let band = Band::new();
+    let [keys, bass, _] = band.tracks;
+
+    assert_eq!(band.run(vec![Command::MuteTrack { track_id: bass, muted: true }]), vec![36]);
+    assert_eq!(band.sounding(band.output_id()), vec![60, 48]);
+    // Unmuting releases nothing, and nor does muting an unknown track
+    assert!(band.run(vec![
+        Command::MuteTrack { track_id: bass, muted: false },
+        Command::MuteTrack { track_id: TrackId::new(), muted: true },
+    ]).is_empty());
+    assert_eq!(band.run(vec![Command::MuteTrack { track_id: keys, muted: true }]), vec![60]);
+}
+
+#[test]
+fn soloing_a_track_releases_the_notes_of_every_track_it_silences() {
+    let band = Band::new();
+    let [keys, bass, pads] = band.tracks;
+
+    let mut released = band.run(vec![Command::SoloTrack { track_id: keys, solo: true }]);
+    released.sort();
+    assert_eq!(released, vec![36, 48]);
+    assert_eq!(band.sounding(band.output_id()), vec![60]);
+    assert!(band.project.read().unwrap().active_timeline().unwrap().tracks[0].is_solo);
+
+    // Adding a second solo silences nothing more; dropping the first silences keys
+    assert!(band.run(vec![Command::SoloTrack { track_id: bass, solo: true }]).is_empty());
+    assert_eq!(band.run(vec![Command::SoloTrack { track_id: keys, solo: false }]), vec![60]);
+    // Dropping the last solo brings everything back and releases nothing
+    assert!(band.run(vec![
+        Command::SoloTrack { track_id: bass, solo: false },
+        Command::SoloTrack { track_id: pads, solo: false },
+    ]).is_empty());
+}
@@ -0,0 +1,106 @@
+use loom::model::{MediaContainer, MediaContent, MidiClip, Project, Track, TrackType};
+use loom::tapestry::{Duration, TimeOffset, TimePosition};
+
+const RATE: u32 = 44100;
+
+fn at(ticks: u64) -> TimePosition {
+    TimePosition::new(ticks)
+}
+
+#[test]
+fn offsets_point_both_ways() {
+    let back = TimeOffset::from_seconds(-0.5, RATE);
+    assert_eq!(back.ticks(), -22050);
+    assert!(back.is_negative());
+    assert_eq!(back.abs(), Duration::new(22050));
+    assert_eq!(back.to_seconds(RATE), -0.5);
+
+    assert_eq!(TimeOffset::between(at(100), at(40)), TimeOffset::new(-60));
+    assert_eq!(at(40).offset_from(at(100)), TimeOffset::new(-60));
+    assert_eq!(at(100) + TimeOffset::new(-60), at(40));
+    assert_eq!(at(100) - TimeOffset::new(-60), at(160));
+    assert_eq!(-TimeOffset::new(-60), TimeOffset::new(60));
+    assert_eq!(TimeOffset::new(-60) + TimeOffset::new(100), TimeOffset::new(40));
+
+    // Further apart than an offset can reach
+    assert_eq!(TimeOffset::between(at(0), at(u64::MAX)), TimeOffset::new(i64::MAX));
+    assert_eq!(TimeOffset::between(at(u64::MAX), at(0)), TimeOffset::new(i64::MIN));
+    assert_eq!(TimeOffset::from(Duration::new(u64::MAX)), TimeOffset::new(i64::MAX));
+}
+
+#[test]
+fn positions_check_wrap_or_saturate_at_their_limits() {
+    let one = Duration::new(1);
+    let top = at(u64::MAX);
+
+    assert_eq!(top.checked_add(one), None);
+    assert_eq!(at(0).checked_sub(one), None);
+    assert_eq!(at(5).checked_sub(one), Some(at(4)));
+    assert_eq!(top.wrapping_add(one), at(0));
+    assert_eq!(at(0).wrapping_sub(one), top);
+    assert_eq!(top.saturating_add(one), top);
+    assert_eq!(at(0).saturating_sub(one), at(0));
+
+    let back = TimeOffset::new(-1);
+    let forward = TimeOffset::new(1);
+    assert_eq!(at(0).checked_offset(back), None);
+    assert_eq!(top.checked_offset(forward), None);
+    assert_eq!(at(5).checked_offset(back), Some(at(4)));
+    assert_eq!(at(0).wrapping_offset(back), top);
+    assert_eq!(top.wrapping_offset(forward), at(0));
+    assert_eq!(at(0).saturating_offset(back), at(0));
+    assert_eq!(top.saturating_offset(forward), top);
+    // The operators saturate
+    assert_eq!(at(0) + back, at(0));
+    assert_eq!(top + forward, top);
+}
+
+#[test]
+fn durations_and_offsets_check_wrap_or_saturate_at_their_limits() {
+    let one = Duration::new(1);
+    let top = Duration::new(u64::MAX);
+    assert_eq!(top.checked_add(one), None);
+    assert_eq!(Duration::zero().checked_sub(one), None);
+    assert_eq!(top.wrapping_add(one), Duration::zero());
+    assert_eq!(Duration::zero().wrapping_sub(one), top);
+    assert_eq!(top.saturating_add(one), top);
+    assert_eq!(Duration::zero().saturating_sub(one), Duration::zero());
+
+    let one = TimeOffset::new(1);
+    let (min, max) = (TimeOffset::new(i64::MIN), TimeOffset::new(i64::MAX));
+    assert_eq!(max.checked_add(one), None);
+    assert_eq!(min.checked_sub(one), None);
+    assert_eq!(TimeOffset::zero().checked_sub(one), Some(TimeOffset::new(-1)));
+    assert_eq!(max.wrapping_add(one), min);
+    assert_eq!(min.wrapping_sub(one), max);
+    assert_eq!(max.saturating_add(one), max);
+    assert_eq!(min.saturating_sub(one), min);
+    assert_eq!(max + one, max);
+    assert_eq!(min - one, min);
+    assert_eq!(-min, max);
+}
+
+#[test]
+fn nudging_before_the_timeline_start_is_refused() {
+    let mut project = Project::new("Nudge".to_string());
+    let clip_id = project.add_midi_clip(MidiClip::new("Clip".to_string(), 960));
+    let container = MediaContainer::new(at(1000), MediaContent::MidiClip(clip_id), &project.tempo_map);
+    let id = container.id;
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    timeline.add_container(track_id, container);
+
+    assert!(timeline.nudge_container(id, TimeOffset::new(-400)));
+    assert_eq!(timeline.containers[&id].position, at(600));
+    assert!(timeline.track_containers[&track_id].contains(&(at(600), id)));
+
+    assert!(!timeline.nudge_container(id, TimeOffset::new(-601)));
+    assert_eq!(timeline.containers[&id].position, at(600));
+
+    // Right back to the start is fine
+    assert!(timeline.nudge_container(id, TimeOffset::new(-600)));
+    assert_eq!(timeline.containers[&id].position, at(0));
+    assert!(timeline.nudge_container(id, TimeOffset::new(250)));
+    assert_eq!(timeline.containers[&id].position, at(250));
+}
@@ -0,0 +1,164 @@
+use loom::model::{AudioFileId, ContainerId, MediaContainer, MediaContent, MidiClip, MidiNote, Project, Track, TrackType};
+use loom::tapestry::{Duration, NoteValue, QuantizeMode, QuantizeSpan, Quantizer, TempoMap, TimePosition};
+
+const RATE: u32 = 44100;
+/// Ticks per beat at the default 120 BPM
+const BEAT: u64 = 22050;
+
+fn map() -> TempoMap {
+    TempoMap::new(RATE, RATE)
+}
+
+fn assert_beats(beats: f64, expected: f64) {
+    assert!((beats - expected).abs() < 1e-9, "{beats} beats, expected {expected}");
+}
+
+/// Positions may round to the nearest tick on the way through beats
+fn assert_ticks(position: TimePosition, expected: u64) {
+    assert!(position.position_ticks.abs_diff(expected) <= 1, "{position:?}, expected {expected} ticks");
+}
+
+#[test]
+fn strength_moves_part_of_the_way() {
+    let map = map();
+    let sixteenths = Quantizer::new(NoteValue::SIXTEENTH);
+
+    assert_beats(sixteenths.quantize_beats(1.3, &map), 1.25);
+    assert_beats(sixteenths.with_strength(50.0).quantize_beats(1.3, &map), 1.275);
+    assert_beats(sixteenths.with_strength(0.0).quantize_beats(1.3, &map), 1.3);
+    // Out of range strengths are clamped
+    assert_beats(sixteenths.with_strength(150.0).quantize_beats(1.3, &map), 1.25);
+}
+
+#[test]
+fn swing_delays_every_second_line() {
+    let map = map();
+    let eighths = Quantizer::new(NoteValue::EIGHTH);
+
+    assert_beats(eighths.quantize_beats(0.62, &map), 0.5);
+    // Full swing puts the off-beat on the last triplet eighth
+    assert_beats(eighths.with_swing(100.0).quantize_beats(0.62, &map), 2.0 / 3.0);
+    assert_beats(eighths.with_swing(50.0).quantize_beats(0.62, &map), 0.5 + 1.0 / 12.0);
+    // On-beats stay put
+    assert_beats(eighths.with_swing(100.0).quantize_beats(1.1, &map), 1.0);
+}
+
+#[test]
+fn capture_window_leaves_distant_events_alone() {
+    let map = map();
+    // Half of half a sixteenth either side of a line
+    let sixteenths = Quantizer::new(NoteValue::SIXTEENTH).with_capture_window(50.0);
+
+    assert_beats(sixteenths.quantize_beats(1.3, &map), 1.25);
+    assert_beats(sixteenths.quantize_beats(1.2, &map), 1.25);
+    assert_beats(sixteenths.quantize_beats(1.33, &map), 1.33);
+    assert_beats(sixteenths.with_capture_window(0.0).quantize_beats(1.3, &map), 1.3);
+}
+
+#[test]
+fn iterative_quantize_builds_on_the_last_pass() {
+    let map = map();
+    let half = Quantizer::new(NoteValue::QUARTER).with_strength(50.0);
+    let length = Duration::new(BEAT / 2);
+    let played = TimePosition::new(BEAT * 7 / 5);
+
+    // Each pass starts again from the timing as played
+    let mut span = QuantizeSpan::new(played, length);
+    half.quantize_span(&mut span, &map);
+    half.quantize_span(&mut span, &map);
+    assert_ticks(span.start, BEAT * 6 / 5);
+    assert_eq!(span.original_start, played);
+    span.unquantize();
+    assert_eq!(span.start, played);
+
+    // Each pass moves on from the last, which becomes the new original
+    let iterative = half.with_iterative(true);
+    let mut span = QuantizeSpan::new(played, length);
+    iterative.quantize_span(&mut span, &map);
+    assert_ticks(span.start, BEAT * 6 / 5);
+    iterative.quantize_span(&mut span, &map);
+    assert_ticks(span.start, BEAT * 11 / 10);
+    span.unquantize();
+    assert_ticks(span.start, BEAT * 11 / 10);
+    assert_eq!(span.length, length);
+}
+
+#[test]
+fn start_and_end_mode_quantizes_the_length_too() {
+    let map = map();
+    let quarters = Quantizer::new(NoteValue::QUARTER).with_mode(QuantizeMode::StartAndEnd);
+
+    let mut span = QuantizeSpan::new(TimePosition::new(BEAT * 9 / 10), Duration::new(BEAT * 6 / 5));
+    quarters.quantize_span(&mut span, &map);
+    assert_eq!(span.start, TimePosition::new(BEAT));
+    assert_eq!(span.length, Duration::new(BEAT));
+
+    // A note that would quantize away keeps a grid step
+    let mut span = QuantizeSpan::new(TimePosition::new(BEAT * 9 / 10), Duration::new(BEAT / 5));
+    quarters.quantize_span(&mut span, &map);
+    assert_eq!(span.length, Duration::new(BEAT));
+}
+
+/// A project with `clip` in a container a sixty-fourth note past beat 4, so the
+/// timeline's sixteenths fall on clip ticks 180, 420, 660 and so on
+fn placed_clip(clip: MidiClip) -> (Project, ContainerId) {
+    let mut project = Project::new("Quantize".to_string());
+    let clip_id = project.add_midi_clip(clip);
+    let position = project.tempo_map.beats_to_position(4.0625);
+    let container = MediaContainer::new(position, MediaContent::MidiClip(clip_id), &project.tempo_map);
+    let container_id = container.id;
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    timeline.add_container(track_id, container);
+    (project, container_id)
+}
+
+fn clip_with(notes: &[(u64, u64)]) -> MidiClip {
+    let mut clip = MidiClip::new("Played".to_string(), 4 * 960);
+    for (start, length) in notes {
+        clip.add_note(MidiNote::new(*start, *length, 60, 100));
+    }
+    clip
+}
+
+fn notes(project: &Project, container_id: ContainerId) -> Vec<(u64, u64)> {
+    let timeline = project.active_timeline().unwrap();
+    let MediaContent::MidiClip(clip_id) = timeline.containers[&container_id].content else {
+        panic!("not a MIDI clip");
+    };
+    project.midi_clips[&clip_id].notes.iter().map(|note| (note.start, note.length)).collect()
+}
+
+#[test]
+fn clips_quantize_to_the_timeline_grid_where_they_are_placed() {
+    let played = [(180, 100), (400, 500), (950, 100)];
+    let sixteenths = Quantizer::new(NoteValue::SIXTEENTH);
+
+    let (mut project, container_id) = placed_clip(clip_with(&played));
+    project.quantize_clip(container_id, &sixteenths).unwrap();
+    assert_eq!(notes(&project, container_id), vec![(180, 100), (420, 500), (900, 100)]);
+
+    let (mut project, container_id) = placed_clip(clip_with(&played));
+    project.quantize_clip(container_id, &sixteenths.with_strength(50.0)).unwrap();
+    assert_eq!(notes(&project, container_id), vec![(180, 100), (410, 500), (925, 100)]);
+
+    // The end of the second note is already on a line
+    let (mut project, container_id) = placed_clip(clip_with(&played));
+    project.quantize_clip(container_id, &sixteenths.with_mode(QuantizeMode::StartAndEnd)).unwrap();
+    assert_eq!(notes(&project, container_id)[1], (420, 480));
+}
+
+#[test]
+fn quantizing_needs_a_container_with_a_midi_clip() {
+    let (mut project, _) = placed_clip(clip_with(&[(400, 100)]));
+    let sixteenths = Quantizer::new(NoteValue::SIXTEENTH);
+    assert_eq!(project.quantize_clip(ContainerId::new(), &sixteenths), Err("Container not found"));
+
+    let audio = MediaContainer::new(TimePosition::zero(), MediaContent::AudioFile(AudioFileId::new()), &project.tempo_map);
+    let audio_id = audio.id;
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Audio".to_string(), TrackType::Audio));
+    timeline.add_container(track_id, audio);
+    assert_eq!(project.quantize_clip(audio_id, &sixteenths), Err("Container does not hold a MIDI clip"));
+}
@@ -0,0 +1,298 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use loom::controller::event::create_event_channel;
+use loom::engine::{ClockSource, ManualClock, PlaybackEngine, RenderedEvent};
+use loom::model::{ArrangerSection, EndpointConfig, MediaContainer, MediaContent, MidiClip, MidiControl, MidiNote, PlaylistEntry};
+use loom::model::{Project, SyncOutput, Track, TrackType};
+use loom::output::{OutputEventType, OutputSystem};
+use loom::tapestry::{NoteValue, TimeOffset, TimePosition, DEFAULT_PPQ};
+
+const RATE: u64 = 44100;
+const PPQ: u64 = DEFAULT_PPQ as u64;
+/// Ticks per beat at the default 120 BPM
+const BEAT: u64 = 22050;
+
+/// An engine for an empty 120 BPM project with one endpoint taking both kinds of sync
+fn engine() -> PlaybackEngine {
+    let mut project = Project::new("Render".to_string());
+    let sync = SyncOutput { midi_clock: true, mtc: true, offset: TimeOffset::zero() };
+    project.add_endpoint(EndpointConfig::new_midi("Sync".to_string(), "none".to_string()).with_sync(sync));
+
+    engine_for(project)
+}
+
+fn engine_for(project: Project) -> PlaybackEngine {
+    let (event_sender, _) = create_event_channel();
+    PlaybackEngine::new(
+        Arc::new(RwLock::new(project)),
+        event_sender,
+        Arc::new(RwLock::new(OutputSystem::new())),
+    )
+}
+
+fn count(events: &[RenderedEvent], matches: impl Fn(&OutputEventType) -> bool) -> usize {
+    events.iter().filter(|rendered| matches(&rendered.event.event_type)).count()
+}
+
+#[test]
+fn manual_clock_moves_only_when_advanced_while_running() {
+    let clock = ManualClock::new(RATE as u32);
+    clock.advance(100);
+    assert_eq!(clock.current_time(), TimePosition::zero());
+
+    clock.start();
+    clock.advance(100);
+    clock.advance(50);
+    assert_eq!(clock.current_time(), TimePosition::new(150));
+
+    clock.pause();
+    clock.advance(100);
+    assert_eq!(clock.current_time(), TimePosition::new(150));
+    assert!(!clock.is_running());
+
+    clock.locate(TimePosition::new(RATE));
+    assert_eq!(clock.current_time(), TimePosition::new(RATE));
+}
+
+#[test]
+fn renders_identically_every_time() {
+    let engine = engine();
+    let end = TimePosition::new(4 * RATE);
+
+    let first = engine.render(TimePosition::zero(), end, 512);
+    let second = engine.render(TimePosition::zero(), end, 512);
+
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}
+
+#[test]
+fn renders_beats_and_sync_for_the_range() {
+    let engine = engine();
+    // Eight beats at 120 BPM
+    let end = TimePosition::new(4 * RATE);
+    let events = engine.render(TimePosition::zero(), end, 441);
+
+    assert!(matches!(events.first().unwrap().event.event_type, OutputEventType::SyncStart));
+    assert!(matches!(events.last().unwrap().event.event_type, OutputEventType::SyncStop));
+    assert_eq!(events.last().unwrap().position, end);
+    assert!(events.windows(2).all(|pair| pair[0].position <= pair[1].position));
+
+    assert_eq!(count(&events, |event| matches!(event, OutputEventType::MidiNoteOn { .. })), 8);
+    assert_eq!(count(&events, |event| matches!(event, OutputEventType::SyncPulse)), 8 * 24);
+    assert_eq!(count(&events, |event| matches!(event, OutputEventType::MtcQuarterFrame { .. })), 4 * 30 * 4);
+}
+
+#[test]
+fn block_size_changes_only_timing() {
+    let engine = engine();
+    let end = TimePosition::new(3 * RATE + 1234);
+
+    let fine = engine.render(TimePosition::new(RATE / 3), end, 64);
+    let coarse = engine.render(TimePosition::new(RATE / 3), end, 4410);
+
+    assert_eq!(fine.len(), coarse.len());
+    let notes = |events: &[RenderedEvent]| -> Vec<OutputEventType> {
+        events.iter()
+            .map(|rendered| rendered.event.event_type.clone())
+            .filter(|event| matches!(event, OutputEventType::MidiNoteOn { .. }))
+            .collect()
+    };
+    assert_eq!(notes(&fine), notes(&coarse));
+}
+
+#[test]
+fn events_carry_their_exact_positions() {
+    let engine = engine();
+    // Blocks that never line up with a beat
+    let events = engine.render(TimePosition::zero(), TimePosition::new(2 * RATE), 1000);
+
+    let notes: Vec<u64> = events.iter()
+        .filter(|rendered| matches!(rendered.event.event_type, OutputEventType::MidiNoteOn { .. }))
+        .map(|rendered| rendered.position.position_ticks)
+        .collect();
+    assert_eq!(notes, vec![RATE / 2, RATE, 3 * RATE / 2, 2 * RATE]);
+
+    // 24 clocks to a beat, each half a second at 120 BPM
+    let clocks: Vec<u64> = events.iter()
+        .filter(|rendered| matches!(rendered.event.event_type, OutputEventType::SyncPulse))
+        .map(|rendered| rendered.position.position_ticks)
+        .collect();
+    for (index, ticks) in clocks.iter().enumerate() {
+        let expected = index as f64 * RATE as f64 / 2.0 / 24.0;
+        assert!((*ticks as f64 - expected).abs() <= 1.0, "clock {index} at {ticks}, expected {expected}");
+    }
+}
+
+/// A project playing `clip` on one MIDI track in a bar-long container at the start
+fn clip_project(clip: MidiClip, looped: bool) -> Project {
+    let mut project = Project::new("Clip".to_string());
+    let output_id = project.add_endpoint(EndpointConfig::new_midi("Synth".to_string(), "0:Synth".to_string()));
+    let clip_id = project.add_midi_clip(clip);
+    let mut container = MediaContainer::new(TimePosition::zero(), MediaContent::MidiClip(clip_id), &project.tempo_map)
+        .with_musical_length(NoteValue::WHOLE, &project.tempo_map);
+    if looped {
+        container = container.with_loop(None);
+    }
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Bass".to_string(), TrackType::Midi).with_output(output_id));
+    timeline.add_container(track_id, container);
+    project
+}
+
+/// What the clip track played, as tick and message
+fn played(project: Project, end: u64) -> Vec<(u64, OutputEventType)> {
+    let track = project.active_timeline().unwrap().tracks[0].clone();
+    let events = engine_for(project).render(TimePosition::zero(), TimePosition::new(end), 1000);
+    events.into_iter()
+        .filter(|rendered| rendered.event.target == track.output_id)
+        .inspect(|rendered| assert_eq!(rendered.event.source, Some(track.id)))
+        .map(|rendered| (rendered.position.position_ticks, rendered.event.event_type))
+        .collect()
+}
+
+fn on(note: u8, velocity: u8) -> OutputEventType {
+    OutputEventType::MidiNoteOn { channel: 0, note, velocity }
+}
+
+fn off(note: u8) -> OutputEventType {
+    OutputEventType::MidiNoteOff { channel: 0, note }
+}
+
+#[test]
+fn renders_midi_clips_in_their_containers() {
+    // Two beats, looped over a bar
+    let mut clip = MidiClip::new("Riff".to_string(), 2 * PPQ);
+    clip.add_control(MidiControl::program(0, 0, 5));
+    clip.add_note(MidiNote::new(0, 2 * PPQ, 40, 100));
+    clip.add_note(MidiNote::new(PPQ, PPQ / 2, 43, 90));
+
+    let program = OutputEventType::MidiProgramChange { channel: 0, program: 5 };
+    assert_eq!(played(clip_project(clip, true), 5 * BEAT), vec![
+        (0, program.clone()),
+        (0, on(40, 100)),
+        (BEAT, on(43, 90)),
+        (3 * BEAT / 2, off(43)),
+        // The loop starts again: the change, then the old note ends before it is played again
+        (2 * BEAT, program),
+        (2 * BEAT, off(40)),
+        (2 * BEAT, on(40, 100)),
+        (3 * BEAT, on(43, 90)),
+        (7 * BEAT / 2, off(43)),
+        // Cut at the end of the container
+        (4 * BEAT, off(40)),
+    ]);
+}
+
+#[test]
+fn releases_notes_cut_by_the_arranger() {
+    let mut clip = MidiClip::new("Drone".to_string(), 4 * PPQ);
+    clip.add_note(MidiNote::new(0, 4 * PPQ, 40, 100));
+    let mut project = clip_project(clip, false);
+
+    // The first half of the bar, twice
+    let arrangement = &mut project.active_timeline_mut().unwrap().arrangement;
+    let section_id = arrangement.add_section(ArrangerSection::new("A".to_string(), TimePosition::zero(), TimePosition::new(2 * BEAT)).unwrap());
+    arrangement.set_playlist(vec![PlaylistEntry::new(section_id, 2)]).unwrap();
+    arrangement.enabled = true;
+
+    assert_eq!(played(project, 5 * BEAT), vec![
+        (0, on(40, 100)),
+        (2 * BEAT, off(40)),
+        (2 * BEAT, on(40, 100)),
+        (4 * BEAT, off(40)),
+    ]);
+}
+
+#[test]
+fn flattening_keeps_containers_that_straddle_a_section() {
+    let mut riff = MidiClip::new("Riff".to_string(), 2 * PPQ);
+    riff.add_note(MidiNote::new(0, PPQ / 2, 40, 100));
+    riff.add_note(MidiNote::new(PPQ, PPQ / 2, 43, 100));
+    let mut line = MidiClip::new("Line".to_string(), 4 * PPQ);
+    for beat in 0..4 {
+        line.add_note(MidiNote::new(beat * PPQ, PPQ / 2, 50 + beat as u8, 100));
+    }
+
+    let mut project = Project::new("Flatten".to_string());
+    let output_id = project.add_endpoint(EndpointConfig::new_midi("Synth".to_string(), "0:Synth".to_string()));
+    let riff_id = project.add_midi_clip(riff);
+    let line_id = project.add_midi_clip(line);
+    let tempo_map = project.tempo_map.clone();
+    // Both start before the section: the loop part way through a pass, the line a beat early
+    let looped = MediaContainer::new(TimePosition::zero(), MediaContent::MidiClip(riff_id), &tempo_map)
+        .with_loop(None);
+    let once = MediaContainer::new(TimePosition::new(BEAT), MediaContent::MidiClip(line_id), &tempo_map);
+
+    let timeline = project.active_timeline_mut().unwrap();
+    for (name, container) in [("Riff", looped), ("Line", once)] {
+        let track_id = timeline.add_track(Track::new(name.to_string(), TrackType::Midi).with_output(output_id));
+        timeline.add_container(track_id, container);
+    }
+    let section = ArrangerSection::new("A".to_string(), TimePosition::new(7 * BEAT / 4), TimePosition::new(15 * BEAT / 4)).unwrap();
+    let section_id = timeline.arrangement.add_section(section);
+    timeline.arrangement.set_playlist(vec![PlaylistEntry::new(section_id, 2)]).unwrap();
+    timeline.arrangement.enabled = true;
+
+    let content = |project: Project| -> Vec<(TimePosition, OutputEventType)> {
+        engine_for(project).render(TimePosition::zero(), TimePosition::new(9 * BEAT / 2), 1000).into_iter()
+            .filter(|rendered| rendered.event.target.is_some())
+            .map(|rendered| (rendered.position, rendered.event.event_type))
+            .collect()
+    };
+    let mut flattened = project.clone();
+    flattened.flatten_arrangement().unwrap();
+    let arranged = content(project);
+
+    assert_eq!(arranged.iter().filter(|(_, event)| matches!(event, OutputEventType::MidiNoteOn { .. })).count(), 8);
+    assert_eq!(content(flattened), arranged);
+}
+
+#[test]
+fn render_loops_over_the_cycle() {
+    let mut clip = MidiClip::new("Drone".to_string(), 4 * PPQ);
+    clip.add_note(MidiNote::new(0, 4 * PPQ, 40, 100));
+    clip.add_note(MidiNote::new(PPQ, PPQ, 43, 100));
+    let mut project = clip_project(clip, false);
+    let timeline = project.active_timeline_mut().unwrap();
+    timeline.tracks[0].chase_notes = true;
+    let output_id = timeline.tracks[0].output_id;
+    timeline.set_cycle(TimePosition::new(BEAT / 2), TimePosition::new(2 * BEAT)).unwrap();
+
+    // Two beats to the end of the cycle, a beat and a half round it, then one more beat
+    let events = engine_for(project).render(TimePosition::zero(), TimePosition::new(9 * BEAT / 2), 1000);
+    let played: Vec<_> = events.into_iter()
+        .filter(|rendered| rendered.event.target == output_id)
+        .map(|rendered| (rendered.position.position_ticks, rendered.event.event_type))
+        .collect();
+    assert_eq!(played, vec![
+        (0, on(40, 100)),
+        (BEAT, on(43, 100)),
+        // Held at the end of the cycle, so released there, then chased at its start
+        (2 * BEAT, off(40)),
+        (2 * BEAT, off(43)),
+        (BEAT / 2, on(40, 100)),
+        (BEAT, on(43, 100)),
+        (2 * BEAT, off(40)),
+        (2 * BEAT, off(43)),
+        (BEAT / 2, on(40, 100)),
+        (BEAT, on(43, 100)),
+    ]);
+}
+
+#[test]
+fn playback_loops_over_the_cycle() {
+    let mut project = Project::new("Cycle".to_string());
+    // A fifth of a second
+    let end = TimePosition::new(RATE / 5);
+    project.active_timeline_mut().unwrap().set_cycle(TimePosition::zero(), end).unwrap();
+    let mut engine = engine_for(project);
+
+    engine.play();
+    thread::sleep(Duration::from_millis(500));
+    let position = engine.current_position();
+    engine.stop();
+
+    assert!(position < end, "played on to {position:?}");
+}
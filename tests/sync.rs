@@ -0,0 +1,157 @@
+use loom::engine::SyncGenerator;
+use loom::model::{EndpointConfig, EndpointId, Project, SyncOutput};
+use loom::output::OutputEventType;
+use loom::tapestry::{FrameRate, TimeOffset, TimePosition, Timecode};
+
+const RATE: u64 = 44100;
+/// Ticks per beat at the default 120 BPM
+const BEAT: u64 = 22050;
+
+fn add_sync(project: &mut Project, midi_clock: bool, mtc: bool, offset: TimeOffset) -> EndpointId {
+    let sync = SyncOutput { midi_clock, mtc, offset };
+    project.add_endpoint(EndpointConfig::new_midi("Sync".to_string(), "none".to_string()).with_sync(sync))
+}
+
+/// Where each clock due from `from` to `to` goes out to `id`, in ticks
+fn clocks(sync: &mut SyncGenerator, project: &Project, id: EndpointId, from: u64, to: u64) -> Vec<u64> {
+    sync.advance(project, &TimePosition::new(from), &TimePosition::new(to)).into_iter()
+        .filter(|(_, event)| event.target == Some(id))
+        .inspect(|(_, event)| assert_eq!(event.event_type, OutputEventType::SyncPulse))
+        .map(|(position, _)| position.position_ticks)
+        .collect()
+}
+
+/// Tick of MIDI clock `index` at 120 BPM, to within rounding
+fn clock_at(index: u64) -> u64 {
+    (index as f64 * BEAT as f64 / 24.0).round() as u64
+}
+
+fn assert_near(ticks: u64, expected: u64) {
+    assert!(ticks.abs_diff(expected) <= 1, "{ticks} ticks, expected {expected}");
+}
+
+#[test]
+fn clocks_fall_every_twenty_fourth_of_a_beat_however_the_range_is_split() {
+    let mut project = Project::new("Sync".to_string());
+    let id = add_sync(&mut project, true, false, TimeOffset::zero());
+    let mut sync = SyncGenerator::new();
+
+    let start = sync.start(&project, TimePosition::zero());
+    assert_eq!(start.len(), 1);
+    assert_eq!(start[0].event_type, OutputEventType::SyncStart);
+
+    let whole = clocks(&mut sync, &project, id, 0, 2 * BEAT);
+    assert_eq!(whole.len(), 48);
+    for (index, ticks) in whole.iter().enumerate() {
+        assert_near(*ticks, clock_at(index as u64));
+    }
+
+    let mut sync = SyncGenerator::new();
+    sync.start(&project, TimePosition::zero());
+    let mut pieces = Vec::new();
+    let mut from = 0;
+    for to in (1..).map(|block| block * 777).take_while(|to| *to < 2 * BEAT).chain([2 * BEAT]) {
+        pieces.extend(clocks(&mut sync, &project, id, from, to));
+        from = to;
+    }
+    assert_eq!(pieces, whole);
+}
+
+#[test]
+fn each_endpoint_gets_its_sync_by_its_own_offset() {
+    let mut project = Project::new("Sync".to_string());
+    let on_time = add_sync(&mut project, true, false, TimeOffset::zero());
+    let early = add_sync(&mut project, true, false, TimeOffset::from_seconds(0.01, RATE as u32));
+    let late = add_sync(&mut project, true, false, TimeOffset::from_seconds(-0.01, RATE as u32));
+    let offset = RATE / 100;
+    let mut sync = SyncGenerator::new();
+    sync.start(&project, TimePosition::zero());
+
+    let events = sync.advance(&project, &TimePosition::new(BEAT), &TimePosition::new(2 * BEAT));
+    let sent_to = |id: EndpointId| -> Vec<u64> {
+        events.iter()
+            .filter(|(_, event)| event.target == Some(id))
+            .map(|(position, _)| position.position_ticks)
+            .collect()
+    };
+
+    // The playhead range covers clocks 24 to 47 on time. Half a clock later, the
+    // endpoint sent early is due clocks 25 to 48; half a clock earlier, the one sent
+    // late is still due 24 to 47
+    let expected = |first: u64, shift: i64| -> Vec<u64> {
+        (first..first + 24).map(|index| (clock_at(index) as i64 + shift) as u64).collect()
+    };
+    let check = |sent: Vec<u64>, expected: Vec<u64>| {
+        assert_eq!(sent.len(), expected.len());
+        for (ticks, expected) in sent.into_iter().zip(expected) {
+            assert_near(ticks, expected);
+        }
+    };
+    check(sent_to(on_time), expected(24, 0));
+    check(sent_to(early), expected(25, -(offset as i64)));
+    check(sent_to(late), expected(24, offset as i64));
+}
+
+#[test]
+fn clocks_resume_on_the_sixteenth_announced_after_a_relocation() {
+    let mut project = Project::new("Sync".to_string());
+    let id = add_sync(&mut project, true, false, TimeOffset::zero());
+    let mut sync = SyncGenerator::new();
+    sync.start(&project, TimePosition::zero());
+
+    // A tenth of a beat past the first beat: the slave picks up on the next sixteenth
+    let position = BEAT + BEAT / 10;
+    let locate = sync.locate(&project, TimePosition::new(position), true);
+    let types: Vec<_> = locate.iter().map(|event| event.event_type.clone()).collect();
+    assert_eq!(types, vec![
+        OutputEventType::SyncStop,
+        OutputEventType::SongPosition { sixteenths: 5 },
+        OutputEventType::SyncContinue,
+    ]);
+
+    let after = clocks(&mut sync, &project, id, position, 2 * BEAT);
+    assert_eq!(after.len(), 48 - 30);
+    assert_near(after[0], clock_at(30));
+
+    // Starting anywhere but the top continues from a song position instead
+    let start = sync.start(&project, TimePosition::new(position));
+    let types: Vec<_> = start.iter().map(|event| event.event_type.clone()).collect();
+    assert_eq!(types, vec![OutputEventType::SongPosition { sixteenths: 5 }, OutputEventType::SyncContinue]);
+}
+
+#[test]
+fn quarter_frames_follow_the_timecode_start() {
+    let mut project = Project::new("Sync".to_string());
+    // An odd frame, so the timeline starts half way through a quarter-frame sequence
+    project.settings.timecode_start = Timecode::new(1, 0, 0, 1, FrameRate::Fps25).unwrap();
+    let id = add_sync(&mut project, false, true, TimeOffset::zero());
+    let mut sync = SyncGenerator::new();
+
+    let start = sync.start(&project, TimePosition::zero());
+    assert_eq!(start.len(), 1);
+    assert_eq!(start[0].event_type, OutputEventType::MtcFullFrame { timecode: project.settings.timecode_start });
+
+    let events = sync.advance(&project, &TimePosition::zero(), &TimePosition::new(RATE));
+    let quarters: Vec<(u64, u8, Timecode)> = events.into_iter()
+        .filter(|(_, event)| event.target == Some(id))
+        .map(|(position, event)| match event.event_type {
+            OutputEventType::MtcQuarterFrame { piece, timecode } => (position.position_ticks, piece, timecode),
+            other => panic!("unexpected {other:?}"),
+        })
+        .collect();
+
+    // Four quarter frames a frame, 25 frames a second
+    assert_eq!(quarters.len(), 100);
+    let frame = |frames| Timecode::new(1, 0, 0, frames, FrameRate::Fps25).unwrap();
+    // Piece 4 of the sequence that started on the frame before the timeline
+    assert_eq!(quarters[0], (0, 4, frame(0)));
+    assert_eq!(quarters[3], (3 * RATE / 100, 7, frame(0)));
+    // Then a new sequence every two frames
+    assert_eq!(quarters[4], (4 * RATE / 100, 0, frame(2)));
+    assert_eq!(quarters[12], (12 * RATE / 100, 0, frame(4)));
+    assert_eq!(quarters.last().unwrap().1, 7);
+
+    // A jump sends the full frame for the new position
+    let locate = sync.locate(&project, TimePosition::new(2 * RATE), false);
+    assert_eq!(locate[0].event_type, OutputEventType::MtcFullFrame { timecode: Timecode::new(1, 0, 2, 1, FrameRate::Fps25).unwrap() });
+}
@@ -0,0 +1,142 @@
+use loom::tapestry::{MeterError, Tempo, TempoCurve, TempoMap, TimePosition, TimeSignature};
+
+const RATE: u32 = 44100;
+
+fn secs(seconds: u64) -> TimePosition {
+    TimePosition::new(seconds * RATE as u64)
+}
+
+/// A long map alternating steps and both kinds of ramp, like a film cue
+fn film_map() -> TempoMap {
+    let mut map = TempoMap::new(RATE, RATE);
+    for i in 0..300u64 {
+        let bpm = 60.0 + ((i * 37) % 120) as f64;
+        let curve = match i % 3 {
+            0 => TempoCurve::Step,
+            1 => TempoCurve::Linear,
+            _ => TempoCurve::Exponential,
+        };
+        map.add_tempo_ramp(TimePosition::new(i * 3 * RATE as u64 + i * 17), Tempo::new(bpm), curve);
+    }
+    map
+}
+
+#[test]
+fn positions_round_trip_through_beats() {
+    let map = film_map();
+    let end = 310 * 3 * RATE as u64;
+
+    for ticks in (0..end).step_by(4099) {
+        let position = TimePosition::new(ticks);
+        let beats = map.position_to_beats(&position);
+        assert_eq!(map.beats_to_position(beats), position, "beats {}", beats);
+    }
+}
+
+#[test]
+fn beats_are_monotonic_across_changes() {
+    let map = film_map();
+    let mut previous = -1.0;
+
+    for ticks in (0..300 * 3 * RATE as u64).step_by(1021) {
+        let beats = map.position_to_beats(&TimePosition::new(ticks));
+        assert!(beats > previous);
+        previous = beats;
+    }
+}
+
+#[test]
+fn beats_after_several_step_changes() {
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_tempo_change(secs(2), Tempo::new(60.0));
+    map.add_tempo_change(secs(6), Tempo::new(240.0));
+
+    // 4 beats at 120, 4 beats at 60, then 4 beats per second
+    assert_eq!(map.position_to_beats(&secs(6)), 8.0);
+    assert_eq!(map.beats_to_position(8.0), secs(6));
+    assert_eq!(map.beats_to_position(12.0), secs(7));
+    assert_eq!(map.beats_to_position(6.0), secs(4));
+}
+
+#[test]
+fn linear_ramp_integrates_exactly() {
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_tempo_ramp(TimePosition::zero(), Tempo::new(60.0), TempoCurve::Linear);
+    map.add_tempo_change(secs(10), Tempo::new(180.0));
+
+    // Average tempo of 120 BPM over ten seconds
+    assert!((map.position_to_beats(&secs(10)) - 20.0).abs() < 1e-9);
+    assert!((map.tempo_at(&secs(5)).bpm - 120.0).abs() < 1e-9);
+    assert_eq!(map.beats_to_position(20.0), secs(10));
+}
+
+#[test]
+fn exponential_ramp_doubles_over_its_span() {
+    let mut map = TempoMap::new(RATE, RATE);
+    map.add_tempo_ramp(TimePosition::zero(), Tempo::new(60.0), TempoCurve::Exponential);
+    map.add_tempo_change(secs(10), Tempo::new(120.0));
+
+    assert!((map.tempo_at(&secs(5)).bpm - 60.0 * 2f64.sqrt()).abs() < 1e-9);
+    let expected = 10.0 / 2f64.ln();
+    assert!((map.position_to_beats(&secs(10)) - expected).abs() < 1e-9);
+}
+
+#[test]
+fn bars_follow_time_signature_changes() {
+    let mut map = TempoMap::new(RATE, RATE);
+    // Two bars of 4/4 at 120 BPM take four seconds
+    map.add_time_signature_change(secs(4), TimeSignature::new(3, 4).unwrap());
+
+    assert_eq!(map.position_to_bars_and_beats(&secs(4)), (2, 0.0));
+    assert_eq!(map.position_to_bars_and_beats(&secs(7)), (4, 0.0));
+    assert_eq!(map.bars_and_beats_to_position(4, 0.0), secs(7));
+    assert_eq!(map.bars_and_beats_to_position(3, 1.0), TimePosition::new(6 * RATE as u64));
+}
+
+#[test]
+fn meter_change_mid_bar_starts_a_new_bar() {
+    let mut map = TempoMap::new(RATE, RATE);
+    // Six beats in: one full bar of 4/4 plus a bar cut short to two beats
+    map.add_time_signature_change(secs(3), TimeSignature::new(3, 4).unwrap());
+
+    assert_eq!(map.position_to_bars_and_beats(&secs(3)), (2, 0.0));
+    assert_eq!(map.bars_and_beats_to_position(1, 1.0), TimePosition::new(5 * RATE as u64 / 2));
+}
+
+#[test]
+fn eighth_note_meters_count_bars_in_quarter_notes() {
+    let mut map = TempoMap::new(RATE, RATE);
+    // A 6/8 bar is three quarter notes, 1.5 seconds at 120 BPM
+    map.add_time_signature_change(TimePosition::zero(), TimeSignature::new(6, 8).unwrap());
+    // Two bars in, a 7/8 bar is three and a half quarter notes
+    map.add_time_signature_change(secs(3), TimeSignature::new(7, 8).unwrap());
+
+    assert_eq!(map.position_to_bars_and_beats(&secs(3)), (2, 0.0));
+    assert_eq!(map.bars_and_beats_to_position(1, 0.0), TimePosition::new(3 * RATE as u64 / 2));
+    assert_eq!(map.bars_and_beats_to_position(3, 0.0), TimePosition::new(19 * RATE as u64 / 4));
+}
+
+#[test]
+fn bars_and_beats_round_trip() {
+    let mut map = film_map();
+    map.add_time_signature_change(secs(30), TimeSignature::new(7, 4).unwrap());
+    map.add_time_signature_change(secs(95), TimeSignature::new(5, 4).unwrap());
+
+    for ticks in (0..300 * 3 * RATE as u64).step_by(7919) {
+        let position = TimePosition::new(ticks);
+        let (bars, beat) = map.position_to_bars_and_beats(&position);
+        assert_eq!(map.bars_and_beats_to_position(bars, beat), position);
+    }
+}
+
+#[test]
+fn time_signatures_need_beats_and_a_real_beat_unit() {
+    assert_eq!(TimeSignature::new(0, 4), Err(MeterError::NoBeats));
+    assert_eq!(TimeSignature::new(4, 0), Err(MeterError::InvalidBeatUnit(0)));
+    assert_eq!(TimeSignature::new(3, 6), Err(MeterError::InvalidBeatUnit(6)));
+
+    let seven_eight = TimeSignature::new(7, 8).unwrap();
+    assert_eq!(seven_eight.grouping(), &[2, 2, 3]);
+    assert_eq!(TimeSignature::new(4, 4).unwrap(), TimeSignature::common_time());
+    assert!(TimeSignature::new(1, 1).is_ok());
+}
@@ -0,0 +1,72 @@
+use loom::tapestry::{FrameRate, TimePosition, Timecode, TimecodeError};
+
+const RATE: u32 = 48000;
+/// Frames in ten minutes of drop-frame timecode
+const TEN_MINUTES: u64 = 17982;
+
+fn df(minutes: u8, seconds: u8, frames: u8) -> Timecode {
+    Timecode::new(0, minutes, seconds, frames, FrameRate::Fps29_97Drop).unwrap()
+}
+
+#[test]
+fn drop_frame_skips_two_labels_each_minute_but_every_tenth() {
+    let label = |frames| Timecode::from_frame_count(frames, FrameRate::Fps29_97Drop);
+
+    // Into the first minute: ;00 and ;01 do not exist
+    assert_eq!(label(1799), df(0, 59, 29));
+    assert_eq!(label(1800), df(1, 0, 2));
+    assert_eq!(label(3597), df(1, 59, 29));
+    assert_eq!(label(3598), df(2, 0, 2));
+    // The tenth minute keeps them
+    assert_eq!(label(TEN_MINUTES - 1), df(9, 59, 29));
+    assert_eq!(label(TEN_MINUTES), df(10, 0, 0));
+    assert_eq!(label(TEN_MINUTES + 1), df(10, 0, 1));
+    assert_eq!(label(TEN_MINUTES + 1800), df(11, 0, 2));
+    // An hour of drop-frame is an hour of labels
+    assert_eq!(label(6 * TEN_MINUTES), Timecode::new(1, 0, 0, 0, FrameRate::Fps29_97Drop).unwrap());
+
+    assert_eq!(df(1, 0, 2).to_string(), "00:01:00;02");
+    assert_eq!(
+        Timecode::new(0, 1, 0, 1, FrameRate::Fps29_97Drop),
+        Err(TimecodeError::DroppedFrame { minutes: 1, frames: 1 }),
+    );
+    assert!(Timecode::new(0, 1, 0, 0, FrameRate::Fps29_97NonDrop).is_ok());
+}
+
+#[test]
+fn frame_counts_round_trip_through_labels() {
+    for rate in [FrameRate::Fps29_97Drop, FrameRate::Fps29_97NonDrop, FrameRate::Fps25, FrameRate::Fps23_976] {
+        let mut previous: Option<Timecode> = None;
+        // Past the first ten-minute block and across the hour
+        for frames in (0..2 * TEN_MINUTES).chain(6 * TEN_MINUTES - 2000..6 * TEN_MINUTES + 2000) {
+            let timecode = Timecode::from_frame_count(frames, rate);
+            assert_eq!(timecode.frame_count(), frames, "{timecode} at {rate}");
+
+            let rebuilt = Timecode::new(timecode.hours, timecode.minutes, timecode.seconds, timecode.frames, rate);
+            assert_eq!(rebuilt, Ok(timecode));
+            if let Some(previous) = previous.filter(|previous| previous.frame_count() + 1 == frames) {
+                assert!(timecode.to_string() > previous.to_string(), "{timecode} after {previous}");
+            }
+            previous = Some(timecode);
+        }
+    }
+}
+
+#[test]
+fn positions_convert_from_a_timecode_start() {
+    let start = Timecode::new(0, 59, 59, 28, FrameRate::Fps29_97Drop).unwrap();
+
+    // The two frames up to the hour, which is a tenth minute and keeps its first labels
+    let one_frame = TimePosition::new(1001 * RATE as u64 / 30000 + 1);
+    assert_eq!(Timecode::from_position(&TimePosition::zero(), &start, RATE), start);
+    assert_eq!(Timecode::from_position(&one_frame, &start, RATE).frames, 29);
+    let hour = Timecode::new(1, 0, 0, 0, FrameRate::Fps29_97Drop).unwrap();
+    let at_hour = hour.to_position(&start, RATE);
+    assert_eq!(Timecode::from_position(&at_hour, &start, RATE), hour);
+
+    // A label before the start is on the next day
+    let earlier = Timecode::new(0, 59, 59, 27, FrameRate::Fps29_97Drop).unwrap();
+    let next_day = earlier.to_position(&start, RATE);
+    assert_eq!(Timecode::from_position(&next_day, &start, RATE), earlier);
+    assert!(next_day > at_hour);
+}
@@ -0,0 +1,175 @@
+use loom::model::{ArrangerSection, MediaContainer, MediaContent, MidiClip, PlaylistEntry, PreserveMode, Project, Timebase, Track, TrackType};
+use loom::tapestry::{NoteValue, Tempo, TempoCurve, TimePosition, TimeSignature, DEFAULT_PPQ};
+
+const PPQ: u64 = DEFAULT_PPQ as u64;
+/// Ticks per beat at the default 120 BPM
+const BEAT: u64 = 22050;
+
+#[test]
+fn containers_meeting_after_a_retime_are_both_kept() {
+    let mut project = Project::new("Retime".to_string());
+    let clip_id = project.add_midi_clip(MidiClip::new("Clip".to_string(), 4 * PPQ));
+    let content = || MediaContent::MidiClip(clip_id);
+    let beat_locked = MediaContainer::new(TimePosition::new(4 * BEAT), content(), &project.tempo_map);
+    let time_locked = MediaContainer::new(TimePosition::new(2 * BEAT), content(), &project.tempo_map)
+        .with_timebase(Timebase::Time);
+    let ids = [beat_locked.id, time_locked.id];
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    timeline.add_container(track_id, beat_locked);
+    timeline.add_container(track_id, time_locked);
+
+    // Twice as fast: beat 4 comes where beat 2 was
+    project.update_tempo_map(PreserveMode::FollowTimebase, |map| {
+        map.add_tempo_change(TimePosition::zero(), Tempo::new(240.0));
+        Ok(())
+    }).unwrap();
+
+    let timeline = project.active_timeline().unwrap();
+    let on_track: Vec<_> = timeline.track_containers[&track_id].iter().collect();
+    assert_eq!(on_track.len(), 2);
+    assert!(on_track.iter().all(|(position, _)| *position == TimePosition::new(2 * BEAT)));
+    for id in ids {
+        assert!(on_track.iter().any(|(_, container_id)| *container_id == id));
+    }
+}
+
+#[test]
+fn musical_preserve_keeps_bar_and_beat_across_a_meter_change() {
+    let mut project = Project::new("Meter".to_string());
+    let clip_id = project.add_midi_clip(MidiClip::new("Clip".to_string(), PPQ));
+    // Bar 3, beat 2 in 4/4
+    let container = MediaContainer::new(TimePosition::new(9 * BEAT), MediaContent::MidiClip(clip_id), &project.tempo_map)
+        .with_timebase(Timebase::Time);
+    let container_id = container.id;
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    timeline.add_container(track_id, container);
+
+    let moved = project.update_tempo_map(PreserveMode::Musical, |map| {
+        map.add_time_signature_change(TimePosition::zero(), TimeSignature::new(3, 4).unwrap());
+        Ok(())
+    }).unwrap();
+
+    // Bar 3, beat 2 in 3/4
+    assert_eq!(moved, vec![(container_id, TimePosition::new(7 * BEAT))]);
+    let timeline = project.active_timeline().unwrap();
+    assert_eq!(timeline.containers[&container_id].position, TimePosition::new(7 * BEAT));
+}
+
+#[test]
+fn musical_lengths_stay_exact_through_resizes_and_moves() {
+    let mut project = Project::new("Resize".to_string());
+    project.tempo_map.add_tempo_change(TimePosition::new(4 * BEAT), Tempo::new(90.0));
+    let tempo_map = project.tempo_map.clone();
+    let clip_id = project.add_midi_clip(MidiClip::new("Clip".to_string(), 4 * PPQ));
+    let container = MediaContainer::new(TimePosition::zero(), MediaContent::MidiClip(clip_id), &tempo_map);
+    let container_id = container.id;
+    // Seven in the time of four quarters: no whole number of ticks at any common PPQ
+    let septuplet = NoteValue::QUARTER.tuplet(7, 4);
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    timeline.add_container(track_id, container);
+
+    let container = timeline.container_mut(container_id).unwrap();
+    container.set_musical_length(septuplet, &tempo_map);
+    // Handing back the length it already has changes nothing
+    let length = container.length;
+    container.set_length(length, &tempo_map);
+    assert_eq!(container.musical_length, Some(septuplet));
+
+    // Across the tempo change the length on the timeline follows the exact value
+    let position = TimePosition::new(6 * BEAT);
+    assert!(timeline.move_container(container_id, position));
+    let container = timeline.container_mut(container_id).unwrap();
+    container.refresh_length(&tempo_map);
+    assert_eq!(container.musical_length, Some(septuplet));
+    assert_eq!(container.length, tempo_map.note_value_to_duration(&position, septuplet));
+}
+
+/// Two sections of the active timeline, played once each in the given order
+fn arrange(project: &mut Project, sections: [(u64, u64); 2], order: [usize; 2]) {
+    let timeline = project.active_timeline_mut().unwrap();
+    let ids: Vec<_> = sections.iter()
+        .map(|(start, end)| ArrangerSection::new("Bar".to_string(), TimePosition::new(*start), TimePosition::new(*end)).unwrap())
+        .map(|section| timeline.arrangement.add_section(section))
+        .collect();
+    timeline.arrangement.set_playlist(order.iter().map(|index| PlaylistEntry::new(ids[*index], 1)).collect()).unwrap();
+    timeline.arrangement.enabled = true;
+}
+
+#[test]
+fn flattening_lays_the_tempo_map_out_in_song_order() {
+    // A bar at 120 BPM, then a bar at 60 BPM
+    let mut project = Project::new("Flatten".to_string());
+    project.tempo_map.add_tempo_change(TimePosition::new(4 * BEAT), Tempo::new(60.0));
+    let tempo_map = project.tempo_map.clone();
+    let clip_id = project.add_midi_clip(MidiClip::new("Clip".to_string(), 2 * PPQ));
+    let half_note = |beat_ticks: u64| MediaContainer::new(TimePosition::new(beat_ticks), MediaContent::MidiClip(clip_id), &tempo_map)
+        .with_musical_length(NoteValue::HALF, &tempo_map);
+
+    let timeline = project.active_timeline_mut().unwrap();
+    let track_id = timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    // On beat 1, across the tempo change from beat 3, and on beat 5
+    for position in [BEAT, 3 * BEAT, 6 * BEAT] {
+        timeline.add_container(track_id, half_note(position));
+    }
+    let other_id = project.add_timeline("Other".to_string());
+    let other = half_note(2 * BEAT);
+    let other_container = other.id;
+    let other_timeline = project.timelines.get_mut(&other_id).unwrap();
+    let other_track = other_timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+    other_timeline.add_container(other_track, other);
+
+    // The slow bar first
+    arrange(&mut project, [(0, 4 * BEAT), (4 * BEAT, 12 * BEAT)], [1, 0]);
+    project.flatten_arrangement().unwrap();
+
+    let tempos: Vec<_> = project.tempo_map.tempo_changes().map(|(position, change)| (position.position_ticks, change.tempo.bpm)).collect();
+    assert_eq!(tempos, vec![(0, 60.0), (8 * BEAT, 120.0)]);
+
+    // (position, length, musical length, start offset), a beat being two BEATs in the slow bar
+    let timeline = project.active_timeline().unwrap();
+    let mut copies: Vec<_> = timeline.containers.values()
+        .map(|container| (container.position.position_ticks, container.length.ticks(), container.musical_length, container.start_offset.ticks()))
+        .collect();
+    copies.sort_by_key(|copy| copy.0);
+    assert_eq!(copies, vec![
+        // The tail of the note across the tempo change, a beat into its content
+        (0, 2 * BEAT, Some(NoteValue::QUARTER), 2 * BEAT),
+        (2 * BEAT, 4 * BEAT, Some(NoteValue::HALF), 0),
+        (9 * BEAT, 2 * BEAT, Some(NoteValue::HALF), 0),
+        // Cut short at the end of the song
+        (11 * BEAT, BEAT, Some(NoteValue::QUARTER), 0),
+    ]);
+
+    // Beat 2 on another timeline is now at the slow tempo
+    assert_eq!(project.timelines[&other_id].containers[&other_container].position, TimePosition::new(4 * BEAT));
+}
+
+#[test]
+fn flattening_refuses_a_ramp_cut_off_by_another_tempo() {
+    // Slowing from 120 to 60 BPM over two bars
+    let mut project = Project::new("Ramp".to_string());
+    project.tempo_map.add_tempo_ramp(TimePosition::zero(), Tempo::new(120.0), TempoCurve::Linear);
+    project.tempo_map.add_tempo_change(TimePosition::new(8 * BEAT), Tempo::new(60.0));
+    let timeline = project.active_timeline_mut().unwrap();
+    timeline.add_track(Track::new("Keys".to_string(), TrackType::Midi));
+
+    // The first bar ends part way through the ramp, where the second bar picks it up
+    let mut in_order = project.clone();
+    arrange(&mut in_order, [(0, 4 * BEAT), (4 * BEAT, 8 * BEAT)], [0, 1]);
+    in_order.flatten_arrangement().unwrap();
+    for ticks in [0, 3 * BEAT, 6 * BEAT, 8 * BEAT] {
+        let position = TimePosition::new(ticks);
+        assert_eq!(in_order.tempo_map.tempo_at(&position), project.tempo_map.tempo_at(&position));
+    }
+
+    // Played the other way round the ramp would head for 120 BPM at the end of the second bar
+    arrange(&mut project, [(0, 4 * BEAT), (4 * BEAT, 8 * BEAT)], [1, 0]);
+    assert_eq!(project.flatten_arrangement(), Err("A tempo ramp runs across the end of a section"));
+    assert!(project.active_timeline().unwrap().arrangement.is_active());
+}